@@ -130,7 +130,7 @@ pub use struct_log::{
 
 mod text_log;
 pub use log::Level;
-pub use text_log::{Logger, CHANNEL_SIZE, DEFAULT_TARGET};
+pub use text_log::{set_runtime_level, Logger, CHANNEL_SIZE, DEFAULT_TARGET};
 
 /// Define crit macro that specify libra as the target
 // TODO Remove historical crit from code base since it isn't supported in Rust Log.
@@ -3,12 +3,15 @@
 
 use chrono::Utc;
 use env_logger::filter;
-use log::{self, Level, Log, Metadata, Record};
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
 
 use std::{
     env, fmt,
     fmt::Write,
-    sync::mpsc::{self, Receiver, RecvError, SyncSender, TrySendError},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvError, SyncSender, TrySendError},
+    },
     thread,
 };
 
@@ -16,6 +19,55 @@ pub const CHANNEL_SIZE: usize = 256;
 pub const DEFAULT_TARGET: &str = "libra";
 const RUST_LOG: &str = "RUST_LOG";
 
+/// The level set by set_runtime_level, 0 if there is none.
+static RUNTIME_LEVEL: AtomicUsize = AtomicUsize::new(0);
+/// The maximum level of the filter the logger was initialized with.
+static INITIAL_MAX_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+/// Overrides the level of the logger at runtime, e.g., to raise the verbosity of a running service
+/// while debugging it. While set, the level replaces the filter the logger was initialized with,
+/// including any per-module directives from RUST_LOG. Passing None restores that filter.
+pub fn set_runtime_level(level: Option<Level>) {
+    RUNTIME_LEVEL.store(level.map_or(0, |level| level as usize), Ordering::Relaxed);
+    let max_level = match level {
+        Some(level) => level.to_level_filter(),
+        None => level_filter(INITIAL_MAX_LEVEL.load(Ordering::Relaxed)),
+    };
+    log::set_max_level(max_level);
+}
+
+fn level_filter(level: usize) -> LevelFilter {
+    match level {
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        5 => LevelFilter::Trace,
+        _ => LevelFilter::Off,
+    }
+}
+
+fn runtime_level() -> Option<LevelFilter> {
+    match RUNTIME_LEVEL.load(Ordering::Relaxed) {
+        0 => None,
+        level => Some(level_filter(level)),
+    }
+}
+
+fn enabled(filter: &filter::Filter, metadata: &Metadata) -> bool {
+    match runtime_level() {
+        Some(level) => metadata.level() <= level,
+        None => filter.enabled(metadata),
+    }
+}
+
+fn matches(filter: &filter::Filter, record: &Record) -> bool {
+    match runtime_level() {
+        Some(level) => record.level() <= level,
+        None => filter.matches(record),
+    }
+}
+
 /// Logging framework for Libra that encapsulates a minimal dependency logger with support for
 /// environmental variable (RUST_LOG) and asynchronous logging.
 /// Note: only a single logger can be instantiated at a time. Repeated instantiates of the loggers
@@ -94,7 +146,8 @@ impl Logger {
 
         let filter = filter_builder.build();
         // Even if there is an existing logger, update the logging level
-        log::set_max_level(filter.filter());
+        INITIAL_MAX_LEVEL.store(filter.filter() as usize, Ordering::Relaxed);
+        log::set_max_level(runtime_level().unwrap_or_else(|| filter.filter()));
 
         if self.is_async {
             let (sender, receiver) = mpsc::sync_channel(self.channel_size);
@@ -133,7 +186,7 @@ struct SyncLogger<W> {
 impl<W: Writer> Log for SyncLogger<W> {
     /// Determines if a log message with the specified metadata would be logged.
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.filter.enabled(metadata)
+        enabled(&self.filter, metadata)
     }
 
     /// Logs the provided record but first evaluates the filters and then writes it.
@@ -143,7 +196,7 @@ impl<W: Writer> Log for SyncLogger<W> {
             return;
         }
 
-        if !self.filter.matches(record) {
+        if !matches(&self.filter, record) {
             return;
         }
 
@@ -201,7 +254,7 @@ struct AsyncLogClient {
 impl Log for AsyncLogClient {
     /// Determines if a log message with the specified metadata would be logged.
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.filter.enabled(metadata)
+        enabled(&self.filter, metadata)
     }
 
     /// Logs the provided record but first evaluates the filters and then sending it to the
@@ -212,7 +265,7 @@ impl Log for AsyncLogClient {
             return;
         }
 
-        if !self.filter.matches(record) {
+        if !matches(&self.filter, record) {
             return;
         }
 
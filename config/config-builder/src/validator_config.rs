@@ -6,8 +6,8 @@ use anyhow::{ensure, format_err, Result};
 use executor::db_bootstrapper;
 use libra_config::{
     config::{
-        ConsensusType, NodeConfig, RemoteService, SafetyRulesService, SecureBackend, Token,
-        VaultConfig,
+        ConsensusType, NodeConfig, RemoteService, RequestQueueConfig, SafetyRulesService,
+        SecureBackend, Token, VaultConfig,
     },
     generator,
 };
@@ -255,6 +255,9 @@ impl ValidatorConfig {
             safety_rules_config.service = SafetyRulesService::Process(RemoteService {
                 server_address,
                 consensus_type: ConsensusType::SignedTransactions,
+                authentication: None,
+                permissions: Vec::new(),
+                request_queue: RequestQueueConfig::default(),
             })
         }
 
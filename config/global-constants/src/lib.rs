@@ -12,12 +12,25 @@
 pub const ASSOCIATION_KEY: &str = "association";
 pub const CONSENSUS_KEY: &str = "consensus";
 pub const FULLNODE_NETWORK_KEY: &str = "fullnode_network";
+pub const NEXT_CONSENSUS_KEY: &str = "next_consensus";
 pub const OPERATOR_KEY: &str = "operator";
 pub const OWNER_KEY: &str = "owner";
+pub const SAFETY_DATA_KEY: &str = "safety_data";
 pub const VALIDATOR_NETWORK_KEY: &str = "validator_network";
 
 /// Definitions of global data items (e.g., as held in secure storage)
+pub const CHAIN_ID: &str = "chain_id";
+pub const CONSENSUS_KEY_ENDORSEMENT: &str = "consensus_endorsement";
 pub const EPOCH: &str = "epoch";
+pub const EPOCH_ENDING_LEDGER_INFO: &str = "epoch_ending_ledger_info";
+pub const HIGHEST_PROPOSED_ROUND: &str = "highest_proposed_round";
+pub const LAST_PROPOSAL: &str = "last_proposal";
 pub const LAST_VOTED_ROUND: &str = "last_voted_round";
+pub const NEXT_CONSENSUS_KEY_ENDORSEMENT: &str = "next_consensus_endorsement";
 pub const PREFERRED_ROUND: &str = "preferred_round";
+pub const RECOVERY_SLOT: &str = "recovery_slot";
+pub const SAFETY_DATA_SIGNATURE: &str = "safety_data_signature";
+pub const SIGNATURE_COUNTS: &str = "signature_counts";
+pub const SIGNER_LEASE: &str = "signer_lease";
 pub const WAYPOINT: &str = "waypoint";
+pub const WAYPOINT_HISTORY: &str = "waypoint_history";
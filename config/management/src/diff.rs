@@ -0,0 +1,264 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{error::Error, secure_backend::SecureBackend};
+use libra_crypto::HashValue;
+use libra_global_constants::{
+    CHAIN_ID, CONSENSUS_KEY, EPOCH, HIGHEST_PROPOSED_ROUND, LAST_PROPOSAL, LAST_VOTED_ROUND,
+    NEXT_CONSENSUS_KEY, PREFERRED_ROUND, SAFETY_DATA_KEY, SAFETY_DATA_SIGNATURE, SIGNATURE_COUNTS,
+    WAYPOINT, WAYPOINT_HISTORY,
+};
+use libra_secure_storage::Storage;
+use libra_types::waypoint::Waypoint;
+use std::{cmp::Ordering, convert::TryInto, fmt::Write, str::FromStr};
+use structopt::StructOpt;
+
+/// Compares the SafetyRules state of two stores field by field, e.g., before moving a validator
+/// from one storage backend to another. The migration is only blessed if the target holds the same
+/// keys and is equal to or ahead of the source in every field, a target that is behind would let
+/// SafetyRules vote again in rounds it already voted in. The counter-signature of the SafetyData
+/// is compared as well, so that a target that would fail its integrity check is not blessed.
+#[derive(Debug, StructOpt)]
+pub struct Diff {
+    /// The secure backend the state is migrated from. See the verify command for the format of
+    /// secure backends.
+    #[structopt(long)]
+    source: SecureBackend,
+    /// The secure backend the state is migrated to.
+    #[structopt(long)]
+    target: SecureBackend,
+}
+
+impl Diff {
+    pub fn execute(self) -> Result<String, Error> {
+        let source: Box<dyn Storage> = self.source.try_into()?;
+        source
+            .available()
+            .map_err(|e| Error::LocalStorageUnavailable(e.to_string()))?;
+        let target: Box<dyn Storage> = self.target.try_into()?;
+        target
+            .available()
+            .map_err(|e| Error::RemoteStorageUnavailable(e.to_string()))?;
+        compare(source.as_ref(), target.as_ref())
+    }
+}
+
+/// How a field of the target compares to the same field of the source.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Status {
+    Equal,
+    Ahead,
+    Behind,
+    Differs,
+}
+
+impl Status {
+    fn blessed(self) -> bool {
+        self == Status::Equal || self == Status::Ahead
+    }
+}
+
+/// A field as read from a store, or the reason it could not be read.
+type Field<T> = Result<T, String>;
+
+struct Report {
+    buffer: String,
+    blessed: bool,
+}
+
+impl Report {
+    fn record<T: ToString>(
+        &mut self,
+        key: &str,
+        source: &Field<T>,
+        target: &Field<T>,
+        status: Status,
+    ) {
+        self.blessed &= status.blessed();
+        let show = |field: &Field<T>| field.as_ref().map_or_else(|e| e.clone(), |v| v.to_string());
+        let status = match status {
+            Status::Equal => "equal",
+            Status::Ahead => "ahead",
+            Status::Behind => "BEHIND",
+            Status::Differs => "DIFFERS",
+        };
+        writeln!(
+            self.buffer,
+            "{} - source: {}, target: {} - {}",
+            key,
+            show(source),
+            show(target),
+            status
+        )
+        .unwrap();
+    }
+}
+
+/// Fields that are not set in the source may be anything in the target, fields that are set in the
+/// source must be set in the target.
+fn compare_fields<T>(
+    source: &Field<T>,
+    target: &Field<T>,
+    compare: impl Fn(&T, &T) -> Status,
+) -> Status {
+    match (source, target) {
+        (Ok(source), Ok(target)) => compare(source, target),
+        (Ok(_), Err(_)) => Status::Behind,
+        (Err(_), _) => Status::Equal,
+    }
+}
+
+fn order<T: Ord>(source: &T, target: &T) -> Status {
+    match target.cmp(source) {
+        Ordering::Less => Status::Behind,
+        Ordering::Equal => Status::Equal,
+        Ordering::Greater => Status::Ahead,
+    }
+}
+
+fn equality<T: Eq>(source: &T, target: &T) -> Status {
+    if source == target {
+        Status::Equal
+    } else {
+        Status::Differs
+    }
+}
+
+fn compare(source: &dyn Storage, target: &dyn Storage) -> Result<String, Error> {
+    let mut report = Report {
+        buffer: String::new(),
+        blessed: true,
+    };
+
+    writeln!(report.buffer, "Keys").unwrap();
+    for key in &[CONSENSUS_KEY, NEXT_CONSENSUS_KEY, SAFETY_DATA_KEY] {
+        let public_key = |storage: &dyn Storage| {
+            storage
+                .get_public_key(key)
+                .map(|response| response.public_key)
+                .map_err(|e| e.to_string())
+        };
+        let (source, target) = (public_key(source), public_key(target));
+        let status = compare_fields(&source, &target, equality);
+        report.record(key, &source, &target, status);
+    }
+
+    writeln!(report.buffer, "Data").unwrap();
+    let chain_id = |storage: &dyn Storage| {
+        storage
+            .get(CHAIN_ID)
+            .and_then(|response| response.value.string())
+            .map_err(|e| e.to_string())
+    };
+    let (source_chain_id, target_chain_id) = (chain_id(source), chain_id(target));
+    let status = compare_fields(&source_chain_id, &target_chain_id, equality);
+    report.record(CHAIN_ID, &source_chain_id, &target_chain_id, status);
+
+    let waypoint = |storage: &dyn Storage| {
+        storage
+            .get(WAYPOINT)
+            .and_then(|response| response.value.string())
+            .map_err(|e| e.to_string())
+            .and_then(|value| Waypoint::from_str(&value).map_err(|e| e.to_string()))
+    };
+    let (source_waypoint, target_waypoint) = (waypoint(source), waypoint(target));
+    let status = compare_fields(
+        &source_waypoint,
+        &target_waypoint,
+        |source, target| match order(&source.version(), &target.version()) {
+            Status::Equal => equality(source, target),
+            status => status,
+        },
+    );
+    report.record(WAYPOINT, &source_waypoint, &target_waypoint, status);
+
+    let u64_field = |storage: &dyn Storage, key: &str| {
+        storage
+            .get(key)
+            .and_then(|response| response.value.u64())
+            .map_err(|e| e.to_string())
+    };
+    let (source_epoch, target_epoch) = (u64_field(source, EPOCH), u64_field(target, EPOCH));
+    let epoch_status = compare_fields(&source_epoch, &target_epoch, order);
+    report.record(EPOCH, &source_epoch, &target_epoch, epoch_status);
+
+    // The rounds restart with every epoch, they are only compared within the same epoch
+    for key in &[HIGHEST_PROPOSED_ROUND, LAST_VOTED_ROUND, PREFERRED_ROUND] {
+        let (source_round, target_round) = (u64_field(source, key), u64_field(target, key));
+        let status = match epoch_status {
+            Status::Equal => compare_fields(&source_round, &target_round, order),
+            status => status,
+        };
+        report.record(key, &source_round, &target_round, status);
+
+        if *key == HIGHEST_PROPOSED_ROUND
+            && epoch_status == Status::Equal
+            && status == Status::Equal
+        {
+            // The same round must have seen the same proposal, otherwise the target could sign
+            // another proposal for it
+            let last_proposal = |storage: &dyn Storage| {
+                storage
+                    .get(LAST_PROPOSAL)
+                    .and_then(|response| response.value.hash_value())
+                    .map_err(|e| e.to_string())
+            };
+            let (source_proposal, target_proposal) = (last_proposal(source), last_proposal(target));
+            let status = compare_fields(&source_proposal, &target_proposal, equality);
+            report.record(LAST_PROPOSAL, &source_proposal, &target_proposal, status);
+        }
+    }
+
+    writeln!(report.buffer, "Counter-signed data").unwrap();
+    let string_field = |storage: &dyn Storage, key: &str| {
+        storage
+            .get(key)
+            .and_then(|response| response.value.string())
+            .map_err(|e| e.to_string())
+    };
+    // The signature is stored as counter:signature, a target that has signed more writes is ahead
+    let counter = |signature: &String| {
+        signature
+            .splitn(2, ':')
+            .next()
+            .and_then(|counter| counter.parse::<u64>().ok())
+    };
+    let (source_signature, target_signature) = (
+        string_field(source, SAFETY_DATA_SIGNATURE),
+        string_field(target, SAFETY_DATA_SIGNATURE),
+    );
+    let signature_status = compare_fields(
+        &source_signature,
+        &target_signature,
+        |source, target| match order(&counter(source), &counter(target)) {
+            Status::Equal => equality(source, target),
+            status => status,
+        },
+    );
+    report.record(
+        SAFETY_DATA_SIGNATURE,
+        &source_signature,
+        &target_signature,
+        signature_status,
+    );
+
+    // These are only compared under the same signature, they are shown by their hash for brevity
+    for key in &[SIGNATURE_COUNTS, WAYPOINT_HISTORY] {
+        let digest = |storage: &dyn Storage| {
+            string_field(storage, key).map(|value| HashValue::from_sha3_256(value.as_bytes()))
+        };
+        let (source_value, target_value) = (digest(source), digest(target));
+        let status = match signature_status {
+            Status::Equal => compare_fields(&source_value, &target_value, equality),
+            status => status,
+        };
+        report.record(key, &source_value, &target_value, status);
+    }
+
+    if report.blessed {
+        writeln!(report.buffer, "Migration blessed").unwrap();
+        Ok(report.buffer)
+    } else {
+        Err(Error::MigrationRefused(report.buffer))
+    }
+}
@@ -3,6 +3,7 @@
 
 #![forbid(unsafe_code)]
 
+mod diff;
 mod error;
 mod genesis;
 mod key;
@@ -42,6 +43,8 @@ pub enum Command {
     AssociationKey(crate::key::AssociationKey),
     #[structopt(about = "Create a waypoint and optionally place it in a store")]
     CreateWaypoint(crate::waypoint::CreateWaypoint),
+    #[structopt(about = "Compares the SafetyRules state of two stores ahead of a migration")]
+    Diff(crate::diff::Diff),
     #[structopt(about = "Retrieves data from a store to produce genesis")]
     Genesis(crate::genesis::Genesis),
     #[structopt(about = "Submits an Ed25519PublicKey for the operator")]
@@ -60,6 +63,7 @@ pub enum Command {
 pub enum CommandName {
     AssociationKey,
     CreateWaypoint,
+    Diff,
     Genesis,
     OperatorKey,
     OwnerKey,
@@ -73,6 +77,7 @@ impl From<&Command> for CommandName {
         match command {
             Command::AssociationKey(_) => CommandName::AssociationKey,
             Command::CreateWaypoint(_) => CommandName::CreateWaypoint,
+            Command::Diff(_) => CommandName::Diff,
             Command::Genesis(_) => CommandName::Genesis,
             Command::OperatorKey(_) => CommandName::OperatorKey,
             Command::OwnerKey(_) => CommandName::OwnerKey,
@@ -88,6 +93,7 @@ impl std::fmt::Display for CommandName {
         let name = match self {
             CommandName::AssociationKey => "association-key",
             CommandName::CreateWaypoint => "create-waypoint",
+            CommandName::Diff => "diff",
             CommandName::Genesis => "genesis",
             CommandName::OperatorKey => "operator-key",
             CommandName::OwnerKey => "owner-key",
@@ -104,6 +110,7 @@ impl Command {
         match &self {
             Command::AssociationKey(_) => self.association_key().unwrap().to_string(),
             Command::CreateWaypoint(_) => self.create_waypoint().unwrap().to_string(),
+            Command::Diff(_) => self.diff().unwrap(),
             Command::Genesis(_) => format!("{:?}", self.genesis().unwrap()),
             Command::OperatorKey(_) => self.operator_key().unwrap().to_string(),
             Command::OwnerKey(_) => self.owner_key().unwrap().to_string(),
@@ -135,6 +142,17 @@ impl Command {
         }
     }
 
+    pub fn diff(self) -> Result<String, Error> {
+        if let Command::Diff(diff) = self {
+            diff.execute()
+        } else {
+            Err(Error::UnexpectedCommand(
+                CommandName::Diff,
+                CommandName::from(&self),
+            ))
+        }
+    }
+
     pub fn genesis(self) -> Result<Transaction, Error> {
         if let Command::Genesis(genesis) = self {
             genesis.execute()
@@ -330,6 +348,89 @@ pub mod tests {
         assert!(!contents.is_empty());
     }
 
+    #[test]
+    fn test_diff() {
+        use libra_global_constants::{
+            CONSENSUS_KEY, EPOCH, HIGHEST_PROPOSED_ROUND, LAST_PROPOSAL, LAST_VOTED_ROUND,
+            PREFERRED_ROUND, SAFETY_DATA_SIGNATURE, SIGNATURE_COUNTS, WAYPOINT,
+        };
+        use libra_secure_storage::Value;
+
+        let helper = StorageHelper::new();
+        let source_ns = "diff_source";
+        let target_ns = "diff_target";
+
+        helper.initialize(source_ns.into());
+        let mut source = helper.storage(source_ns.into());
+        let mut target = helper.storage(target_ns.into());
+        helper.diff(source_ns, target_ns).unwrap_err();
+
+        // Migrate the consensus key and the safety data
+        let consensus_key = source.export_private_key(CONSENSUS_KEY).unwrap();
+        target
+            .set(CONSENSUS_KEY, Value::Ed25519PrivateKey(consensus_key))
+            .unwrap();
+        for key in &[
+            EPOCH,
+            HIGHEST_PROPOSED_ROUND,
+            LAST_PROPOSAL,
+            LAST_VOTED_ROUND,
+            PREFERRED_ROUND,
+            WAYPOINT,
+        ] {
+            target.set(key, source.get(key).unwrap().value).unwrap();
+        }
+        assert!(helper
+            .diff(source_ns, target_ns)
+            .unwrap()
+            .contains("Migration blessed"));
+
+        // A target ahead of the source is blessed, one behind it is not
+        target.set(LAST_VOTED_ROUND, Value::U64(2)).unwrap();
+        helper.diff(source_ns, target_ns).unwrap();
+        source.set(LAST_VOTED_ROUND, Value::U64(3)).unwrap();
+        match helper.diff(source_ns, target_ns) {
+            Err(Error::MigrationRefused(report)) => {
+                assert!(report.contains("last_voted_round - source: 3, target: 2 - BEHIND"))
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // Rounds restart in a later epoch
+        target.set(EPOCH, Value::U64(1)).unwrap();
+        target.set(LAST_VOTED_ROUND, Value::U64(0)).unwrap();
+        helper.diff(source_ns, target_ns).unwrap();
+
+        // A target without the counter-signature of the source would fail its integrity check
+        source
+            .set(SAFETY_DATA_SIGNATURE, Value::String("7:signature".into()))
+            .unwrap();
+        source
+            .set(SIGNATURE_COUNTS, Value::String("counts".into()))
+            .unwrap();
+        helper.diff(source_ns, target_ns).unwrap_err();
+        for key in &[SAFETY_DATA_SIGNATURE, SIGNATURE_COUNTS] {
+            target.set(key, source.get(key).unwrap().value).unwrap();
+        }
+        helper.diff(source_ns, target_ns).unwrap();
+
+        // Under the same signature, the signed values must be equal
+        target
+            .set(SIGNATURE_COUNTS, Value::String("other counts".into()))
+            .unwrap();
+        match helper.diff(source_ns, target_ns) {
+            Err(Error::MigrationRefused(report)) => {
+                assert!(report.contains("signature_counts - source: "));
+                assert!(report.contains("DIFFERS"));
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        target
+            .set(SAFETY_DATA_SIGNATURE, Value::String("8:signature".into()))
+            .unwrap();
+        helper.diff(source_ns, target_ns).unwrap();
+    }
+
     #[test]
     fn test_set_layout() {
         let helper = StorageHelper::new();
@@ -134,7 +134,8 @@ impl TryInto<Box<dyn Storage>> for SecureBackend {
 
     fn try_into(self) -> Result<Box<dyn Storage>, Error> {
         let config: config::SecureBackend = self.try_into()?;
-        Ok((&config).into())
+        <Box<dyn Storage>>::try_from(&config)
+            .map_err(|e| Error::UnexpectedError(format!("Unable to open storage: {}", e)))
     }
 }
 
@@ -2,10 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{error::Error, Command};
-use libra_crypto::ed25519::Ed25519PublicKey;
+use libra_crypto::{ed25519::Ed25519PublicKey, HashValue};
 use libra_global_constants::{
-    ASSOCIATION_KEY, CONSENSUS_KEY, EPOCH, FULLNODE_NETWORK_KEY, LAST_VOTED_ROUND, OPERATOR_KEY,
-    OWNER_KEY, PREFERRED_ROUND, VALIDATOR_NETWORK_KEY, WAYPOINT,
+    ASSOCIATION_KEY, CONSENSUS_KEY, EPOCH, FULLNODE_NETWORK_KEY, HIGHEST_PROPOSED_ROUND,
+    LAST_PROPOSAL, LAST_VOTED_ROUND, OPERATOR_KEY, OWNER_KEY, PREFERRED_ROUND,
+    VALIDATOR_NETWORK_KEY, WAYPOINT,
 };
 use libra_network_address::NetworkAddress;
 use libra_secure_storage::{NamespacedStorage, OnDiskStorage, Storage, Value};
@@ -45,6 +46,10 @@ impl StorageHelper {
         storage.create_key(VALIDATOR_NETWORK_KEY).unwrap();
 
         storage.set(EPOCH, Value::U64(0)).unwrap();
+        storage.set(HIGHEST_PROPOSED_ROUND, Value::U64(0)).unwrap();
+        storage
+            .set(LAST_PROPOSAL, Value::HashValue(HashValue::zero()))
+            .unwrap();
         storage.set(LAST_VOTED_ROUND, Value::U64(0)).unwrap();
         storage.set(PREFERRED_ROUND, Value::U64(0)).unwrap();
         storage.set(WAYPOINT, Value::String("".into())).unwrap();
@@ -213,6 +218,28 @@ impl StorageHelper {
         command.validator_config()
     }
 
+    pub fn diff(&self, source_ns: &str, target_ns: &str) -> Result<String, Error> {
+        let args = format!(
+            "
+                management
+                diff
+                --source backend={backend};\
+                    path={path};\
+                    namespace={source_ns}
+                --target backend={backend};\
+                    path={path};\
+                    namespace={target_ns}\
+            ",
+            backend = crate::secure_backend::DISK,
+            path = self.path_string(),
+            source_ns = source_ns,
+            target_ns = target_ns,
+        );
+
+        let command = Command::from_iter(args.split_whitespace());
+        command.diff()
+    }
+
     pub fn verify(&self, namespace: &str) -> Result<String, Error> {
         let args = format!(
             "
@@ -1,22 +1,96 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::config::SecureBackend;
+use crate::config::{SecureBackend, Token};
+use libra_crypto::ed25519::Ed25519PublicKey;
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, path::PathBuf};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct SafetyRulesConfig {
+    /// Serves authenticated admin commands, such as changing the log level or dumping diagnostics,
+    /// when SafetyRules runs as its own process.
+    pub admin: Option<AdminConfig>,
+    /// Permits signing before SafetyRules has been initialized with a validator set, relying only
+    /// upon the rounds in storage that follow the waypoint. By default, all signing requires an
+    /// initialized validator verifier.
+    pub allow_waypoint_only_signing: bool,
+    /// Records every request served over the SafetyRules protocol as batches in storage, signed by
+    /// its safety data key. SafetyRules refuses to start with an audit log if storage lacks the key.
+    pub audit_log: Option<AuditLogConfig>,
     pub backend: SecureBackend,
+    /// The chain that the SafetyRules storage is bound to. It is recorded when the storage is
+    /// bootstrapped or first opened, afterward SafetyRules refuses to initialize from a storage
+    /// that was bound to a different chain.
+    pub chain_id: Option<String>,
+    /// The operator key that must endorse a consensus key SafetyRules switches to at an epoch
+    /// boundary. It is configured rather than read from storage, so that a compromised storage
+    /// backend cannot replace a consensus key along with its endorsement.
+    pub consensus_key_endorser: Option<Ed25519PublicKey>,
+    /// Enables fencing between SafetyRules instances that share the same storage.
+    pub failover: Option<FailoverConfig>,
+    pub feature_flags: FeatureFlags,
+    pub latency_budgets: LatencyBudgets,
+    /// Refuse to sign while the consensus process, as of its last heartbeat, is more than this
+    /// many epochs behind SafetyRules. This catches a second consensus process, e.g., one left
+    /// running after a failover, signing through the same SafetyRules.
+    pub max_caller_epoch_lag: Option<u64>,
+    /// The maximum number of votes, timeouts and proposals signed per epoch. Once used up,
+    /// SafetyRules enters maintenance mode and refuses to sign until an operator initializes it
+    /// again, which caps how far a compromised consensus process may abuse the consensus key.
+    pub max_signatures_per_epoch: Option<u64>,
+    /// Refuse to sign a timeout for a round more than this many rounds beyond the highest QC
+    /// round known to SafetyRules, which guards against timeouts alone driving up the rounds.
+    pub max_timeout_round_skew: Option<u64>,
+    /// Replaces the quorum voting power of each epoch's validator set, e.g., to let a single node
+    /// devnet make progress. This is only accepted by test deployments.
+    pub quorum_voting_power_override: Option<u64>,
+    /// How long the SafetyData replaced by a destructive operation, a reset to a waypoint or the
+    /// ratchet to a newer configured waypoint, is held for recovery. SafetyRules refuses to sign
+    /// until the operation is confirmed within this window, a later confirmation is refused and
+    /// the operation has to be recovered and repeated.
+    pub recovery_window_ms: u64,
+    /// Counter-sign all SafetyData in storage and refuse to start unless it carries a valid
+    /// counter-signature. A storage without a safety data key, e.g., one set up by the management
+    /// tooling, is provisioned with one and signed as it is found the first time it is opened.
+    /// Without this only a storage that already holds a safety data key is checked. Disabled by
+    /// default.
+    pub require_storage_integrity: bool,
     pub service: SafetyRulesService,
+    /// How the signatures of quorum certificates are verified
+    pub signature_verification: SignatureVerification,
+    /// Holds the signature counts of timeouts in memory for up to this long, so that a cascade of
+    /// timeouts does not write them for every round. The rounds themselves are always persisted
+    /// before a timeout is signed, a crash only loses the count of the timeouts within the window.
+    /// Disabled by default.
+    pub timeout_flush_window_ms: u64,
 }
 
 impl Default for SafetyRulesConfig {
     fn default() -> Self {
         Self {
+            admin: None,
+            allow_waypoint_only_signing: false,
+            audit_log: None,
             backend: SecureBackend::InMemoryStorage,
+            chain_id: None,
+            consensus_key_endorser: None,
+            failover: None,
+            feature_flags: FeatureFlags::default(),
+            latency_budgets: LatencyBudgets::default(),
+            max_caller_epoch_lag: None,
+            max_signatures_per_epoch: None,
+            max_timeout_round_skew: None,
+            quorum_voting_power_override: None,
+            recovery_window_ms: 10 * 60 * 1000,
+            require_storage_integrity: false,
             service: SafetyRulesService::Thread,
+            signature_verification: SignatureVerification::Individual,
+            timeout_flush_window_ms: 0,
         }
     }
 }
@@ -29,6 +103,99 @@ impl SafetyRulesConfig {
     }
 }
 
+/// The admin endpoint of the SafetyRules process. Every command must carry the token, the
+/// endpoint should nonetheless only be reachable by operators.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// The number of the most recent audit entries included in a diagnostic snapshot
+    pub audit_entries: usize,
+    /// The directory diagnostic snapshots are written to
+    pub diagnostics_dir: PathBuf,
+    pub server_address: SocketAddr,
+    pub token: Token,
+}
+
+/// Audit entries are buffered in a fixed-size ring and written to the storage backend in batches
+/// signed by the safety data key. Storage holds a bounded number of batches, the oldest of which
+/// are overwritten by newer ones.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditLogConfig {
+    /// Number of entries written per batch
+    pub batch_size: usize,
+    /// Maximum number of entries held in memory, while storage is unavailable the oldest entries
+    /// are dropped beyond this
+    pub capacity: usize,
+    /// Prefix applied to the keys of the audit log, separating it from the SafetyData
+    pub namespace: String,
+    /// Number of batches retained in storage
+    pub retained_batches: u64,
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            capacity: 1024,
+            namespace: "audit_log".to_string(),
+            retained_batches: 128,
+        }
+    }
+}
+
+/// Configures an active / standby pair of SafetyRules instances, only the holder of an unexpired
+/// signer lease in the shared storage may sign.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverConfig {
+    /// Uniquely identifies this instance amongst those sharing the storage
+    pub signer_id: String,
+    /// How long the lease remains valid after the last signing operation
+    pub lease_duration_ms: u64,
+}
+
+/// Experimental rules that SafetyRules enforces in addition to, or in place of, the default rules.
+/// These are read at startup and reported by consensus_state, so that validators running with
+/// different flags can be identified. All validators within an epoch are expected to agree upon
+/// them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureFlags {
+    /// Lock on the highest certified block and commit on a 2-chain of consecutive rounds instead
+    /// of locking on its parent and committing on a 3-chain
+    pub two_chain: bool,
+    /// Only vote on blocks whose timestamps strictly increase over their parent's, with the
+    /// exception of nil blocks and reconfiguration suffixes which carry their parent's timestamp
+    pub timestamp_checks: bool,
+    /// Only sign proposals beyond the last voted round that extend the preferred round
+    pub strict_proposal_signing: bool,
+}
+
+/// The amount of time, in milliseconds, each SafetyRules operation may take before it is reported
+/// as slow along with a breakdown of where that time was spent.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LatencyBudgets {
+    pub construct_and_sign_vote_ms: u64,
+    pub initialize_ms: u64,
+    pub sign_proposal_ms: u64,
+    pub sign_timeout_ms: u64,
+    pub update_ms: u64,
+}
+
+impl Default for LatencyBudgets {
+    fn default() -> Self {
+        Self {
+            construct_and_sign_vote_ms: 100,
+            initialize_ms: 1000,
+            sign_proposal_ms: 100,
+            sign_timeout_ms: 100,
+            update_ms: 100,
+        }
+    }
+}
+
 /// Defines how safety rules should be executed
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(tag = "type")]
@@ -52,6 +219,83 @@ pub enum SafetyRulesService {
 pub struct RemoteService {
     pub server_address: SocketAddr,
     pub consensus_type: ConsensusType,
+    /// If set, the service only accepts requests from the clients listed here, each of which
+    /// authenticates with its own key.
+    #[serde(default)]
+    pub authentication: Option<RemoteAuthentication>,
+    /// Restricts the operations each client may request, identified by the address it connects
+    /// from. If empty, every client may request every operation.
+    #[serde(default)]
+    pub permissions: Vec<ClientPermissions>,
+    #[serde(default)]
+    pub request_queue: RequestQueueConfig,
+}
+
+/// The clients the remote service authenticates, and the one consensus connects as.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteAuthentication {
+    /// The id of the client consensus connects as, it must be listed among the clients
+    pub client: String,
+    pub clients: Vec<ClientKey>,
+}
+
+/// A client of the remote service and its hex encoded key of at least 32 bytes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientKey {
+    pub id: String,
+    pub key: String,
+}
+
+/// The operations, e.g., "consensus_state" or "sign_proposal", a single client may request.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientPermissions {
+    pub address: IpAddr,
+    pub operations: Vec<String>,
+}
+
+/// Bounds the requests the remote service holds while it is busy with another one, so that a
+/// flood of requests can neither exhaust its memory nor delay a vote indefinitely.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RequestQueueConfig {
+    /// The maximum number of pending requests
+    pub depth: usize,
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for RequestQueueConfig {
+    fn default() -> Self {
+        Self {
+            depth: 16,
+            overflow_policy: OverflowPolicy::RejectNewest,
+        }
+    }
+}
+
+/// Which request is rejected, with a retriable error, when a request arrives at a full queue.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Rejects the request that just arrived
+    RejectNewest,
+    /// Rejects the oldest pending request to make room, favoring recent requests
+    RejectOldest,
+}
+
+/// The scheme by which SafetyRules verifies the quorum of signatures a certificate carries.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureVerification {
+    /// Verifies each signature in turn
+    Individual,
+    /// Verifies all signatures of a certificate at once, which is faster for quorum sized
+    /// certificates. If the batch fails, the signatures are verified in turn to find the invalid
+    /// one, so a certificate with an invalid signature costs more than with individual
+    /// verification.
+    Batch,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
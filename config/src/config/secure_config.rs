@@ -106,6 +106,16 @@ impl VaultConfig {
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct OnDiskStorageConfig {
+    /// When writes are synced to disk. Writes that their caller requires to be durable, such as
+    /// those of the SafetyData, are synced regardless, as SafetyRules could sign twice in a round
+    /// after a crash that loses them.
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+    /// Takes an advisory lock on the storage file, so that a second process pointed at the same
+    /// file fails to open it instead of sharing it, e.g., to prevent two SafetyRules instances
+    /// from signing with the same safety data.
+    #[serde(default)]
+    pub lock: bool,
     // Required path for on disk storage
     pub path: PathBuf,
     /// A namespace is an optional portion of the path to a key stored within OnDiskStorage. For
@@ -116,6 +126,24 @@ pub struct OnDiskStorageConfig {
     data_dir: PathBuf,
 }
 
+/// When OnDiskStorage syncs its writes to disk, trading crash durability for speed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Syncs every write
+    Always,
+    /// Syncs every few writes, a crash may lose the writes since the last sync
+    Batched,
+    /// Never syncs, only for tests
+    Never,
+}
+
+impl Default for FsyncPolicy {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
 /// Tokens can either be directly within this config or stored somewhere on disk.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(tag = "type")]
@@ -156,6 +184,8 @@ pub struct TokenFromDisk {
 impl Default for OnDiskStorageConfig {
     fn default() -> Self {
         Self {
+            fsync: FsyncPolicy::default(),
+            lock: false,
             namespace: None,
             path: PathBuf::from("secure_storage.toml"),
             data_dir: PathBuf::from("/opt/libra/data/common"),
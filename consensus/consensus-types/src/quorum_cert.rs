@@ -97,6 +97,15 @@ impl QuorumCert {
     }
 
     pub fn verify(&self, validator: &ValidatorVerifier) -> anyhow::Result<()> {
+        self.verify_with(|ledger_info| Ok(ledger_info.verify_signatures(validator)?))
+    }
+
+    /// Performs the same checks as verify, but leaves the verification of the signatures to the
+    /// given function, e.g., to verify them as a batch.
+    pub fn verify_with(
+        &self,
+        verify_signatures: impl FnOnce(&LedgerInfoWithSignatures) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
         let vote_hash = self.vote_data.hash();
         ensure!(
             self.ledger_info().ledger_info().consensus_data_hash() == vote_hash,
@@ -120,9 +129,7 @@ impl QuorumCert {
             );
             return Ok(());
         }
-        self.ledger_info()
-            .verify_signatures(validator)
-            .context("Fail to verify QuorumCert")?;
+        verify_signatures(self.ledger_info()).context("Fail to verify QuorumCert")?;
         self.vote_data.verify()?;
         Ok(())
     }
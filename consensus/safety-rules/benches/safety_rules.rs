@@ -64,6 +64,16 @@ fn in_memory(n: u64) {
     lsr(safety_rules_manager.client(), signer, n);
 }
 
+/// Storage is a no-op past provisioning, leaving the cost of signing and verification.
+#[cfg(feature = "unsafe_bench")]
+fn unsafe_bench(n: u64) {
+    let signer = ValidatorSigner::from_int(0);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let storage = PersistentSafetyStorage::unsafe_bench(signer.private_key().clone(), waypoint);
+    let safety_rules_manager = SafetyRulesManager::new_local(signer.author(), storage);
+    lsr(safety_rules_manager.client(), signer, n);
+}
+
 fn on_disk(n: u64) {
     let signer = ValidatorSigner::from_int(0);
     let file_path = NamedTempFile::new().unwrap().into_temp_path().to_path_buf();
@@ -118,6 +128,8 @@ pub fn benchmark(c: &mut Criterion) {
     let count = 100;
     let mut group = c.benchmark_group("SafetyRules");
     group.bench_function("InMemory", |b| b.iter(|| in_memory(black_box(count))));
+    #[cfg(feature = "unsafe_bench")]
+    group.bench_function("UnsafeBench", |b| b.iter(|| unsafe_bench(black_box(count))));
     group.bench_function("OnDisk", |b| b.iter(|| on_disk(black_box(count))));
     group.bench_function("Serializer", |b| b.iter(|| serializer(black_box(count))));
     group.bench_function("Thread", |b| b.iter(|| thread(black_box(count))));
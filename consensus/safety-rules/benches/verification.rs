@@ -0,0 +1,64 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use libra_crypto::{hash::CryptoHash, HashValue};
+use libra_types::{
+    block_info::BlockInfo,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    validator_signer::ValidatorSigner,
+    validator_verifier::{ValidatorConsensusInfo, ValidatorVerifier},
+};
+use safety_rules::{BatchVerification, IndividualVerification, VerificationBackend};
+
+/// A validator set of the given size and a ledger info signed by a quorum of it, as carried by a
+/// QC.
+fn quorum_certified(validators: usize) -> (ValidatorVerifier, LedgerInfoWithSignatures) {
+    let signers: Vec<_> = (0..validators)
+        .map(|i| ValidatorSigner::from_int(i as u8))
+        .collect();
+    let verifier = ValidatorVerifier::new(
+        signers
+            .iter()
+            .map(|signer| {
+                let info = ValidatorConsensusInfo::new(signer.public_key(), 1);
+                (signer.author(), info)
+            })
+            .collect(),
+    );
+    let ledger_info = LedgerInfo::new(BlockInfo::empty(), HashValue::zero());
+    let quorum = verifier.quorum_voting_power() as usize;
+    let signatures = signers[..quorum]
+        .iter()
+        .map(|signer| (signer.author(), signer.sign_message(ledger_info.hash())))
+        .collect();
+    (
+        verifier,
+        LedgerInfoWithSignatures::new(ledger_info, signatures),
+    )
+}
+
+pub fn benchmark(c: &mut Criterion) {
+    let backends: [&dyn VerificationBackend; 2] = [&IndividualVerification, &BatchVerification];
+    let mut group = c.benchmark_group("QuorumCertVerification");
+    for validators in &[4, 16, 64, 128] {
+        let (verifier, ledger_info) = quorum_certified(*validators);
+        for backend in &backends {
+            group.bench_with_input(
+                BenchmarkId::new(backend.name(), validators),
+                &ledger_info,
+                |b, ledger_info| {
+                    b.iter(|| {
+                        backend
+                            .verify_signatures(&verifier, black_box(ledger_info))
+                            .unwrap()
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark);
+criterion_main!(benches);
@@ -0,0 +1,145 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::Error;
+use anyhow::ensure;
+use libra_crypto::{hash::TransactionAccumulatorHasher, HashValue};
+use libra_types::{
+    block_info::BlockInfo,
+    proof::{accumulator::InMemoryAccumulator, AccumulatorExtensionProof},
+    transaction::Version,
+};
+
+pub type ExtensionProof = AccumulatorExtensionProof<TransactionAccumulatorHasher>;
+
+/// Verifies that the proof extends the transaction accumulator of the parent block, returning the
+/// root hash and version of the extended accumulator.
+pub fn verify_extension(
+    proof: &ExtensionProof,
+    parent: &BlockInfo,
+) -> Result<(HashValue, Version), Error> {
+    let result = if proof.leaves().is_empty() {
+        verify_empty_extension(proof, parent)
+    } else {
+        proof
+            .verify(parent.executed_state_id())
+            .map(|new_tree| (new_tree.root_hash(), new_tree.version()))
+    };
+    result.map_err(|e| Error::InvalidAccumulatorExtension {
+        error: format!("{}", e),
+    })
+}
+
+/// NIL blocks and the blocks following a reconfiguration append no leaves, so the extended
+/// accumulator is the original one. Its root is computed from the frozen subtrees once and
+/// compared with the parent's, skipping the append that would copy the subtrees and compute the
+/// same root again. The root cannot be trusted without being computed, as a proof with forged
+/// frozen subtrees would otherwise sign off on a version the parent never reached.
+fn verify_empty_extension(
+    proof: &ExtensionProof,
+    parent: &BlockInfo,
+) -> anyhow::Result<(HashValue, Version)> {
+    let tree = InMemoryAccumulator::<TransactionAccumulatorHasher>::new(
+        proof.frozen_subtree_roots().clone(),
+        proof.num_leaves(),
+    )?;
+    ensure!(
+        tree.root_hash() == parent.executed_state_id(),
+        "Root hashes do not match. Actual root hash: {:x}. Expected root hash: {:x}.",
+        tree.root_hash(),
+        parent.executed_state_id()
+    );
+    Ok((tree.root_hash(), tree.version()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(i: u8) -> HashValue {
+        HashValue::new([i; HashValue::LENGTH])
+    }
+
+    fn parent(tree: &InMemoryAccumulator<TransactionAccumulatorHasher>) -> BlockInfo {
+        BlockInfo::new(
+            1,
+            1,
+            HashValue::zero(),
+            tree.root_hash(),
+            tree.version(),
+            0,
+            None,
+        )
+    }
+
+    fn proof(tree: &InMemoryAccumulator<TransactionAccumulatorHasher>) -> ExtensionProof {
+        ExtensionProof::new(
+            tree.frozen_subtree_roots().clone(),
+            tree.num_leaves(),
+            vec![],
+        )
+    }
+
+    /// Asserts that the fast path reaches the same decision and result as the full verification
+    fn assert_equivalent(proof: &ExtensionProof, parent: &BlockInfo) {
+        let slow_path = proof
+            .verify(parent.executed_state_id())
+            .map(|new_tree| (new_tree.root_hash(), new_tree.version()));
+        let fast_path = verify_empty_extension(proof, parent);
+        match (slow_path, fast_path) {
+            (Ok(slow_path), Ok(fast_path)) => assert_eq!(slow_path, fast_path),
+            (Err(_), Err(_)) => (),
+            (slow_path, fast_path) => panic!("{:?} differs from {:?}", fast_path, slow_path),
+        }
+    }
+
+    #[test]
+    fn test_empty_extension_equivalence() {
+        let leaves: Vec<_> = (0..34).map(leaf).collect();
+        for num_leaves in 0..leaves.len() {
+            let tree = InMemoryAccumulator::from_leaves(&leaves[..num_leaves]);
+            assert_equivalent(&proof(&tree), &parent(&tree));
+            verify_extension(&proof(&tree), &parent(&tree)).unwrap();
+
+            // The accumulator of a different block
+            let other = InMemoryAccumulator::from_leaves(&leaves[1..=num_leaves]);
+            assert_equivalent(&proof(&other), &parent(&tree));
+
+            // Forged frozen subtrees
+            let mut forged = proof(&tree).frozen_subtree_roots().clone();
+            if let Some(root) = forged.last_mut() {
+                *root = HashValue::random();
+            }
+            let forged = ExtensionProof::new(forged, tree.num_leaves(), vec![]);
+            assert_equivalent(&forged, &parent(&tree));
+
+            // A number of leaves inconsistent with the frozen subtrees
+            let inconsistent = ExtensionProof::new(
+                tree.frozen_subtree_roots().clone(),
+                tree.num_leaves() + 1,
+                vec![],
+            );
+            assert_equivalent(&inconsistent, &parent(&tree));
+        }
+    }
+
+    #[test]
+    fn test_nonempty_extension() {
+        let leaves: Vec<_> = (0..8).map(leaf).collect();
+        let tree = InMemoryAccumulator::from_leaves(&leaves[..5]);
+        let proof = ExtensionProof::new(
+            tree.frozen_subtree_roots().clone(),
+            tree.num_leaves(),
+            leaves[5..].to_vec(),
+        );
+        let new_tree = InMemoryAccumulator::from_leaves(&leaves);
+        assert_eq!(
+            verify_extension(&proof, &parent(&tree)).unwrap(),
+            (new_tree.root_hash(), new_tree.version())
+        );
+        assert!(matches!(
+            verify_extension(&proof, &parent(&new_tree)),
+            Err(Error::InvalidAccumulatorExtension { .. })
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The admin endpoint of the SafetyRules process lets an operator raise the log level and dump a
+//! diagnostic snapshot of a running instance, e.g., while debugging a stuck round, without
+//! restarting it, reset it to a waypoint, and confirm or recover a destructive operation, a reset
+//! or the ratchet to a newer configured waypoint at startup.
+//! It listens on its own address, separate from the SafetyRules protocol, and every command must
+//! carry the configured admin token.
+
+use crate::{
+    audit_log::AuditEntry, commit_stats::CommitStats, consensus_state::ConsensusState,
+    recovery::RecoverySlot, remote_service::MessageChannel, serializer::SerializerService,
+    waypoint_history::WaypointRecord, Error,
+};
+use consensus_types::common::{Payload, Round};
+use libra_config::config::AdminConfig;
+use libra_logger::{info, warn, Level};
+use libra_secure_net::{NetworkClient, NetworkServer};
+use libra_types::{epoch_change::EpochChangeProof, waypoint::Waypoint};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct AdminRequest {
+    pub token: String,
+    pub command: AdminCommand,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AdminCommand {
+    /// Sets the log level, e.g., "debug", or restores the configured log level if None
+    SetLogLevel(Option<String>),
+    /// Writes a diagnostic snapshot to the diagnostics directory and returns its path
+    DumpDiagnostics,
+    /// Confirms the destructive operation with the given recovery id, purging the SafetyData it
+    /// replaced
+    ConfirmRecovery(u64),
+    /// Undoes the destructive operation with the given recovery id, restoring the SafetyData it
+    /// replaced
+    Recover(u64),
+    /// Resets SafetyRules to the waypoint, the proof must lead to it from the stored waypoint.
+    /// Returns the recovery id that confirms the reset.
+    ResetToWaypoint(Waypoint, EpochChangeProof),
+}
+
+/// A snapshot of a running SafetyRules instance. Each part is collected independently, so that a
+/// failing storage backend still leaves the in-memory state in the snapshot.
+#[derive(Debug)]
+pub struct Diagnostics {
+    pub timestamp_ms: u64,
+    pub consensus_state: Result<ConsensusState, Error>,
+    pub commit_stats: CommitStats,
+    /// The highest certified round seen in this epoch, which is only held in memory
+    pub highest_qc_round: Round,
+    /// The SafetyData held for a destructive operation awaiting confirmation
+    pub recovery_slot: Result<Option<RecoverySlot>, Error>,
+    pub storage_health: Result<(), Error>,
+    pub waypoint_history: Result<Vec<WaypointRecord>, Error>,
+    /// The most recent audit entries, including those not yet written to storage
+    pub audit_entries: Result<Vec<AuditEntry>, Error>,
+}
+
+/// Serves admin commands until the process exits.
+pub fn execute<T: Payload>(config: AdminConfig, service: Arc<Mutex<SerializerService<T>>>) {
+    let mut network_server = NetworkServer::new(config.server_address);
+    loop {
+        if let Err(e) = process_one_command(&config, &mut network_server, &service) {
+            warn!("Warning: Failed to process admin command: {}", e);
+        }
+    }
+}
+
+fn process_one_command<T: Payload>(
+    config: &AdminConfig,
+    channel: &mut dyn MessageChannel,
+    service: &Mutex<SerializerService<T>>,
+) -> Result<(), Error> {
+    let request = channel.read()?;
+    let response = handle_request(config, &request, service);
+    channel.write(&lcs::to_bytes(&response)?)?;
+    Ok(())
+}
+
+/// Authenticates and executes a serialized AdminRequest, returning a description of the outcome.
+pub fn handle_request<T: Payload>(
+    config: &AdminConfig,
+    request: &[u8],
+    service: &Mutex<SerializerService<T>>,
+) -> Result<String, Error> {
+    let request: AdminRequest = lcs::from_bytes(request)?;
+    let token = config.token.read_token()?;
+    if !constant_time_eq(request.token.as_bytes(), token.as_bytes()) {
+        return Err(Error::Unauthorized("Invalid admin token".into()));
+    }
+
+    info!("Executing admin command {:?}", request.command);
+    match request.command {
+        AdminCommand::SetLogLevel(Some(level)) => {
+            let level = level.parse::<Level>().map_err(|_| Error::InternalError {
+                error: format!("Invalid log level: {}", level),
+            })?;
+            libra_logger::set_runtime_level(Some(level));
+            Ok(format!("Log level set to {}", level))
+        }
+        AdminCommand::SetLogLevel(None) => {
+            libra_logger::set_runtime_level(None);
+            Ok("Log level restored".into())
+        }
+        AdminCommand::DumpDiagnostics => {
+            let diagnostics = service
+                .lock()
+                .expect("SafetyRules lock is poisoned")
+                .diagnostics(config.audit_entries);
+            let path = diagnostics_path(config, diagnostics.timestamp_ms);
+            fs::write(&path, format!("{:#?}\n", diagnostics)).map_err(|e| {
+                Error::InternalError {
+                    error: format!("Unable to write {}: {}", path.display(), e),
+                }
+            })?;
+            Ok(path.display().to_string())
+        }
+        AdminCommand::ConfirmRecovery(id) => {
+            service
+                .lock()
+                .expect("SafetyRules lock is poisoned")
+                .confirm_recovery(id)?;
+            Ok(format!("Confirmed recovery {}", id))
+        }
+        AdminCommand::Recover(id) => {
+            service
+                .lock()
+                .expect("SafetyRules lock is poisoned")
+                .recover(id)?;
+            Ok(format!(
+                "Recovered {}, initialize SafetyRules to resume",
+                id
+            ))
+        }
+        AdminCommand::ResetToWaypoint(waypoint, proof) => {
+            let id = service
+                .lock()
+                .expect("SafetyRules lock is poisoned")
+                .reset_to_waypoint(waypoint, &proof)?;
+            Ok(format!(
+                "Reset to waypoint {}, confirm recovery {} to resume signing",
+                waypoint, id
+            ))
+        }
+    }
+}
+
+fn diagnostics_path(config: &AdminConfig, timestamp_ms: u64) -> PathBuf {
+    config
+        .diagnostics_dir
+        .join(format!("safety_rules_diagnostics_{}.txt", timestamp_ms))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Sends a command to the admin endpoint of a SafetyRules process.
+pub fn send_admin_command(config: &AdminConfig, command: AdminCommand) -> Result<String, Error> {
+    let request = AdminRequest {
+        token: config.token.read_token()?,
+        command,
+    };
+    let mut network_client = NetworkClient::new(config.server_address);
+    network_client.write(&lcs::to_bytes(&request)?)?;
+    let response = network_client.read()?;
+    lcs::from_bytes(&response)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{persistent_safety_storage::PersistentSafetyStorage, SafetyRules};
+    use libra_config::config::Token;
+    use libra_temppath::TempPath;
+    use libra_types::validator_signer::ValidatorSigner;
+
+    fn request(token: &str, command: AdminCommand) -> Vec<u8> {
+        lcs::to_bytes(&AdminRequest {
+            token: token.to_string(),
+            command,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_admin_commands() {
+        let signer = ValidatorSigner::from_int(0);
+        let storage = PersistentSafetyStorage::in_memory(signer.private_key().clone());
+        let safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+        let service = Mutex::new(SerializerService::new(safety_rules));
+
+        let diagnostics_dir = TempPath::new();
+        diagnostics_dir.create_as_dir().unwrap();
+        let config = AdminConfig {
+            audit_entries: 8,
+            diagnostics_dir: diagnostics_dir.path().to_path_buf(),
+            server_address: "127.0.0.1:0".parse().unwrap(),
+            token: Token::new_config("secret".into()),
+        };
+
+        let command = AdminCommand::DumpDiagnostics;
+        assert_eq!(
+            handle_request(&config, &request("guess", command.clone()), &service),
+            Err(Error::Unauthorized("Invalid admin token".into()))
+        );
+        let path = handle_request(&config, &request("secret", command), &service).unwrap();
+        let snapshot = fs::read_to_string(path).unwrap();
+        assert!(snapshot.contains("consensus_state"));
+
+        let command = AdminCommand::SetLogLevel(Some("verbose".into()));
+        handle_request(&config, &request("secret", command), &service).unwrap_err();
+        let command = AdminCommand::SetLogLevel(Some("debug".into()));
+        handle_request(&config, &request("secret", command), &service).unwrap();
+        let command = AdminCommand::SetLogLevel(None);
+        handle_request(&config, &request("secret", command), &service).unwrap();
+
+        let command = AdminCommand::ConfirmRecovery(7);
+        assert!(matches!(
+            handle_request(&config, &request("secret", command), &service),
+            Err(Error::RecoveryRefused(_))
+        ));
+        let command = AdminCommand::Recover(7);
+        assert!(matches!(
+            handle_request(&config, &request("secret", command), &service),
+            Err(Error::RecoveryRefused(_))
+        ));
+
+        // A reset needs a proof from the stored waypoint
+        let command = AdminCommand::ResetToWaypoint(
+            Waypoint::default(),
+            EpochChangeProof::new(vec![], false),
+        );
+        assert!(matches!(
+            handle_request(&config, &request("secret", command), &service),
+            Err(Error::WaypointMismatch(_))
+        ));
+    }
+}
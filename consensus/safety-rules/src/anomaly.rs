@@ -0,0 +1,64 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use consensus_types::common::Round;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// A security relevant event behind a refused request. Unlike most rejections, which are part of
+/// normal operation, each of these points at a misbehaving peer, a compromised caller or tampered
+/// storage, so operators may forward them to their alerting.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Anomaly {
+    /// A client of the remote transport sent a request it is not permitted to make
+    AuthenticationFailure {
+        peer: Option<String>,
+        request: String,
+        reason: String,
+    },
+    /// A proposal was to be signed for a round in which a different one had been signed
+    EquivocationAttempt { epoch: u64, round: Round },
+    /// The signature quota of an epoch has been used up
+    QuotaExceeded { epoch: u64, quota: u64 },
+    /// Storage holds state that SafetyRules did not write, e.g., that of another chain
+    StorageDivergence(String),
+    /// SafetyRules was to be moved back to an earlier epoch
+    WaypointRegressionAttempt { current_epoch: u64, epoch: u64 },
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Anomaly::AuthenticationFailure {
+                peer,
+                request,
+                reason,
+            } => write!(
+                f,
+                "{} request from {} refused: {}",
+                request,
+                peer.as_deref().unwrap_or("an unknown peer"),
+                reason
+            ),
+            Anomaly::EquivocationAttempt { epoch, round } => write!(
+                f,
+                "equivocating proposal for epoch {}, round {} refused",
+                epoch, round
+            ),
+            Anomaly::QuotaExceeded { epoch, quota } => write!(
+                f,
+                "signature quota of {} for epoch {} used up",
+                quota, epoch
+            ),
+            Anomaly::StorageDivergence(reason) => write!(f, "storage diverged: {}", reason),
+            Anomaly::WaypointRegressionAttempt {
+                current_epoch,
+                epoch,
+            } => write!(
+                f,
+                "move from epoch {} back to epoch {} refused",
+                current_epoch, epoch
+            ),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    epoch_summary::EpochSummary, persistent_safety_storage::PersistentSafetyStorage,
+    serializer::RequestId,
+};
+use anyhow::Result;
+use libra_config::config::AuditLogConfig;
+use libra_crypto::{ed25519::Ed25519Signature, HashValue};
+use libra_logger::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A request served by SafetyRules along with the response it produced, both as they were
+/// serialized over the SafetyRules protocol.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub request_id: RequestId,
+    pub timestamp_ms: u64,
+    pub request: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+/// A consecutive run of audit entries as written to storage.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuditBatch {
+    /// Batches are numbered consecutively starting at 0
+    pub index: u64,
+    /// The number of entries dropped from memory before this batch could be written
+    pub dropped: u64,
+    pub entries: Vec<AuditEntry>,
+    /// The summaries of the epochs that ended since the previous batch
+    pub epoch_summaries: Vec<EpochSummary>,
+}
+
+impl AuditBatch {
+    pub fn hash(&self) -> Result<HashValue> {
+        Ok(HashValue::from_iter_sha3(vec![
+            b"AuditBatch".as_ref(),
+            lcs::to_bytes(self)?.as_slice(),
+        ]))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SignedAuditBatch {
+    pub batch: AuditBatch,
+    /// The signature of the safety data key over the hash of the batch
+    pub signature: Ed25519Signature,
+}
+
+/// Buffers audit entries in memory until a full batch can be written to storage. Memory is
+/// bounded by the configured capacity, if storage remains unavailable the oldest entries are
+/// dropped and the number dropped is recorded in the next batch written.
+pub struct AuditLog {
+    config: AuditLogConfig,
+    dropped: u64,
+    entries: VecDeque<AuditEntry>,
+    epoch_summaries: Vec<EpochSummary>,
+}
+
+impl AuditLog {
+    pub fn new(config: AuditLogConfig) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(config.capacity),
+            config,
+            dropped: 0,
+            epoch_summaries: Vec::new(),
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        request_id: RequestId,
+        request: Vec<u8>,
+        response: Vec<u8>,
+        storage: &mut PersistentSafetyStorage,
+    ) {
+        if self.entries.len() >= self.config.capacity {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(AuditEntry {
+            request_id,
+            timestamp_ms: now_ms(),
+            request,
+            response,
+        });
+
+        if self.entries.len() >= self.config.batch_size {
+            if let Err(e) = self.flush(storage) {
+                warn!("Unable to write the SafetyRules audit log: {}", e);
+            }
+        }
+    }
+
+    /// Records the summary of an epoch that just ended and writes it to storage right away, along
+    /// with any buffered entries, rather than waiting for a full batch.
+    pub fn record_epoch_summary(
+        &mut self,
+        summary: EpochSummary,
+        storage: &mut PersistentSafetyStorage,
+    ) {
+        self.epoch_summaries.push(summary);
+        if let Err(e) = self.flush(storage) {
+            warn!("Unable to write the SafetyRules audit log: {}", e);
+        }
+    }
+
+    /// Returns the most recent entries, whether they have been written to storage or are still
+    /// buffered in memory.
+    pub fn recent_entries(
+        &self,
+        count: usize,
+        storage: &PersistentSafetyStorage,
+    ) -> Result<Vec<AuditEntry>> {
+        let mut entries: Vec<_> = storage
+            .audit_batches(&self.config)?
+            .into_iter()
+            .flat_map(|batch| batch.entries)
+            .chain(self.entries.iter().cloned())
+            .collect();
+        let skip = entries.len().saturating_sub(count);
+        Ok(entries.split_off(skip))
+    }
+
+    /// Writes all buffered entries to storage in batches of at most the configured batch size.
+    /// Pending epoch summaries are written with the first batch.
+    pub fn flush(&mut self, storage: &mut PersistentSafetyStorage) -> Result<()> {
+        while !self.entries.is_empty() || !self.epoch_summaries.is_empty() {
+            let count = std::cmp::min(self.config.batch_size, self.entries.len());
+            let entries = self.entries.iter().take(count).cloned().collect();
+            storage.append_audit_batch(
+                &self.config,
+                self.dropped,
+                entries,
+                self.epoch_summaries.clone(),
+            )?;
+            self.entries.drain(..count);
+            self.dropped = 0;
+            self.epoch_summaries.clear();
+        }
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the UNIX epoch")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libra_types::validator_signer::ValidatorSigner;
+
+    #[test]
+    fn test_rotation() {
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let mut storage = PersistentSafetyStorage::in_memory(private_key);
+        let config = AuditLogConfig {
+            batch_size: 2,
+            capacity: 4,
+            namespace: "audit".to_string(),
+            retained_batches: 2,
+        };
+        let mut audit_log = AuditLog::new(config.clone());
+
+        for i in 0..5 {
+            audit_log.record(RequestId::random(), vec![i], vec![], &mut storage);
+        }
+        let batches = storage.audit_batches(&config).unwrap();
+        let indices: Vec<_> = batches.iter().map(|batch| batch.index).collect();
+        assert_eq!(indices, vec![0, 1]);
+        assert_eq!(batches[1].entries[1].request, vec![3]);
+
+        // The oldest batch is overwritten, the last entry is only written once flushed
+        audit_log.flush(&mut storage).unwrap();
+        let batches = storage.audit_batches(&config).unwrap();
+        let indices: Vec<_> = batches.iter().map(|batch| batch.index).collect();
+        assert_eq!(indices, vec![1, 2]);
+        assert_eq!(batches[1].entries.len(), 1);
+        assert_eq!(batches[1].entries[0].request, vec![4]);
+
+        // An epoch summary is written right away, even without entries
+        let summary = EpochSummary {
+            epoch: 1,
+            ..Default::default()
+        };
+        audit_log.record_epoch_summary(summary.clone(), &mut storage);
+        let batches = storage.audit_batches(&config).unwrap();
+        assert_eq!(batches[1].index, 3);
+        assert!(batches[1].entries.is_empty());
+        assert_eq!(batches[1].epoch_summaries, vec![summary]);
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Authenticates the clients of the remote service. libra-secure-net establishes no identity of its
+//! own and the address a connection comes from, which permissions rely on, says nothing about who
+//! wrote the messages arriving over it. Instead, each client holds its own key, which the service
+//! knows as well, and every message is tagged with HMAC-SHA256.
+//!
+//! A client opens a session with a hello carrying its id and a random nonce, and the service
+//! answers with a nonce of its own. The session key is derived from the client's key and both
+//! nonces, and the service tags its answer with it to prove that it holds the client's key too.
+//! Within a session both directions number their messages, so a message is refused if it is
+//! replayed or reordered, and as every session has a fresh key, neither can a message from an
+//! earlier session be replayed into a later one. The session the service serves is only replaced
+//! once a request authenticates under the new one, so an unanswered hello cannot disrupt it.
+
+use crate::{remote_service::MessageChannel, Error};
+use hmac::{Hmac, Mac};
+use libra_config::config::RemoteAuthentication;
+use libra_logger::debug;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{collections::HashMap, net::SocketAddr};
+
+const DOMAIN: &[u8] = b"LIBRA::SafetyRules::Authentication";
+const MIN_KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 32;
+
+// Labels separate the uses of the HMAC, they all precede fixed length fields
+const SESSION: u8 = 0;
+const WELCOME: u8 = 1;
+const REQUEST: u8 = 2;
+const RESPONSE: u8 = 3;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Deserialize, Serialize)]
+enum Frame {
+    /// Opens a session, sent by the client
+    Hello {
+        client: String,
+        nonce: [u8; NONCE_LENGTH],
+    },
+    /// Accepts a session, the tag proves that the service holds the client's key
+    Welcome {
+        nonce: [u8; NONCE_LENGTH],
+        tag: Vec<u8>,
+    },
+    /// A request or response within a session
+    Message {
+        sequence: u64,
+        message: Vec<u8>,
+        tag: Vec<u8>,
+    },
+}
+
+fn encode(frame: &Frame) -> Result<Vec<u8>, Error> {
+    Ok(lcs::to_bytes(frame)?)
+}
+
+fn decode(frame: &[u8]) -> Result<Frame, Error> {
+    lcs::from_bytes(frame)
+        .map_err(|e| Error::AuthenticationFailed(format!("Malformed message: {}", e)))
+}
+
+fn hmac(key: &[u8], parts: &[&[u8]]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_varkey(key).expect("HMAC accepts keys of any length");
+    mac.input(DOMAIN);
+    for part in parts {
+        mac.input(part);
+    }
+    mac
+}
+
+fn tag(key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    hmac(key, parts).result().code().to_vec()
+}
+
+/// The comparison takes constant time.
+fn verify(key: &[u8], parts: &[&[u8]], tag: &[u8]) -> Result<(), Error> {
+    hmac(key, parts)
+        .verify(tag)
+        .map_err(|_| Error::AuthenticationFailed("Invalid tag".into()))
+}
+
+fn parse_key(id: &str, key: &str) -> Result<Vec<u8>, Error> {
+    let key = hex::decode(key).map_err(|e| Error::InternalError {
+        error: format!("The key of client {} is not hex encoded: {}", id, e),
+    })?;
+    if key.len() < MIN_KEY_LENGTH {
+        return Err(Error::InternalError {
+            error: format!(
+                "The key of client {} has {} bytes, at least {} are required",
+                id,
+                key.len(),
+                MIN_KEY_LENGTH
+            ),
+        });
+    }
+    Ok(key)
+}
+
+struct Session {
+    client: String,
+    key: Vec<u8>,
+    /// The sequence numbers of the next message read and written
+    read_sequence: u64,
+    write_sequence: u64,
+}
+
+impl Session {
+    fn new(
+        client: String,
+        client_key: &[u8],
+        client_nonce: &[u8; NONCE_LENGTH],
+        server_nonce: &[u8; NONCE_LENGTH],
+    ) -> Self {
+        Self {
+            client,
+            key: tag(client_key, &[&[SESSION], client_nonce, server_nonce]),
+            read_sequence: 0,
+            write_sequence: 0,
+        }
+    }
+
+    fn seal(&mut self, label: u8, message: &[u8]) -> Result<Vec<u8>, Error> {
+        let sequence = self.write_sequence;
+        self.write_sequence += 1;
+        encode(&Frame::Message {
+            sequence,
+            message: message.to_vec(),
+            tag: tag(&self.key, &[&[label], &sequence.to_le_bytes(), message]),
+        })
+    }
+
+    fn open(&mut self, label: u8, sequence: u64, message: &[u8], tag: &[u8]) -> Result<(), Error> {
+        verify(
+            &self.key,
+            &[&[label], &sequence.to_le_bytes(), message],
+            tag,
+        )?;
+        if sequence != self.read_sequence {
+            return Err(Error::AuthenticationFailed(format!(
+                "Replayed or reordered message, sequence {} where {} was expected",
+                sequence, self.read_sequence
+            )));
+        }
+        self.read_sequence += 1;
+        Ok(())
+    }
+}
+
+/// The client end of an authenticated transport. Any failure ends the session, and the next
+/// request opens a new one, as it would after the transport reconnected.
+pub struct AuthenticatedClient {
+    channel: Box<dyn MessageChannel>,
+    id: String,
+    key: Vec<u8>,
+    session: Option<Session>,
+}
+
+impl AuthenticatedClient {
+    /// Connects as the client the config names.
+    pub fn new(
+        channel: Box<dyn MessageChannel>,
+        config: &RemoteAuthentication,
+    ) -> Result<Self, Error> {
+        let client = config
+            .clients
+            .iter()
+            .find(|client| client.id == config.client)
+            .ok_or_else(|| Error::InternalError {
+                error: format!("No key is configured for client {}", config.client),
+            })?;
+        Ok(Self {
+            channel,
+            id: client.id.clone(),
+            key: parse_key(&client.id, &client.key)?,
+            session: None,
+        })
+    }
+
+    fn connect(&mut self) -> Result<Session, Error> {
+        let nonce: [u8; NONCE_LENGTH] = rand::random();
+        self.channel.write(&encode(&Frame::Hello {
+            client: self.id.clone(),
+            nonce,
+        })?)?;
+        loop {
+            match decode(&self.channel.read()?)? {
+                Frame::Welcome {
+                    nonce: server_nonce,
+                    tag,
+                } => {
+                    let session = Session::new(self.id.clone(), &self.key, &nonce, &server_nonce);
+                    verify(&session.key, &[&[WELCOME]], &tag)?;
+                    return Ok(session);
+                }
+                // A response that was still in flight for an earlier session
+                Frame::Message { .. } => continue,
+                Frame::Hello { .. } => {
+                    return Err(Error::AuthenticationFailed("Unexpected hello".into()))
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        if self.session.is_none() {
+            self.session = Some(self.connect()?);
+        }
+        let session = self.session.as_mut().expect("The session was just opened");
+        let frame = session.seal(REQUEST, message)?;
+        self.channel.write(&frame)
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>, Error> {
+        let frame = decode(&self.channel.read()?)?;
+        let session = self.session.as_mut().ok_or_else(|| Error::InternalError {
+            error: "No authenticated session".into(),
+        })?;
+        match frame {
+            Frame::Message {
+                sequence,
+                message,
+                tag,
+            } => {
+                session.open(RESPONSE, sequence, &message, &tag)?;
+                Ok(message)
+            }
+            _ => Err(Error::AuthenticationFailed("Unexpected handshake".into())),
+        }
+    }
+}
+
+impl MessageChannel for AuthenticatedClient {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        let result = self.receive();
+        if result.is_err() {
+            self.session = None;
+        }
+        result
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        let result = self.send(message);
+        if result.is_err() {
+            self.session = None;
+        }
+        result
+    }
+}
+
+/// The service end of an authenticated transport, which answers hellos on its own and only
+/// returns requests that authenticate.
+pub struct AuthenticatedServer {
+    channel: Box<dyn MessageChannel>,
+    keys: HashMap<String, Vec<u8>>,
+    session: Option<Session>,
+    /// The session offered in the last welcome, it replaces the current one once a request
+    /// authenticates under it
+    pending: Option<Session>,
+}
+
+impl AuthenticatedServer {
+    pub fn new(
+        channel: Box<dyn MessageChannel>,
+        config: &RemoteAuthentication,
+    ) -> Result<Self, Error> {
+        let mut keys = HashMap::new();
+        for client in &config.clients {
+            keys.insert(client.id.clone(), parse_key(&client.id, &client.key)?);
+        }
+        Ok(Self {
+            channel,
+            keys,
+            session: None,
+            pending: None,
+        })
+    }
+
+    fn receive(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        match decode(frame)? {
+            Frame::Hello { client, nonce } => {
+                let key = self.keys.get(&client).ok_or_else(|| {
+                    Error::AuthenticationFailed(format!("Unknown client {}", client))
+                })?;
+                let server_nonce: [u8; NONCE_LENGTH] = rand::random();
+                let session = Session::new(client, key, &nonce, &server_nonce);
+                let welcome = Frame::Welcome {
+                    nonce: server_nonce,
+                    tag: tag(&session.key, &[&[WELCOME]]),
+                };
+                self.pending = Some(session);
+                self.channel.write(&encode(&welcome)?)?;
+                Ok(None)
+            }
+            Frame::Message {
+                sequence,
+                message,
+                tag,
+            } => {
+                if let Some(pending) = &mut self.pending {
+                    if pending.open(REQUEST, sequence, &message, &tag).is_ok() {
+                        debug!("Opened an authenticated session with {}", pending.client);
+                        self.session = self.pending.take();
+                        return Ok(Some(message));
+                    }
+                }
+                let session = self.session.as_mut().ok_or_else(|| {
+                    Error::AuthenticationFailed("Request outside of a session".into())
+                })?;
+                session.open(REQUEST, sequence, &message, &tag)?;
+                Ok(Some(message))
+            }
+            Frame::Welcome { .. } => Err(Error::AuthenticationFailed("Unexpected welcome".into())),
+        }
+    }
+}
+
+impl MessageChannel for AuthenticatedServer {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        loop {
+            let frame = self.channel.read()?;
+            if let Some(message) = self.receive(&frame)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        let session = self.session.as_mut().ok_or_else(|| Error::InternalError {
+            error: "No authenticated session".into(),
+        })?;
+        let frame = session.seal(RESPONSE, message)?;
+        self.channel.write(&frame)
+    }
+
+    fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        while let Some(frame) = self.channel.try_read()? {
+            if let Some(message) = self.receive(&frame)? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    fn peer(&self) -> Option<SocketAddr> {
+        self.channel.peer()
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{counters::COUNTERS, rules::CommitDecision};
+use serde::{Deserialize, Serialize};
+
+/// Counts how the votes signed by this instance fared against the commit rule since it started.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CommitStats {
+    /// Votes that committed a block
+    pub commits: u64,
+    /// Votes that did not commit as B0 and B1 were not contiguous
+    pub parent_gaps: u64,
+    /// Votes that did not commit as B1 and B2 were not contiguous
+    pub child_gaps: u64,
+    /// Votes that did not commit as neither pair of blocks was contiguous
+    pub both_gaps: u64,
+}
+
+impl CommitStats {
+    pub fn record(&mut self, decision: CommitDecision) {
+        match decision {
+            CommitDecision::Commit => {
+                self.commits += 1;
+                COUNTERS.commit_rule_commits.inc();
+            }
+            CommitDecision::ParentGap => {
+                self.parent_gaps += 1;
+                COUNTERS.commit_rule_parent_gaps.inc();
+            }
+            CommitDecision::ChildGap => {
+                self.child_gaps += 1;
+                COUNTERS.commit_rule_child_gaps.inc();
+            }
+            CommitDecision::BothGaps => {
+                self.both_gaps += 1;
+                COUNTERS.commit_rule_both_gaps.inc();
+            }
+        }
+    }
+
+    /// The total number of votes the commit rule was evaluated for
+    pub fn votes(&self) -> u64 {
+        self.commits + self.parent_gaps + self.child_gaps + self.both_gaps
+    }
+}
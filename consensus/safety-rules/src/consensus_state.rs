@@ -1,7 +1,9 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::signature_counts::SignatureCounts;
 use consensus_types::common::Round;
+use libra_config::config::FeatureFlags;
 use libra_types::waypoint::Waypoint;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
@@ -12,8 +14,10 @@ use std::fmt::{Display, Formatter};
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ConsensusState {
     epoch: u64,
+    feature_flags: FeatureFlags,
     last_voted_round: Round,
     preferred_round: Round,
+    signature_counts: SignatureCounts,
     waypoint: Waypoint,
 }
 
@@ -26,8 +30,17 @@ impl Display for ConsensusState {
              \tlast_voted_round = {},\n\
              \tpreferred_round = {}\n\
              \twaypoint = {}\n\
+             \tfeature_flags = {:?}\n\
+             \tepoch_signatures = [{}]\n\
+             \tlifetime_signatures = [{}]\n\
              ]",
-            self.epoch, self.last_voted_round, self.preferred_round, self.waypoint,
+            self.epoch,
+            self.last_voted_round,
+            self.preferred_round,
+            self.waypoint,
+            self.feature_flags,
+            self.signature_counts.epoch_count,
+            self.signature_counts.lifetime_count,
         )
     }
 }
@@ -41,17 +54,34 @@ impl ConsensusState {
     ) -> Self {
         Self {
             epoch,
+            feature_flags: FeatureFlags::default(),
             last_voted_round,
             preferred_round,
+            signature_counts: SignatureCounts::default(),
             waypoint,
         }
     }
 
+    pub fn with_feature_flags(mut self, feature_flags: FeatureFlags) -> Self {
+        self.feature_flags = feature_flags;
+        self
+    }
+
+    pub fn with_signature_counts(mut self, signature_counts: SignatureCounts) -> Self {
+        self.signature_counts = signature_counts;
+        self
+    }
+
     /// Returns the current epoch
     pub fn epoch(&self) -> u64 {
         self.epoch
     }
 
+    /// Returns the experimental rules that SafetyRules enforces
+    pub fn feature_flags(&self) -> FeatureFlags {
+        self.feature_flags
+    }
+
     /// Returns the last round that was voted on
     pub fn last_voted_round(&self) -> Round {
         self.last_voted_round
@@ -63,6 +93,12 @@ impl ConsensusState {
         self.preferred_round
     }
 
+    /// Returns the signatures issued by the consensus key, over its lifetime and in the epoch it
+    /// last signed in
+    pub fn signature_counts(&self) -> SignatureCounts {
+        self.signature_counts
+    }
+
     /// Last known checkpoint this should map to a LedgerInfo that contains a new ValidatorSet
     pub fn waypoint(&self) -> Waypoint {
         self.waypoint
@@ -9,6 +9,74 @@ use std::sync::Arc;
 // Use the libra_safety_rules prefix for all counters
 define_counters![
     "libra_safety_rules",
+    (
+        anomalies: Counter,
+        "counts security relevant anomalies, such as equivocation attempts, see Anomaly"
+    ),
+    (
+        caller_epoch: Gauge,
+        "the epoch last reported by the heartbeat of the consensus process"
+    ),
+    (
+        caller_epoch_lag: Gauge,
+        "how many epochs the consensus process lags behind SafetyRules, as of its last heartbeat"
+    ),
+    (
+        caller_heartbeat_ms: Gauge,
+        "when the last heartbeat of the consensus process was received, in ms since the Unix epoch"
+    ),
+    (
+        caller_round: Gauge,
+        "the round last reported by the heartbeat of the consensus process"
+    ),
+    (
+        commit_rule_both_gaps: Counter,
+        "counts votes that did not commit as neither pair of rounds in the chain was contiguous"
+    ),
+    (
+        commit_rule_child_gaps: Counter,
+        "counts votes that did not commit as the proposal does not directly follow its parent"
+    ),
+    (
+        commit_rule_commits: Counter,
+        "counts votes that committed a block"
+    ),
+    (
+        commit_rule_parent_gaps: Counter,
+        "counts votes that did not commit as the certified block does not directly follow its parent"
+    ),
+    (
+        epoch_signatures: Gauge,
+        "the number of signatures issued by the consensus key in the current epoch"
+    ),
+    (
+        epoch_summary_epoch: Gauge,
+        "the last completed epoch, which the other epoch_summary metrics describe"
+    ),
+    (
+        epoch_summary_max_round: Gauge,
+        "the highest round voted in, proposed or certified in the last completed epoch"
+    ),
+    (
+        epoch_summary_proposals: Gauge,
+        "the number of proposals signed in the last completed epoch"
+    ),
+    (
+        epoch_summary_rejections: Gauge,
+        "the number of vote proposals refused in the last completed epoch"
+    ),
+    (
+        epoch_summary_timeouts: Gauge,
+        "the number of timeouts signed in the last completed epoch"
+    ),
+    (
+        epoch_summary_votes: Gauge,
+        "the number of votes signed in the last completed epoch"
+    ),
+    (
+        lifetime_signatures: Gauge,
+        "the number of signatures ever issued by the consensus key"
+    ),
     (
         requested_sign_timeout: Counter,
         "counts requests to sign_timeouts"
@@ -18,6 +86,21 @@ define_counters![
         "sign_proposal counter counts sign_proposals"
     ),
     (sign_timeout: Counter, "counts successful sign_timeouts"),
+    (
+        signature_quota_exceeded: Counter,
+        "counts signatures refused as the quota for the epoch was used up"
+    ),
+    (
+        sign_timeout_round_skew: Gauge,
+        "the distance of the last requested timeout round beyond the highest QC round"
+    ),
+    (
+        sign_timeout_round_skew_rejections: Counter,
+        "counts timeouts refused for being too far beyond the highest QC round"
+    ),
+    (signed_proposals: Counter, "counts proposal signatures issued"),
+    (signed_timeouts: Counter, "counts timeout signatures issued"),
+    (signed_votes: Counter, "counts vote signatures issued"),
     (some_gauge_counter: Gauge, "example help for a gauge metric"),
 ];
 
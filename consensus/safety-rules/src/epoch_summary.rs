@@ -0,0 +1,131 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    counters::COUNTERS,
+    error::Error,
+    signature_counts::{SignatureCount, SignatureCounts},
+};
+use consensus_types::common::Round;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+/// A compact record of what SafetyRules did in an epoch, produced as it moves into the next one.
+/// The signatures are read from storage and cover the whole epoch, the rejections are only held
+/// in memory and cover the epoch since SafetyRules last started.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EpochSummary {
+    pub epoch: u64,
+    pub ended_ms: u64,
+    pub signatures: SignatureCount,
+    /// The vote proposals refused, by the name of the error they were refused with
+    pub rejections: BTreeMap<String, u64>,
+    /// The highest round voted in, proposed or certified
+    pub max_round: Round,
+}
+
+impl EpochSummary {
+    pub fn new(
+        epoch: u64,
+        ended_ms: u64,
+        signature_counts: &SignatureCounts,
+        rejections: BTreeMap<String, u64>,
+        max_round: Round,
+    ) -> Self {
+        let signatures = if signature_counts.epoch == epoch {
+            signature_counts.epoch_count
+        } else {
+            SignatureCount::default()
+        };
+        Self {
+            epoch,
+            ended_ms,
+            signatures,
+            rejections,
+            max_round,
+        }
+    }
+
+    pub fn rejection_count(&self) -> u64 {
+        self.rejections.values().sum()
+    }
+
+    /// Publishes the summary as the metrics of the last completed epoch and resets the metrics
+    /// that cover the current epoch.
+    pub fn publish(&self) {
+        COUNTERS.epoch_summary_epoch.set(self.epoch as i64);
+        COUNTERS.epoch_summary_max_round.set(self.max_round as i64);
+        COUNTERS
+            .epoch_summary_proposals
+            .set(self.signatures.proposals as i64);
+        COUNTERS
+            .epoch_summary_rejections
+            .set(self.rejection_count() as i64);
+        COUNTERS
+            .epoch_summary_timeouts
+            .set(self.signatures.timeouts as i64);
+        COUNTERS
+            .epoch_summary_votes
+            .set(self.signatures.votes as i64);
+        COUNTERS.epoch_signatures.set(0);
+    }
+}
+
+impl Display for EpochSummary {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "epoch {}: {}, max round = {}, rejections = {:?}",
+            self.epoch, self.signatures, self.max_round, self.rejections
+        )
+    }
+}
+
+/// The name of the error variant, e.g., OldProposal, which groups rejections without the rounds
+/// they carry.
+pub fn rejection_reason(error: &Error) -> String {
+    let debug = format!("{:?}", error);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature_counts::SignatureKind;
+
+    #[test]
+    fn test_summary() {
+        let mut signature_counts = SignatureCounts::default();
+        signature_counts.record(3, SignatureKind::Vote);
+        signature_counts.record(3, SignatureKind::Vote);
+        signature_counts.record(3, SignatureKind::Timeout);
+
+        let mut rejections = BTreeMap::new();
+        let error = Error::OldProposal {
+            last_voted_round: 4,
+            proposal_round: 3,
+        };
+        *rejections.entry(rejection_reason(&error)).or_default() += 2;
+        *rejections
+            .entry(rejection_reason(&Error::MaintenanceMode))
+            .or_default() += 1;
+
+        let summary = EpochSummary::new(3, 0, &signature_counts, rejections, 5);
+        assert_eq!(summary.signatures.votes, 2);
+        assert_eq!(summary.signatures.timeouts, 1);
+        assert_eq!(summary.rejections["OldProposal"], 2);
+        assert_eq!(summary.rejections["MaintenanceMode"], 1);
+        assert_eq!(summary.rejection_count(), 3);
+
+        // Nothing was signed in an epoch the counts have moved past
+        let summary = EpochSummary::new(2, 0, &signature_counts, BTreeMap::new(), 0);
+        assert_eq!(summary.signatures, SignatureCount::default());
+    }
+}
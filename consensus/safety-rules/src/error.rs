@@ -1,11 +1,12 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::rules::RuleViolation;
 use consensus_types::common::Round;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Deserialize, Error, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Error, PartialEq, Serialize)]
 /// Different reasons for proposal rejection
 pub enum Error {
     #[error("Timeout round, {0}, is incompatible with last votedx round, {1}")]
@@ -14,6 +15,36 @@ pub enum Error {
     #[error("Timeout round, {0}, is incompatible with preferred round, {1}")]
     BadTimeoutPreferredRound(u64, u64),
 
+    #[error(
+        "Timeout round, {}, is more than {} rounds beyond the highest QC round, {}",
+        timeout_round,
+        max_skew,
+        highest_qc_round
+    )]
+    BadTimeoutRoundSkew {
+        highest_qc_round: u64,
+        max_skew: u64,
+        timeout_round: u64,
+    },
+
+    #[error(
+        "The caller reports epoch {}, more than {} epochs behind epoch {}",
+        caller_epoch,
+        max_lag,
+        epoch
+    )]
+    CallerEpochLag {
+        caller_epoch: u64,
+        epoch: u64,
+        max_lag: u64,
+    },
+
+    #[error("A different proposal has already been signed for round {0}")]
+    EquivocatingProposal(u64),
+
+    #[error("Stored chain id, {0}, does not match expected chain id, {1}")]
+    IncorrectChainId(String, String),
+
     #[error("Provided epoch, {0}, does not match expected epoch, {1}")]
     IncorrectEpoch(u64, u64),
 
@@ -23,13 +54,52 @@ pub enum Error {
     #[error("Unable to verify that the new tree extneds the parent: {:?}", error)]
     InvalidAccumulatorExtension { error: String },
 
+    #[error("The consensus key advertised by the validator set is not endorsed: {0}")]
+    InvalidKeyEndorsement(String),
+
     #[error("No next_epoch_state specified in the provided Ledger Info")]
     InvalidLedgerInfo,
 
+    #[error("Ledger info signatures failed verification: {0}")]
+    InvalidLedgerInfoSignatures(String),
+
+    #[error(
+        "Block at epoch {}, round {} does not extend the block its QC certifies at epoch {}, round {}",
+        epoch,
+        round,
+        parent_epoch,
+        parent_round
+    )]
+    InvalidParent {
+        epoch: u64,
+        parent_epoch: u64,
+        parent_round: u64,
+        round: u64,
+    },
+
     #[error("Invalid QC: {}", {0})]
     InvalidQuorumCertificate(String),
 
-    #[error("validator_verifier is not set, SafetyRules is not initialized")]
+    #[error(
+        "Block timestamp, {}, is incompatible with its parent's timestamp, {}",
+        timestamp_usecs,
+        parent_timestamp_usecs
+    )]
+    InvalidTimestamp {
+        parent_timestamp_usecs: u64,
+        timestamp_usecs: u64,
+    },
+
+    #[error("Invalid validator set diff: {0}")]
+    InvalidValidatorSetDiff(String),
+
+    #[error("Signing is fenced, {holder} holds the signer lease until {expiration_ms}")]
+    NotLeaseHolder { holder: String, expiration_ms: u64 },
+
+    #[error("SafetyRules is in maintenance mode, call initialize to resume")]
+    MaintenanceMode,
+
+    #[error("SafetyRules is not initialized, call initialize with an EpochChangeProof")]
     NotInitialized,
 
     /// This proposal's round is less than round of preferred block.
@@ -51,11 +121,57 @@ pub enum Error {
         proposal_round: Round,
     },
 
+    /// This proposal is older than one that has already been signed - return
+    /// highest_proposed_round
+    #[error(
+        "Proposal at round {:?} is older than the highest proposed round {:?}",
+        proposal_round,
+        highest_proposed_round
+    )]
+    OldProposedRound {
+        highest_proposed_round: Round,
+        proposal_round: Round,
+    },
+
+    #[error("Proposal at round {round} was vetoed by the proposal inspector: {reason}")]
+    ProposalVetoed { round: Round, reason: String },
+
+    #[error("Signing is refused until recovery {0} is confirmed or recovered")]
+    RecoveryPending(u64),
+
+    #[error("Recovery refused: {0}")]
+    RecoveryRefused(String),
+
+    #[error("The request queue holds {0} pending requests and is full, retry later")]
+    RequestQueueFull(usize),
+
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
+    #[error("The quota of {quota} signatures in epoch {epoch} has been used up")]
+    SignatureQuotaExceeded { epoch: u64, quota: u64 },
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("The vote could not be signed by its deadline, {deadline_ms}")]
+    VoteDeadlineExceeded { deadline_ms: u64 },
+
     #[error("Waypoint mismatch: {0}")]
     WaypointMismatch(String),
+
+    #[error("Unable to authenticate the message: {0}")]
+    AuthenticationFailed(String),
+}
+
+impl Error {
+    /// Whether the same request may succeed if it is sent again later
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            Error::RequestQueueFull(_) | Error::VoteDeadlineExceeded { .. }
+        )
+    }
 }
 
 impl From<anyhow::Error> for Error {
@@ -66,6 +182,69 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+impl From<RuleViolation> for Error {
+    fn from(violation: RuleViolation) -> Self {
+        match violation {
+            RuleViolation::EquivocatingProposal { round } => Self::EquivocatingProposal(round),
+            RuleViolation::ExtendsBelowPreferredRound { preferred_round } => {
+                Self::ProposalRoundLowerThenPreferredBlock { preferred_round }
+            }
+            RuleViolation::InvalidParent {
+                epoch,
+                parent_epoch,
+                parent_round,
+                round,
+            } => Self::InvalidParent {
+                epoch,
+                parent_epoch,
+                parent_round,
+                round,
+            },
+            RuleViolation::InvalidTimestamp {
+                parent_timestamp_usecs,
+                timestamp_usecs,
+            } => Self::InvalidTimestamp {
+                parent_timestamp_usecs,
+                timestamp_usecs,
+            },
+            RuleViolation::OldProposal {
+                last_voted_round,
+                proposal_round,
+            } => Self::OldProposal {
+                last_voted_round,
+                proposal_round,
+            },
+            RuleViolation::OldProposedRound {
+                highest_proposed_round,
+                proposal_round,
+            } => Self::OldProposedRound {
+                highest_proposed_round,
+                proposal_round,
+            },
+            RuleViolation::QuorumCertBelowPreferredRound { .. } => {
+                Self::InvalidQuorumCertificate("Preferred round too early".into())
+            }
+            RuleViolation::TimeoutBelowLastVotedRound {
+                last_voted_round,
+                timeout_round,
+            } => Self::BadTimeoutLastVotedRound(timeout_round, last_voted_round),
+            RuleViolation::TimeoutNotAbovePreferredRound {
+                preferred_round,
+                timeout_round,
+            } => Self::BadTimeoutPreferredRound(timeout_round, preferred_round),
+            RuleViolation::TimeoutRoundSkew {
+                highest_qc_round,
+                max_skew,
+                timeout_round,
+            } => Self::BadTimeoutRoundSkew {
+                highest_qc_round,
+                max_skew,
+                timeout_round,
+            },
+        }
+    }
+}
+
 impl From<lcs::Error> for Error {
     fn from(error: lcs::Error) -> Self {
         Self::SerializationError(format!("{}", error))
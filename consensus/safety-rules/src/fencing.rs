@@ -0,0 +1,168 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fencing allows an active / standby pair of SafetyRules instances to share a single storage.
+//! Only the holder of an unexpired signer lease may sign, and the lease is renewed by every
+//! signing operation. Once the active instance stops renewing, the lease expires and the next
+//! instance to sign takes it over by incrementing the fencing token. An instance that held an
+//! older token is fenced: it refuses to sign until it in turn takes over an expired lease.
+//!
+//! Secure storage offers no compare-and-swap, so a takeover is only confirmed on a best-effort
+//! basis by reading back the lease. Two instances racing for the same expired lease usually
+//! resolve in favor of the last writer, but both may read back their own write before the other
+//! lands and sign for a short while. Fencing narrows the window for a split brain, it does not
+//! close it.
+
+use crate::{error::Error, persistent_safety_storage::PersistentSafetyStorage};
+use anyhow::anyhow;
+use libra_config::config::FailoverConfig;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The lease as persisted in storage, it is kept in a single value so that it is written
+/// atomically.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Lease {
+    /// The signer id of the instance holding the lease
+    pub holder: String,
+    /// Incremented on every takeover
+    pub token: u64,
+    /// Milliseconds since the Unix epoch after which the lease may be taken over
+    pub expiration_ms: u64,
+}
+
+impl fmt::Display for Lease {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.token, self.expiration_ms, self.holder)
+    }
+}
+
+impl FromStr for Lease {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut split = s.splitn(3, ':');
+        let mut next = || {
+            split
+                .next()
+                .ok_or_else(|| anyhow!("Malformed lease: {}", s))
+        };
+        let token = next()?.parse()?;
+        let expiration_ms = next()?.parse()?;
+        let holder = next()?.to_string();
+        Ok(Self {
+            holder,
+            token,
+            expiration_ms,
+        })
+    }
+}
+
+/// The fencing state of a single SafetyRules instance.
+pub struct Fencing {
+    config: FailoverConfig,
+    /// The token of the lease this instance last held
+    token: Option<u64>,
+}
+
+impl Fencing {
+    pub fn new(config: FailoverConfig) -> Self {
+        Self {
+            config,
+            token: None,
+        }
+    }
+
+    /// Renews the lease held by this instance, or takes over the lease if it has expired. Fails
+    /// if another instance holds an unexpired lease.
+    pub fn acquire(&mut self, storage: &mut PersistentSafetyStorage) -> Result<(), Error> {
+        let now = now_ms();
+        let token = match storage.signer_lease()? {
+            Some(lease)
+                if Some(lease.token) == self.token && lease.holder == self.config.signer_id =>
+            {
+                lease.token
+            }
+            Some(lease) if lease.expiration_ms > now => {
+                return Err(Error::NotLeaseHolder {
+                    holder: lease.holder,
+                    expiration_ms: lease.expiration_ms,
+                })
+            }
+            Some(lease) => lease.token + 1,
+            None => 0,
+        };
+
+        let lease = Lease {
+            holder: self.config.signer_id.clone(),
+            token,
+            expiration_ms: now + self.config.lease_duration_ms,
+        };
+        storage.set_signer_lease(&lease)?;
+
+        match storage.signer_lease()? {
+            Some(stored) if stored == lease => {
+                self.token = Some(token);
+                Ok(())
+            }
+            Some(stored) => Err(Error::NotLeaseHolder {
+                holder: stored.holder,
+                expiration_ms: stored.expiration_ms,
+            }),
+            None => Err(Error::InternalError {
+                error: "Signer lease vanished after being written".into(),
+            }),
+        }
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the Unix epoch")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use libra_types::validator_signer::ValidatorSigner;
+
+    #[test]
+    fn test_lease_round_trip() {
+        let lease = Lease {
+            holder: "standby:1".into(),
+            token: 3,
+            expiration_ms: 1_000,
+        };
+        assert_eq!(Lease::from_str(&lease.to_string()).unwrap(), lease);
+        Lease::from_str("3:1000").unwrap_err();
+    }
+
+    #[test]
+    fn test_renewal_requires_holder() {
+        let signer = ValidatorSigner::from_int(0);
+        let mut storage = test_utils::test_storage(&signer);
+        let mut fencing = Fencing::new(FailoverConfig {
+            signer_id: "primary".into(),
+            lease_duration_ms: 60_000,
+        });
+        fencing.acquire(&mut storage).unwrap();
+
+        // Another instance holds a lease with the same token, e.g. after the lease was reset
+        let lease = Lease {
+            holder: "standby".into(),
+            token: 0,
+            expiration_ms: now_ms() + 60_000,
+        };
+        storage.set_signer_lease(&lease).unwrap();
+        match fencing.acquire(&mut storage) {
+            Err(Error::NotLeaseHolder { holder, .. }) => assert_eq!(holder, "standby"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
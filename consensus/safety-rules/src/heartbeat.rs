@@ -0,0 +1,49 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{fencing, Error, COUNTERS};
+use consensus_types::common::Round;
+use serde::{Deserialize, Serialize};
+
+/// The view of the consensus process as reported by its last heartbeat. A caller whose epoch lags
+/// the signer's is likely not the consensus process the signer is meant to serve, e.g., a stale
+/// instance left running after a failover.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CallerView {
+    pub epoch: u64,
+    pub round: Round,
+    /// When the heartbeat was received, in milliseconds since the Unix epoch
+    pub received_ms: u64,
+}
+
+impl CallerView {
+    pub fn new(epoch: u64, round: Round) -> Self {
+        let view = Self {
+            epoch,
+            round,
+            received_ms: fencing::now_ms(),
+        };
+        COUNTERS.caller_epoch.set(epoch as i64);
+        COUNTERS.caller_round.set(round as i64);
+        COUNTERS.caller_heartbeat_ms.set(view.received_ms as i64);
+        view
+    }
+
+    /// How many epochs the caller lags behind the given epoch of the signer.
+    pub fn epoch_lag(&self, epoch: u64) -> u64 {
+        epoch.saturating_sub(self.epoch)
+    }
+
+    /// Refuses signing if the caller lags the signer by more than the given number of epochs.
+    pub fn check_epoch_lag(&self, epoch: u64, max_lag: u64) -> Result<(), Error> {
+        let lag = self.epoch_lag(epoch);
+        if lag > max_lag {
+            return Err(Error::CallerEpochLag {
+                caller_epoch: self.epoch,
+                epoch,
+                max_lag,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use consensus_types::common::Author;
+use libra_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
+    HashValue, Signature, SigningKey,
+};
+
+/// The message an operator signs to endorse a consensus key, it binds the key to the validator
+/// so that an endorsement cannot be replayed for another validator sharing the operator.
+fn endorsement_message(author: Author, public_key: &Ed25519PublicKey) -> Result<HashValue> {
+    Ok(HashValue::from_iter_sha3(vec![
+        b"ConsensusKeyEndorsement".as_ref(),
+        lcs::to_bytes(&(author, public_key))?.as_slice(),
+    ]))
+}
+
+/// Endorses the consensus key of a validator with the operator key, the endorsement is stored
+/// next to the consensus key.
+pub fn endorse_consensus_key(
+    operator_key: &Ed25519PrivateKey,
+    author: Author,
+    public_key: &Ed25519PublicKey,
+) -> Result<Ed25519Signature> {
+    Ok(operator_key.sign_message(&endorsement_message(author, public_key)?))
+}
+
+pub fn verify_endorsement(
+    endorser: &Ed25519PublicKey,
+    author: Author,
+    public_key: &Ed25519PublicKey,
+    endorsement: &Ed25519Signature,
+) -> Result<()> {
+    endorsement.verify(&endorsement_message(author, public_key)?, endorser)
+}
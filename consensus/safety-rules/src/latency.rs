@@ -0,0 +1,93 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use libra_logger::warn;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Accumulates the time spent verifying and signing within the SafetyRules operation currently
+/// in progress. Everything else an operation does is dominated by storage access, so the storage
+/// time is reported as the remainder.
+#[derive(Clone, Default)]
+pub struct LatencyTracker {
+    timings: Arc<Timings>,
+}
+
+#[derive(Default)]
+struct Timings {
+    signing_nanos: AtomicU64,
+    verification_nanos: AtomicU64,
+}
+
+impl LatencyTracker {
+    /// Starts timing a new operation, the operation is reported once the returned timer is
+    /// dropped if it took longer than `budget_ms`.
+    pub fn timer(&self, operation: &'static str, budget_ms: u64) -> OperationTimer {
+        self.timings.signing_nanos.store(0, Ordering::Relaxed);
+        self.timings.verification_nanos.store(0, Ordering::Relaxed);
+        OperationTimer {
+            budget: Duration::from_millis(budget_ms),
+            operation,
+            start: Instant::now(),
+            timings: self.timings.clone(),
+        }
+    }
+
+    pub fn time_signing<R>(&self, f: impl FnOnce() -> R) -> R {
+        Self::time(&self.timings.signing_nanos, f)
+    }
+
+    pub fn time_verification<R>(&self, f: impl FnOnce() -> R) -> R {
+        Self::time(&self.timings.verification_nanos, f)
+    }
+
+    fn time<R>(nanos: &AtomicU64, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        nanos.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
+}
+
+pub struct OperationTimer {
+    budget: Duration,
+    operation: &'static str,
+    start: Instant,
+    timings: Arc<Timings>,
+}
+
+impl Drop for OperationTimer {
+    fn drop(&mut self) {
+        let total = self.start.elapsed();
+        if total <= self.budget {
+            return;
+        }
+
+        let operation = self.operation;
+        let budget_ms = self.budget.as_millis() as u64;
+        let total_ms = total.as_millis() as u64;
+        let signing = Duration::from_nanos(self.timings.signing_nanos.load(Ordering::Relaxed));
+        let verification =
+            Duration::from_nanos(self.timings.verification_nanos.load(Ordering::Relaxed));
+        let signing_ms = signing.as_millis() as u64;
+        let verification_ms = verification.as_millis() as u64;
+        let storage_ms = total
+            .checked_sub(signing + verification)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        warn!(
+            "SafetyRules {} took {}ms exceeding its budget of {}ms: storage {}ms, verification {}ms, signing {}ms",
+            operation,
+            total_ms,
+            budget_ms,
+            storage_ms,
+            verification_ms,
+            signing_ms,
+        );
+    }
+}
@@ -3,34 +3,97 @@
 
 #![forbid(unsafe_code)]
 
+mod accumulator_extension;
+mod admin;
+mod anomaly;
+mod audit_log;
+mod authentication;
+mod commit_stats;
 mod consensus_state;
 mod counters;
+mod epoch_summary;
 mod error;
+mod fencing;
+mod heartbeat;
+mod key_endorsement;
+mod latency;
 mod local_client;
+mod permissions;
 mod persistent_safety_storage;
 mod process;
+mod proposal_inspector;
+mod recovery;
+mod rejection;
 mod remote_service;
+mod replay;
+mod request_queue;
+pub mod rules;
 mod safety_rules;
 mod safety_rules_manager;
 mod serializer;
+mod signature_counts;
+mod signing_message;
 mod spawned_process;
 mod t_safety_rules;
 mod thread;
+mod trusted_checkpoint;
+mod validator_set_diff;
+mod verification_backend;
+mod verified_vote_proposal;
+mod waypoint_history;
+mod waypoint_reconciliation;
 
 pub use crate::{
-    consensus_state::ConsensusState, counters::COUNTERS, error::Error,
-    persistent_safety_storage::PersistentSafetyStorage, process::Process,
-    safety_rules::SafetyRules, safety_rules_manager::SafetyRulesManager,
+    admin::{send_admin_command, AdminCommand, Diagnostics},
+    anomaly::Anomaly,
+    audit_log::{AuditBatch, AuditEntry},
+    authentication::{AuthenticatedClient, AuthenticatedServer},
+    commit_stats::CommitStats,
+    consensus_state::ConsensusState,
+    counters::COUNTERS,
+    epoch_summary::EpochSummary,
+    error::Error,
+    heartbeat::CallerView,
+    key_endorsement::{endorse_consensus_key, verify_endorsement},
+    persistent_safety_storage::PersistentSafetyStorage,
+    process::Process,
+    proposal_inspector::ProposalInspector,
+    recovery::{RecoverySlot, SafetyDataSnapshot},
+    rejection::RejectionReport,
+    remote_service::MessageChannel,
+    replay::{Divergence, ReplayReport},
+    safety_rules::SafetyRules,
+    safety_rules_manager::{export_audit_log, replay_audit_log, SafetyRulesManager},
+    serializer::RequestId,
+    signature_counts::{SignatureCount, SignatureCounts},
+    signing_message::{proposal_signing_message, timeout_signing_message, SigningMessage},
     t_safety_rules::TSafetyRules,
+    trusted_checkpoint::TrustedCheckpoint,
+    validator_set_diff::{epoch_state_hash, ValidatorSetDiff},
+    verification_backend::{BatchVerification, IndividualVerification, VerificationBackend},
+    verified_vote_proposal::{VerifiedVoteProposal, VoteProposalVerifier},
+    waypoint_history::{WaypointRecord, MAX_WAYPOINT_HISTORY},
+    waypoint_reconciliation::{reconcile_waypoint, WaypointReconciliation},
 };
 
 #[cfg(any(test, feature = "testing"))]
 #[path = "process_client_wrapper.rs"]
 pub mod process_client_wrapper;
 
+#[cfg(any(test, feature = "testing"))]
+#[path = "simulated_network.rs"]
+pub mod simulated_network;
+
 #[cfg(any(test, feature = "testing"))]
 #[path = "test_utils.rs"]
 pub mod test_utils;
 
+#[cfg(any(test, feature = "testing"))]
+#[path = "test_vectors.rs"]
+pub mod test_vectors;
+
+#[cfg(feature = "unsafe_bench")]
+pub mod unsafe_bench;
+
 #[cfg(test)]
 mod tests;
@@ -1,13 +1,22 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{ConsensusState, Error, SafetyRules, TSafetyRules};
+use crate::{
+    CommitStats, ConsensusState, Error, SafetyRules, TSafetyRules, TrustedCheckpoint,
+    WaypointRecord,
+};
 use consensus_types::{
-    block::Block, block_data::BlockData, common::Payload, quorum_cert::QuorumCert,
-    timeout::Timeout, vote::Vote, vote_proposal::VoteProposal,
+    block::Block,
+    block_data::BlockData,
+    common::{Payload, Round},
+    quorum_cert::QuorumCert,
+    sync_info::SyncInfo,
+    timeout::Timeout,
+    vote::Vote,
+    vote_proposal::VoteProposal,
 };
 use libra_crypto::ed25519::Ed25519Signature;
-use libra_types::epoch_change::EpochChangeProof;
+use libra_types::{epoch_change::EpochChangeProof, epoch_state::EpochState};
 use std::sync::{Arc, RwLock};
 
 /// A local interface into SafetyRules. Constructed in such a way that the container / caller
@@ -28,14 +37,45 @@ impl<T: Payload> TSafetyRules<T> for LocalClient<T> {
         self.internal.write().unwrap().consensus_state()
     }
 
+    fn commit_stats(&mut self) -> Result<CommitStats, Error> {
+        self.internal.write().unwrap().commit_stats()
+    }
+
+    fn waypoint_history(&mut self) -> Result<Vec<WaypointRecord>, Error> {
+        self.internal.write().unwrap().waypoint_history()
+    }
+
+    fn current_epoch_state(&mut self) -> Result<EpochState, Error> {
+        self.internal.write().unwrap().current_epoch_state()
+    }
+
     fn initialize(&mut self, proof: &EpochChangeProof) -> Result<(), Error> {
         self.internal.write().unwrap().initialize(proof)
     }
 
+    fn initialize_from_trusted_state(
+        &mut self,
+        trusted_state: TrustedCheckpoint,
+        proof: &EpochChangeProof,
+    ) -> Result<(), Error> {
+        self.internal
+            .write()
+            .unwrap()
+            .initialize_from_trusted_state(trusted_state, proof)
+    }
+
     fn update(&mut self, qc: &QuorumCert) -> Result<(), Error> {
         self.internal.write().unwrap().update(qc)
     }
 
+    fn update_sync_info(&mut self, sync_info: &SyncInfo) -> Result<(), Error> {
+        self.internal.write().unwrap().update_sync_info(sync_info)
+    }
+
+    fn heartbeat(&mut self, epoch: u64, round: Round) -> Result<(), Error> {
+        self.internal.write().unwrap().heartbeat(epoch, round)
+    }
+
     fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error> {
         self.internal
             .write()
@@ -43,6 +83,17 @@ impl<T: Payload> TSafetyRules<T> for LocalClient<T> {
             .construct_and_sign_vote(vote_proposal)
     }
 
+    fn construct_and_sign_vote_with_deadline(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        deadline_ms: Option<u64>,
+    ) -> Result<Vote, Error> {
+        self.internal
+            .write()
+            .unwrap()
+            .construct_and_sign_vote_with_deadline(vote_proposal, deadline_ms)
+    }
+
     fn sign_proposal(&mut self, block_data: BlockData<T>) -> Result<Block<T>, Error> {
         self.internal.write().unwrap().sign_proposal(block_data)
     }
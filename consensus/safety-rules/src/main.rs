@@ -2,26 +2,59 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Usage: ./safety-rules node.config
+//!        ./safety-rules export-audit-log node.config output_file
+//!        ./safety-rules replay node.config audit_log_file
+//!        ./safety-rules admin node.config set-log-level [level]
+//!        ./safety-rules admin node.config dump-diagnostics
+//!        ./safety-rules admin node.config reset-to-waypoint waypoint proof_file
+//!        ./safety-rules admin node.config --confirm recovery_id
+//!        ./safety-rules admin node.config recover recovery_id
+//!
+//! The proof file of reset-to-waypoint holds an LCS encoded EpochChangeProof that leads from the
+//! stored waypoint to the new one.
 
 #![forbid(unsafe_code)]
 
-use libra_config::config::NodeConfig;
+use consensus_types::common::{Payload, Round};
+use libra_config::config::{ConsensusType, NodeConfig, SafetyRulesService};
 use libra_secure_push_metrics::MetricsPusher;
-use safety_rules::{Process, COUNTERS};
-use std::{env, process};
+use libra_types::{
+    epoch_change::EpochChangeProof, transaction::SignedTransaction, waypoint::Waypoint,
+};
+use safety_rules::{AdminCommand, AuditBatch, Process, COUNTERS};
+use std::{env, fs, process};
 
 fn main() {
+    // SafetyRules built with unsafe_bench may run against a storage that persists nothing, it must
+    // never sign for a validator
+    if cfg!(feature = "unsafe_bench") {
+        eprintln!("Refusing to start, safety-rules was built with the unsafe_bench feature");
+        process::exit(1);
+    }
+
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 2 {
-        eprintln!("Incorrect number of parameters, expected a path to a config file");
-        process::exit(1);
+    match args.len() {
+        2 => start(&args[1]),
+        4 if args[1] == "export-audit-log" => export_audit_log(&args[2], &args[3]),
+        4 if args[1] == "replay" => replay(&args[2], &args[3]),
+        4..=6 if args[1] == "admin" => admin(&args[2], &args[3], &args[4..]),
+        _ => {
+            eprintln!("Incorrect parameters, expected a path to a config file");
+            process::exit(1);
+        }
     }
+}
 
-    let config = NodeConfig::load(&args[1]).unwrap_or_else(|e| {
+fn load_config(path: &str) -> NodeConfig {
+    NodeConfig::load(path).unwrap_or_else(|e| {
         eprintln!("Unable to read provided config: {}", e);
         process::exit(1);
-    });
+    })
+}
+
+fn start(config_path: &str) {
+    let config = load_config(config_path);
 
     libra_logger::Logger::new()
         .channel_size(config.logger.chan_size)
@@ -32,3 +65,144 @@ fn main() {
     let mut service = Process::new(config);
     service.start();
 }
+
+/// Sends an admin command to the running SafetyRules process of the node.
+fn admin(config_path: &str, command: &str, arguments: &[String]) {
+    let command = match (command, arguments) {
+        ("set-log-level", []) => AdminCommand::SetLogLevel(None),
+        ("set-log-level", [level]) => AdminCommand::SetLogLevel(Some(level.clone())),
+        ("dump-diagnostics", []) => AdminCommand::DumpDiagnostics,
+        ("reset-to-waypoint", [waypoint, proof_path]) => {
+            AdminCommand::ResetToWaypoint(parse_waypoint(waypoint), read_proof(proof_path))
+        }
+        ("--confirm", [id]) => AdminCommand::ConfirmRecovery(parse_recovery_id(id)),
+        ("recover", [id]) => AdminCommand::Recover(parse_recovery_id(id)),
+        _ => {
+            eprintln!("Unknown admin command: {}", command);
+            process::exit(1);
+        }
+    };
+    let config = load_config(config_path);
+    let admin_config = config
+        .consensus
+        .safety_rules
+        .admin
+        .as_ref()
+        .unwrap_or_else(|| {
+            eprintln!("SafetyRules has no admin endpoint configured");
+            process::exit(1);
+        });
+    match safety_rules::send_admin_command(admin_config, command) {
+        Ok(outcome) => println!("{}", outcome),
+        Err(e) => {
+            eprintln!("Admin command failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_recovery_id(id: &str) -> u64 {
+    id.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid recovery id: {}", id);
+        process::exit(1);
+    })
+}
+
+fn parse_waypoint(waypoint: &str) -> Waypoint {
+    waypoint.parse().unwrap_or_else(|e| {
+        eprintln!("Invalid waypoint {}: {}", waypoint, e);
+        process::exit(1);
+    })
+}
+
+fn read_proof(proof_path: &str) -> EpochChangeProof {
+    fs::read(proof_path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| lcs::from_bytes(&bytes).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("Unable to read the epoch change proof: {}", e);
+            process::exit(1);
+        })
+}
+
+/// Writes the verified audit log batches retained in storage to the output file as LCS.
+fn export_audit_log(config_path: &str, output_path: &str) {
+    let config = load_config(config_path);
+    let batches =
+        safety_rules::export_audit_log(&config.consensus.safety_rules).unwrap_or_else(|e| {
+            eprintln!("Unable to export the audit log: {}", e);
+            process::exit(1);
+        });
+    let bytes = lcs::to_bytes(&batches).expect("Unable to serialize the audit log");
+    fs::write(output_path, bytes).unwrap_or_else(|e| {
+        eprintln!("Unable to write the audit log: {}", e);
+        process::exit(1);
+    });
+    let entries: usize = batches.iter().map(|batch| batch.entries.len()).sum();
+    println!(
+        "Exported {} audit log entries in {} batches",
+        entries,
+        batches.len()
+    );
+}
+
+/// Replays an exported audit log through a fresh SafetyRules instance and reports every request
+/// whose decision diverges from the recorded one, exiting with an error if any does.
+fn replay(config_path: &str, audit_log_path: &str) {
+    let config = load_config(config_path);
+    let batches: Vec<AuditBatch> = fs::read(audit_log_path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| lcs::from_bytes(&bytes).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("Unable to read the audit log: {}", e);
+            process::exit(1);
+        });
+
+    let consensus_type = match &config.consensus.safety_rules.service {
+        SafetyRulesService::Process(service) | SafetyRulesService::SpawnedProcess(service) => {
+            service.consensus_type
+        }
+        _ => ConsensusType::SignedTransactions,
+    };
+    match consensus_type {
+        ConsensusType::Bytes => replay_internal::<Vec<u8>>(&config, &batches),
+        ConsensusType::Rounds => replay_internal::<Round>(&config, &batches),
+        ConsensusType::SignedTransactions => {
+            replay_internal::<Vec<SignedTransaction>>(&config, &batches)
+        }
+    }
+}
+
+fn replay_internal<T: Payload>(config: &NodeConfig, batches: &[AuditBatch]) {
+    let author = config
+        .validator_network
+        .as_ref()
+        .unwrap_or_else(|| {
+            eprintln!("Missing validator network");
+            process::exit(1);
+        })
+        .peer_id;
+    let report = safety_rules::replay_audit_log::<T>(
+        &config.consensus.safety_rules,
+        author,
+        config.base.waypoint,
+        batches,
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Unable to replay the audit log: {}", e);
+        process::exit(1);
+    });
+
+    for divergence in &report.divergences {
+        println!("{}", divergence);
+    }
+    println!(
+        "Replayed {} audit log entries, {} dropped, {} diverged",
+        report.replayed,
+        report.dropped,
+        report.divergences.len()
+    );
+    if !report.divergences.is_empty() {
+        process::exit(1);
+    }
+}
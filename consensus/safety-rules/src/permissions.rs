@@ -0,0 +1,151 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Restricts which SafetyRules operations each remote client may request, so that read-only
+//! tooling, such as a monitor polling consensus_state, can connect to the signer without being able
+//! to sign. Clients are identified by the address of their connection, the only identity the
+//! transport currently establishes, and the check happens only once a request has been received
+//! over that connection.
+
+use crate::{serializer::OPERATIONS, Error};
+use libra_config::config::ClientPermissions;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct Permissions {
+    /// No restrictions apply if there are no clients
+    clients: HashMap<IpAddr, HashSet<String>>,
+}
+
+impl Permissions {
+    pub fn new(config: &[ClientPermissions]) -> Result<Self, Error> {
+        let mut clients = HashMap::new();
+        for client in config {
+            for operation in &client.operations {
+                if !OPERATIONS.contains(&operation.as_str()) {
+                    return Err(Error::InternalError {
+                        error: format!("Unknown SafetyRules operation: {}", operation),
+                    });
+                }
+            }
+            let operations = clients.entry(client.address).or_insert_with(HashSet::new);
+            operations.extend(client.operations.iter().cloned());
+        }
+        Ok(Self { clients })
+    }
+
+    /// Whether the client at the given address may request the operation. Once restrictions are
+    /// configured, a client that is not listed, or whose address is unknown, may request nothing.
+    pub fn check(&self, peer: Option<IpAddr>, operation: &str) -> Result<(), Error> {
+        if self.clients.is_empty() {
+            return Ok(());
+        }
+        let permitted = peer
+            .and_then(|peer| self.clients.get(&peer))
+            .map(|operations| operations.contains(operation))
+            .unwrap_or(false);
+        if permitted {
+            Ok(())
+        } else {
+            let peer = peer.map_or_else(|| "unknown".into(), |peer| peer.to_string());
+            Err(Error::Unauthorized(format!(
+                "Client {} may not request {}",
+                peer, operation
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        persistent_safety_storage::PersistentSafetyStorage,
+        serializer::{
+            RequestId, SafetyRulesInput, SafetyRulesRequest, SafetyRulesResponse, SerializerService,
+        },
+        ConsensusState, SafetyRules,
+    };
+    use consensus_types::{common::Round, timeout::Timeout};
+    use libra_crypto::ed25519::Ed25519Signature;
+    use libra_types::validator_signer::ValidatorSigner;
+
+    fn monitor() -> IpAddr {
+        "10.0.0.2".parse().unwrap()
+    }
+
+    fn consensus() -> IpAddr {
+        "10.0.0.1".parse().unwrap()
+    }
+
+    fn config() -> Vec<ClientPermissions> {
+        vec![
+            ClientPermissions {
+                address: consensus(),
+                operations: OPERATIONS.iter().map(|op| op.to_string()).collect(),
+            },
+            ClientPermissions {
+                address: monitor(),
+                operations: vec!["consensus_state".into()],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_permissions() {
+        Permissions::default().check(None, "sign_timeout").unwrap();
+
+        let permissions = Permissions::new(&config()).unwrap();
+        permissions
+            .check(Some(consensus()), "sign_timeout")
+            .unwrap();
+        permissions
+            .check(Some(monitor()), "consensus_state")
+            .unwrap();
+        permissions
+            .check(Some(monitor()), "sign_timeout")
+            .unwrap_err();
+        permissions
+            .check(Some("10.0.0.3".parse().unwrap()), "consensus_state")
+            .unwrap_err();
+        permissions.check(None, "consensus_state").unwrap_err();
+
+        let mut config = config();
+        config[1].operations.push("sign".into());
+        Permissions::new(&config).unwrap_err();
+    }
+
+    #[test]
+    fn test_denied_request() {
+        let signer = ValidatorSigner::from_int(0);
+        let storage = PersistentSafetyStorage::in_memory(signer.private_key().clone());
+        let safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+        let permissions = Permissions::new(&config()).unwrap();
+        let mut service = SerializerService::new(safety_rules).with_permissions(permissions);
+
+        let id = RequestId::random();
+        let request = |input| lcs::to_bytes(&SafetyRulesRequest::<Round> { id, input }).unwrap();
+        let output = |response: Vec<u8>| {
+            let response: SafetyRulesResponse = lcs::from_bytes(&response).unwrap();
+            assert_eq!(response.id, id);
+            response.output
+        };
+
+        let response = service
+            .handle_message_from(request(SafetyRulesInput::ConsensusState), Some(monitor()))
+            .unwrap();
+        lcs::from_bytes::<Result<ConsensusState, Error>>(&output(response))
+            .unwrap()
+            .unwrap();
+
+        let timeout = SafetyRulesInput::SignTimeout(Box::new(Timeout::new(1, 1)));
+        let response = service
+            .handle_message_from(request(timeout), Some(monitor()))
+            .unwrap();
+        let result: Result<Ed25519Signature, Error> = lcs::from_bytes(&output(response)).unwrap();
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+    }
+}
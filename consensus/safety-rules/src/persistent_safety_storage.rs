@@ -1,21 +1,72 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
+use crate::{
+    audit_log::{AuditBatch, AuditEntry, SignedAuditBatch},
+    epoch_summary::EpochSummary,
+    fencing::{self, Lease},
+    recovery::{RecoverySlot, SafetyDataSnapshot},
+    signature_counts::{SignatureCounts, SignatureKind},
+    waypoint_history::{WaypointRecord, MAX_WAYPOINT_HISTORY},
+};
+use anyhow::{anyhow, ensure, Result};
 use consensus_types::common::Round;
-use libra_crypto::ed25519::Ed25519PrivateKey;
-use libra_global_constants::{CONSENSUS_KEY, EPOCH, LAST_VOTED_ROUND, PREFERRED_ROUND, WAYPOINT};
-use libra_secure_storage::{InMemoryStorage, Storage, Value};
-use libra_types::waypoint::Waypoint;
-use std::str::FromStr;
+use libra_config::config::AuditLogConfig;
+use libra_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519Signature},
+    HashValue, Signature, ValidCryptoMaterialStringExt,
+};
+use libra_global_constants::{
+    CHAIN_ID, CONSENSUS_KEY, CONSENSUS_KEY_ENDORSEMENT, EPOCH, EPOCH_ENDING_LEDGER_INFO,
+    HIGHEST_PROPOSED_ROUND, LAST_PROPOSAL, LAST_VOTED_ROUND, NEXT_CONSENSUS_KEY,
+    NEXT_CONSENSUS_KEY_ENDORSEMENT, PREFERRED_ROUND, RECOVERY_SLOT, SAFETY_DATA_KEY,
+    SAFETY_DATA_SIGNATURE, SIGNATURE_COUNTS, SIGNER_LEASE, WAYPOINT, WAYPOINT_HISTORY,
+};
+use libra_logger::warn;
+use libra_secure_storage::{Error as StorageError, InMemoryStorage, Storage, Value};
+use libra_types::{ledger_info::LedgerInfoWithSignatures, waypoint::Waypoint};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// The values that make up the SafetyData, they are counter-signed as a whole when integrity
+/// checks are enabled. The chain id is among them, so that the storage cannot be rebound to
+/// another chain unnoticed.
+const SAFETY_DATA: &[&str] = &[
+    CHAIN_ID,
+    EPOCH,
+    HIGHEST_PROPOSED_ROUND,
+    LAST_PROPOSAL,
+    LAST_VOTED_ROUND,
+    PREFERRED_ROUND,
+    SIGNATURE_COUNTS,
+    WAYPOINT,
+    WAYPOINT_HISTORY,
+];
 
 /// SafetyRules needs an abstract storage interface to act as a common utility for storing
 /// persistent data to local disk, cloud, secrets managers, or even memory (for tests)
 /// Any set function is expected to sync to the remote system before returning.
+///
+/// If the storage holds a safety data key, every SafetyData write verifies the stored SafetyData
+/// and counter-signs all of its values at once, along with a write counter that increases with
+/// every write. This makes modifications made outside of SafetyRules, such as lowering the last
+/// voted round or restoring an older signed value, detectable by verify_integrity. Restoring all
+/// of the SafetyData to an earlier signed state is detected while this instance runs, as the
+/// counter may not go backward. A crash between writing a value and its signature also fails the
+/// check, which is intentional: it is safer to halt than to guess.
 /// @TODO add access to private key from persistent store
 /// @TODO add retrieval of private key based upon public key to persistent store
 pub struct PersistentSafetyStorage {
+    chain_id: Option<String>,
+    integrity_checks: bool,
     internal_store: Box<dyn Storage>,
+    /// Signature counts not yet written, along with when the oldest of them was recorded
+    pending_signature_counts: Option<(SignatureCounts, Instant)>,
+    /// The highest write counter of the SafetyData this instance has written
+    safety_data_counter: u64,
+    timeout_flush_window_ms: u64,
 }
 
 impl PersistentSafetyStorage {
@@ -25,34 +76,265 @@ impl PersistentSafetyStorage {
     }
 
     /// Use this to instantiate a PersistentStorage for a new data store, one that has no
-    /// SafetyRules values set.
+    /// SafetyRules values set. This also provisions the safety data key, so that all writes are
+    /// counter-signed.
     pub fn initialize(
-        mut internal_store: Box<dyn Storage>,
+        internal_store: Box<dyn Storage>,
         private_key: Ed25519PrivateKey,
         waypoint: Waypoint,
     ) -> Self {
-        Self::initialize_(internal_store.as_mut(), private_key, waypoint)
+        let mut storage = Self {
+            chain_id: None,
+            integrity_checks: true,
+            internal_store,
+            pending_signature_counts: None,
+            safety_data_counter: 0,
+            timeout_flush_window_ms: 0,
+        };
+        storage
+            .initialize_(private_key, waypoint)
             .expect("Unable to initialize backend storage");
-        Self { internal_store }
+        storage
     }
 
-    fn initialize_(
-        internal_store: &mut dyn Storage,
+    /// As initialize, but also binds the new data store to the given chain.
+    pub fn initialize_for_chain(
+        internal_store: Box<dyn Storage>,
         private_key: Ed25519PrivateKey,
         waypoint: Waypoint,
-    ) -> Result<()> {
-        internal_store.set(CONSENSUS_KEY, Value::Ed25519PrivateKey(private_key))?;
-        internal_store.set(EPOCH, Value::U64(1))?;
-        internal_store.set(LAST_VOTED_ROUND, Value::U64(0))?;
-        internal_store.set(PREFERRED_ROUND, Value::U64(0))?;
-        internal_store.set(WAYPOINT, Value::String(waypoint.to_string()))?;
+        chain_id: &str,
+    ) -> Self {
+        let mut storage = Self::initialize(internal_store, private_key, waypoint);
+        storage
+            .set_safety_data(CHAIN_ID, Value::String(chain_id.to_string()))
+            .expect("Unable to bind backend storage to its chain");
+        storage.chain_id = Some(chain_id.to_string());
+        storage
+    }
+
+    /// Instantiates a PersistentSafetyStorage that persists nothing past its provisioning and does
+    /// not counter-sign its writes, see unsafe_bench. Only for benchmarks.
+    #[cfg(feature = "unsafe_bench")]
+    pub fn unsafe_bench(private_key: Ed25519PrivateKey, waypoint: Waypoint) -> Self {
+        let mut storage = Self {
+            chain_id: None,
+            integrity_checks: false,
+            internal_store: Box::new(crate::unsafe_bench::NoopStorage::new()),
+            pending_signature_counts: None,
+            safety_data_counter: 0,
+            timeout_flush_window_ms: 0,
+        };
+        storage
+            .initialize_(private_key, waypoint)
+            .expect("Unable to initialize backend storage");
+        storage
+    }
+
+    fn initialize_(&mut self, private_key: Ed25519PrivateKey, waypoint: Waypoint) -> Result<()> {
+        self.internal_store
+            .set(CONSENSUS_KEY, Value::Ed25519PrivateKey(private_key))?;
+        if self.internal_store.get_public_key(SAFETY_DATA_KEY).is_err() {
+            self.internal_store.create_key(SAFETY_DATA_KEY)?;
+        }
+        // The values of a new data store carry no valid signature yet, so they are written as is
+        // and then signed as a whole
+        self.internal_store.set_durable(EPOCH, Value::U64(1))?;
+        self.internal_store
+            .set_durable(HIGHEST_PROPOSED_ROUND, Value::U64(0))?;
+        self.internal_store
+            .set_durable(LAST_PROPOSAL, Value::HashValue(HashValue::zero()))?;
+        self.internal_store
+            .set_durable(LAST_VOTED_ROUND, Value::U64(0))?;
+        self.internal_store
+            .set_durable(PREFERRED_ROUND, Value::U64(0))?;
+        self.internal_store.set_durable(
+            SIGNATURE_COUNTS,
+            Value::String(hex::encode(lcs::to_bytes(&SignatureCounts::default())?)),
+        )?;
+        if self.integrity_checks {
+            let safety_data = self.read_safety_data()?;
+            self.sign_safety_data(safety_data)?;
+        }
+        self.set_waypoint(1, &waypoint)?;
         Ok(())
     }
 
     /// Use this to instantiate a PersistentStorage with an existing data store. This is intended
-    /// for constructed environments.
+    /// for constructed environments. Integrity checks are enabled if the data store holds a
+    /// safety data key, see enable_integrity_checks for a data store without one.
     pub fn new(internal_store: Box<dyn Storage>) -> Self {
-        Self { internal_store }
+        let integrity_checks = internal_store.get_public_key(SAFETY_DATA_KEY).is_ok();
+        Self {
+            chain_id: None,
+            integrity_checks,
+            internal_store,
+            pending_signature_counts: None,
+            safety_data_counter: 0,
+            timeout_flush_window_ms: 0,
+        }
+    }
+
+    /// Checks that the storage backend is reachable.
+    pub fn available(&self) -> Result<()> {
+        Ok(self.internal_store.available()?)
+    }
+
+    pub fn integrity_checks_enabled(&self) -> bool {
+        self.integrity_checks
+    }
+
+    /// Whether the storage holds the safety data key, which signs SafetyData and audit batches.
+    pub fn has_safety_data_key(&self) -> bool {
+        self.internal_store.get_public_key(SAFETY_DATA_KEY).is_ok()
+    }
+
+    /// Provisions the safety data key for a storage that holds none, such as one set up by the
+    /// management tooling or before SafetyData was counter-signed, and signs its SafetyData as it
+    /// is found. Any value modified before this goes undetected, so it is done once, at startup.
+    pub fn enable_integrity_checks(&mut self) -> Result<()> {
+        ensure!(
+            !self.has_safety_data_key(),
+            "The storage already holds a safety data key"
+        );
+        self.internal_store.create_key(SAFETY_DATA_KEY)?;
+        self.integrity_checks = true;
+        let safety_data = self.read_safety_data()?;
+        self.sign_safety_data(safety_data)
+    }
+
+    /// Holds the signature counts of timeouts in memory for up to the given time, so that a burst
+    /// of timeouts writes them once rather than once per round. The counts are written along with
+    /// the next vote or proposal, the first timeout after the window, or on flush. Rounds are
+    /// unaffected, they are always written before a signature is produced.
+    pub fn set_timeout_flush_window_ms(&mut self, timeout_flush_window_ms: u64) {
+        self.timeout_flush_window_ms = timeout_flush_window_ms;
+    }
+
+    /// Writes any signature counts held in memory. This is done on drop and ahead of every epoch
+    /// change, reset or snapshot of the SafetyData, so that none of them leaves counts behind.
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some((signature_counts, _)) = self.pending_signature_counts {
+            self.set_signature_counts(&signature_counts)?;
+            self.pending_signature_counts = None;
+        }
+        Ok(())
+    }
+
+    /// Verifies that the SafetyData carries a valid signature from the safety data key and that its
+    /// write counter has not gone backward.
+    pub fn verify_integrity(&self) -> Result<()> {
+        self.verified_safety_data().map(|_| ())
+    }
+
+    /// Reads the SafetyData along with the write counter of its signature, without verifying it.
+    fn read_safety_data(&self) -> Result<StoredSafetyData> {
+        let mut values = Vec::with_capacity(SAFETY_DATA.len());
+        for key in SAFETY_DATA {
+            match self.internal_store.get(key) {
+                Ok(response) => values.push(Some(safety_data_bytes(key, &response.value)?)),
+                Err(StorageError::KeyNotSet(_)) => values.push(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let (counter, signature) = match self.internal_store.get(SAFETY_DATA_SIGNATURE) {
+            Ok(response) => {
+                let (counter, signature) = parse_safety_data_signature(&response.value.string()?)?;
+                (counter, Some(signature))
+            }
+            Err(StorageError::KeyNotSet(_)) => (0, None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(StoredSafetyData {
+            counter,
+            signature,
+            values,
+        })
+    }
+
+    fn verified_safety_data(&self) -> Result<StoredSafetyData> {
+        let public_key = self
+            .internal_store
+            .get_public_key(SAFETY_DATA_KEY)
+            .map_err(|e| anyhow!("Unable to retrieve the safety data key: {}", e))?
+            .public_key;
+        let safety_data = self.read_safety_data()?;
+        let signature = safety_data
+            .signature
+            .as_ref()
+            .ok_or_else(|| anyhow!("SafetyData has not been signed"))?;
+        signature
+            .verify(&safety_data.hash()?, &public_key)
+            .map_err(|_| anyhow!("SafetyData has been modified outside of SafetyRules"))?;
+        ensure!(
+            safety_data.counter >= self.safety_data_counter,
+            "SafetyData has been rolled back from write {} to write {}",
+            self.safety_data_counter,
+            safety_data.counter,
+        );
+        Ok(safety_data)
+    }
+
+    /// Signs the given SafetyData as the next write and stores the signature.
+    fn sign_safety_data(&mut self, mut safety_data: StoredSafetyData) -> Result<()> {
+        safety_data.counter = std::cmp::max(safety_data.counter, self.safety_data_counter) + 1;
+        let signature = self
+            .internal_store
+            .sign_message(SAFETY_DATA_KEY, &safety_data.hash()?)?;
+        self.internal_store.set_durable(
+            SAFETY_DATA_SIGNATURE,
+            Value::String(format!(
+                "{}:{}",
+                safety_data.counter,
+                signature.to_encoded_string()?
+            )),
+        )?;
+        self.safety_data_counter = safety_data.counter;
+        Ok(())
+    }
+
+    /// Writes a SafetyData value and, if integrity checks are enabled, verifies the SafetyData
+    /// before and counter-signs it after. Verifying first ensures that a write never signs off on
+    /// a value modified outside of SafetyRules. SafetyData is always written durably, as losing
+    /// any of it in a crash could let SafetyRules sign twice.
+    fn set_safety_data(&mut self, key: &str, value: Value) -> Result<()> {
+        if !self.integrity_checks {
+            self.internal_store.set_durable(key, value)?;
+            return Ok(());
+        }
+
+        let mut safety_data = self.verified_safety_data()?;
+        let index = SAFETY_DATA
+            .iter()
+            .position(|safety_data_key| *safety_data_key == key)
+            .ok_or_else(|| anyhow!("{} is not part of the SafetyData", key))?;
+        safety_data.values[index] = Some(safety_data_bytes(key, &value)?);
+        self.internal_store.set_durable(key, value)?;
+        self.sign_safety_data(safety_data)
+    }
+
+    /// Sets the chain this instance is opened for, binding the storage to it if it has not yet
+    /// been bound to a chain. A storage bound to another chain is left untouched, SafetyRules
+    /// refuses to initialize from it.
+    pub fn set_expected_chain_id(&mut self, chain_id: &str) -> Result<()> {
+        if self.chain_id()?.is_none() {
+            self.set_safety_data(CHAIN_ID, Value::String(chain_id.to_string()))?;
+        }
+        self.chain_id = Some(chain_id.to_string());
+        Ok(())
+    }
+
+    /// The chain id stored within the backend, if the storage has been bound to a chain.
+    pub fn chain_id(&self) -> Result<Option<String>> {
+        match self.internal_store.get(CHAIN_ID) {
+            Ok(response) => Ok(Some(response.value.string()?)),
+            Err(StorageError::KeyNotSet(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The chain id this instance was opened for, if any.
+    pub fn expected_chain_id(&self) -> Option<&str> {
+        self.chain_id.as_deref()
     }
 
     pub fn consensus_key(&self) -> Result<Ed25519PrivateKey> {
@@ -68,12 +350,115 @@ impl PersistentSafetyStorage {
         Ok(())
     }
 
+    /// A consensus key staged next to the current one, such as a key that the validator set has
+    /// yet to adopt.
+    pub fn next_consensus_key(&self) -> Result<Option<Ed25519PrivateKey>> {
+        match self.internal_store.get(NEXT_CONSENSUS_KEY) {
+            Ok(response) => Ok(Some(response.value.ed25519_private_key()?)),
+            Err(StorageError::KeyNotSet(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_next_consensus_key(&mut self, consensus_key: Ed25519PrivateKey) -> Result<()> {
+        self.internal_store
+            .set(NEXT_CONSENSUS_KEY, Value::Ed25519PrivateKey(consensus_key))?;
+        Ok(())
+    }
+
+    /// The operator's endorsement of the consensus key, see key_endorsement.
+    pub fn consensus_key_endorsement(&self) -> Result<Option<Ed25519Signature>> {
+        self.endorsement(CONSENSUS_KEY_ENDORSEMENT)
+    }
+
+    pub fn set_consensus_key_endorsement(&mut self, endorsement: &Ed25519Signature) -> Result<()> {
+        self.set_endorsement(CONSENSUS_KEY_ENDORSEMENT, endorsement)
+    }
+
+    /// The operator's endorsement of the next consensus key, see key_endorsement.
+    pub fn next_consensus_key_endorsement(&self) -> Result<Option<Ed25519Signature>> {
+        self.endorsement(NEXT_CONSENSUS_KEY_ENDORSEMENT)
+    }
+
+    pub fn set_next_consensus_key_endorsement(
+        &mut self,
+        endorsement: &Ed25519Signature,
+    ) -> Result<()> {
+        self.set_endorsement(NEXT_CONSENSUS_KEY_ENDORSEMENT, endorsement)
+    }
+
+    fn endorsement(&self, key: &str) -> Result<Option<Ed25519Signature>> {
+        match self.internal_store.get(key) {
+            Ok(response) => Ok(Some(Ed25519Signature::from_encoded_string(
+                &response.value.string()?,
+            )?)),
+            Err(StorageError::KeyNotSet(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_endorsement(&mut self, key: &str, endorsement: &Ed25519Signature) -> Result<()> {
+        self.internal_store
+            .set(key, Value::String(endorsement.to_encoded_string()?))?;
+        Ok(())
+    }
+
     pub fn epoch(&self) -> Result<u64> {
         Ok(self.internal_store.get(EPOCH).and_then(|r| r.value.u64())?)
     }
 
     pub fn set_epoch(&mut self, epoch: u64) -> Result<()> {
-        self.internal_store.set(EPOCH, Value::U64(epoch))?;
+        self.flush()?;
+        self.set_safety_data(EPOCH, Value::U64(epoch))?;
+        Ok(())
+    }
+
+    /// The ledger info that began the current epoch, if one has been stored. It is not
+    /// counter-signed, it is verified against the waypoint before it is trusted.
+    pub fn epoch_ending_ledger_info(&self) -> Result<Option<LedgerInfoWithSignatures>> {
+        match self.internal_store.get(EPOCH_ENDING_LEDGER_INFO) {
+            Ok(response) => Ok(Some(lcs::from_bytes(&hex::decode(
+                response.value.string()?,
+            )?)?)),
+            Err(StorageError::KeyNotSet(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_epoch_ending_ledger_info(
+        &mut self,
+        ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<()> {
+        self.internal_store.set(
+            EPOCH_ENDING_LEDGER_INFO,
+            Value::String(hex::encode(lcs::to_bytes(ledger_info)?)),
+        )?;
+        Ok(())
+    }
+
+    pub fn highest_proposed_round(&self) -> Result<Round> {
+        Ok(self
+            .internal_store
+            .get(HIGHEST_PROPOSED_ROUND)
+            .and_then(|r| r.value.u64())?)
+    }
+
+    pub fn set_highest_proposed_round(&mut self, highest_proposed_round: Round) -> Result<()> {
+        self.set_safety_data(HIGHEST_PROPOSED_ROUND, Value::U64(highest_proposed_round))?;
+        Ok(())
+    }
+
+    /// The hash of the BlockData most recently signed as a proposal, this is the only proposal
+    /// that may be signed again at the highest proposed round.
+    pub fn last_proposal(&self) -> Result<HashValue> {
+        Ok(self
+            .internal_store
+            .get(LAST_PROPOSAL)
+            .and_then(|r| r.value.hash_value())?)
+    }
+
+    pub fn set_last_proposal(&mut self, last_proposal: HashValue) -> Result<()> {
+        self.set_safety_data(LAST_PROPOSAL, Value::HashValue(last_proposal))?;
         Ok(())
     }
 
@@ -85,8 +470,7 @@ impl PersistentSafetyStorage {
     }
 
     pub fn set_last_voted_round(&mut self, last_voted_round: Round) -> Result<()> {
-        self.internal_store
-            .set(LAST_VOTED_ROUND, Value::U64(last_voted_round))?;
+        self.set_safety_data(LAST_VOTED_ROUND, Value::U64(last_voted_round))?;
         Ok(())
     }
 
@@ -98,8 +482,194 @@ impl PersistentSafetyStorage {
     }
 
     pub fn set_preferred_round(&mut self, preferred_round: Round) -> Result<()> {
+        self.set_safety_data(PREFERRED_ROUND, Value::U64(preferred_round))?;
+        Ok(())
+    }
+
+    /// The signatures issued by the consensus key, storage provisioned before these were tracked
+    /// starts counting from zero.
+    pub fn signature_counts(&self) -> Result<SignatureCounts> {
+        if let Some((signature_counts, _)) = self.pending_signature_counts {
+            return Ok(signature_counts);
+        }
+        match self.internal_store.get(SIGNATURE_COUNTS) {
+            Ok(response) => Ok(lcs::from_bytes(&hex::decode(response.value.string()?)?)?),
+            Err(StorageError::KeyNotSet(_)) => Ok(SignatureCounts::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_signature_counts(&mut self, signature_counts: &SignatureCounts) -> Result<()> {
+        self.set_safety_data(
+            SIGNATURE_COUNTS,
+            Value::String(hex::encode(lcs::to_bytes(signature_counts)?)),
+        )
+    }
+
+    /// Counts a signature about to be issued in the given epoch. This must succeed before the
+    /// signature is produced. The counts of timeouts may be held in memory, see
+    /// set_timeout_flush_window_ms.
+    pub fn record_signature(&mut self, epoch: u64, kind: SignatureKind) -> Result<()> {
+        let mut signature_counts = self.signature_counts()?;
+        signature_counts.record(epoch, kind);
+        if kind == SignatureKind::Timeout && self.timeout_flush_window_ms > 0 {
+            let since = self
+                .pending_signature_counts
+                .map_or_else(Instant::now, |(_, since)| since);
+            if since.elapsed() < Duration::from_millis(self.timeout_flush_window_ms) {
+                self.pending_signature_counts = Some((signature_counts, since));
+                return Ok(());
+            }
+        }
+        self.set_signature_counts(&signature_counts)?;
+        self.pending_signature_counts = None;
+        Ok(())
+    }
+
+    /// Resets the safety data to the start of the given epoch at the given waypoint. The epoch is
+    /// written before the rounds are cleared, so that an interrupted reset leaves the stored rounds
+    /// as restrictive as they were before, and the waypoint is written first so that SafetyRules
+    /// can only be initialized against the new one.
+    pub fn reset(&mut self, epoch: u64, waypoint: &Waypoint) -> Result<()> {
+        self.set_waypoint(epoch, waypoint)?;
+        self.set_epoch(epoch)?;
+        self.set_last_voted_round(0)?;
+        self.set_preferred_round(0)?;
+        self.set_highest_proposed_round(0)?;
+        self.set_last_proposal(HashValue::zero())?;
+        Ok(())
+    }
+
+    /// The rounds, epoch and waypoint that make up the SafetyData.
+    pub fn safety_data_snapshot(&self) -> Result<SafetyDataSnapshot> {
+        Ok(SafetyDataSnapshot {
+            epoch: self.epoch()?,
+            highest_proposed_round: self.highest_proposed_round()?,
+            last_proposal: self.last_proposal()?,
+            last_voted_round: self.last_voted_round()?,
+            preferred_round: self.preferred_round()?,
+            waypoint: self.waypoint()?,
+        })
+    }
+
+    /// Writes back a snapshot taken by safety_data_snapshot, in the same order as reset.
+    pub fn restore_safety_data(&mut self, snapshot: &SafetyDataSnapshot) -> Result<()> {
+        self.set_waypoint(snapshot.epoch, &snapshot.waypoint)?;
+        self.set_epoch(snapshot.epoch)?;
+        self.set_last_voted_round(snapshot.last_voted_round)?;
+        self.set_preferred_round(snapshot.preferred_round)?;
+        self.set_highest_proposed_round(snapshot.highest_proposed_round)?;
+        self.set_last_proposal(snapshot.last_proposal)?;
+        Ok(())
+    }
+
+    /// The SafetyData held for an operation that awaits confirmation, if any.
+    pub fn recovery_slot(&self) -> Result<Option<RecoverySlot>> {
+        match self.internal_store.get(RECOVERY_SLOT) {
+            Ok(response) => Ok(lcs::from_bytes(&hex::decode(response.value.string()?)?)?),
+            Err(StorageError::KeyNotSet(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fills the recovery slot, or purges it if None. Storage offers no deletion, so an empty
+    /// slot is written in its place.
+    pub fn set_recovery_slot(&mut self, slot: Option<&RecoverySlot>) -> Result<()> {
+        self.flush()?;
+        self.internal_store.set_durable(
+            RECOVERY_SLOT,
+            Value::String(hex::encode(lcs::to_bytes(&slot)?)),
+        )?;
+        Ok(())
+    }
+
+    /// Appends a batch of audit entries and epoch summaries, signed by the safety data key, to the
+    /// audit log. The batches are stored in a ring of `retained_batches` slots, overwriting the
+    /// oldest batch once the ring is full.
+    pub fn append_audit_batch(
+        &mut self,
+        config: &AuditLogConfig,
+        dropped: u64,
+        entries: Vec<AuditEntry>,
+        epoch_summaries: Vec<EpochSummary>,
+    ) -> Result<()> {
+        let index = self.next_audit_batch(config)?;
+        let batch = AuditBatch {
+            index,
+            dropped,
+            entries,
+            epoch_summaries,
+        };
+        let signature = self
+            .internal_store
+            .sign_message(SAFETY_DATA_KEY, &batch.hash()?)?;
+        let signed_batch = SignedAuditBatch { batch, signature };
+        self.internal_store.set(
+            &audit_batch_key(config, index),
+            Value::String(hex::encode(lcs::to_bytes(&signed_batch)?)),
+        )?;
+        self.internal_store
+            .set(&audit_key(config, NEXT_AUDIT_BATCH), Value::U64(index + 1))?;
+        Ok(())
+    }
+
+    /// Returns the retained audit batches from oldest to newest, after verifying that each was
+    /// signed by the safety data key.
+    pub fn audit_batches(&self, config: &AuditLogConfig) -> Result<Vec<AuditBatch>> {
+        let public_key = self
+            .internal_store
+            .get_public_key(SAFETY_DATA_KEY)
+            .map_err(|e| anyhow!("Unable to retrieve the safety data key: {}", e))?
+            .public_key;
+        let next = self.next_audit_batch(config)?;
+        let first = next.saturating_sub(config.retained_batches.max(1));
+
+        let mut batches = Vec::new();
+        for index in first..next {
+            let encoded = self
+                .internal_store
+                .get(&audit_batch_key(config, index))?
+                .value
+                .string()?;
+            let signed_batch: SignedAuditBatch = lcs::from_bytes(&hex::decode(encoded)?)?;
+            ensure!(
+                signed_batch.batch.index == index,
+                "Audit batch {} was found in the slot of batch {}",
+                signed_batch.batch.index,
+                index
+            );
+            signed_batch
+                .signature
+                .verify(&signed_batch.batch.hash()?, &public_key)
+                .map_err(|_| anyhow!("Audit batch {} has an invalid signature", index))?;
+            batches.push(signed_batch.batch);
+        }
+        Ok(batches)
+    }
+
+    fn next_audit_batch(&self, config: &AuditLogConfig) -> Result<u64> {
+        match self
+            .internal_store
+            .get(&audit_key(config, NEXT_AUDIT_BATCH))
+        {
+            Ok(response) => Ok(response.value.u64()?),
+            Err(StorageError::KeyNotSet(_)) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The signer lease shared by a failover pair, if one has been acquired.
+    pub fn signer_lease(&self) -> Result<Option<Lease>> {
+        match self.internal_store.get(SIGNER_LEASE) {
+            Ok(response) => Ok(Some(Lease::from_str(&response.value.string()?)?)),
+            Err(StorageError::KeyNotSet(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_signer_lease(&mut self, lease: &Lease) -> Result<()> {
         self.internal_store
-            .set(PREFERRED_ROUND, Value::U64(preferred_round))?;
+            .set_durable(SIGNER_LEASE, Value::String(lease.to_string()))?;
         Ok(())
     }
 
@@ -111,16 +681,112 @@ impl PersistentSafetyStorage {
         Waypoint::from_str(&waypoint)
     }
 
-    pub fn set_waypoint(&mut self, waypoint: &Waypoint) -> Result<()> {
-        self.internal_store
-            .set(WAYPOINT, Value::String(waypoint.to_string()))?;
-        Ok(())
+    /// Sets the waypoint that begins the given epoch and appends it to the waypoint history,
+    /// unless it is already the most recent entry.
+    pub fn set_waypoint(&mut self, epoch: u64, waypoint: &Waypoint) -> Result<()> {
+        self.flush()?;
+        self.set_safety_data(WAYPOINT, Value::String(waypoint.to_string()))?;
+
+        let mut history = self.waypoint_history()?;
+        if history.last().map(|record| (record.epoch, record.waypoint)) == Some((epoch, *waypoint))
+        {
+            return Ok(());
+        }
+        history.push(WaypointRecord {
+            epoch,
+            timestamp_ms: fencing::now_ms(),
+            waypoint: *waypoint,
+        });
+        let excess = history.len().saturating_sub(MAX_WAYPOINT_HISTORY);
+        history.drain(..excess);
+        self.set_safety_data(
+            WAYPOINT_HISTORY,
+            Value::String(hex::encode(lcs::to_bytes(&history)?)),
+        )
     }
+
+    /// The waypoints this storage has been bound to from oldest to newest, bounded to the most
+    /// recent MAX_WAYPOINT_HISTORY.
+    pub fn waypoint_history(&self) -> Result<Vec<WaypointRecord>> {
+        match self.internal_store.get(WAYPOINT_HISTORY) {
+            Ok(response) => Ok(lcs::from_bytes(&hex::decode(response.value.string()?)?)?),
+            Err(StorageError::KeyNotSet(_)) => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for PersistentSafetyStorage {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Unable to write the signature counts held in memory: {}", e);
+        }
+    }
+}
+
+const NEXT_AUDIT_BATCH: &str = "next_batch";
+
+fn audit_key(config: &AuditLogConfig, key: &str) -> String {
+    format!("{}/{}", config.namespace, key)
+}
+
+fn audit_batch_key(config: &AuditLogConfig, index: u64) -> String {
+    audit_key(
+        config,
+        &(index % config.retained_batches.max(1)).to_string(),
+    )
+}
+
+/// The SafetyData as stored, each value is absent if it has not been set.
+struct StoredSafetyData {
+    counter: u64,
+    signature: Option<Ed25519Signature>,
+    values: Vec<Option<Vec<u8>>>,
+}
+
+impl StoredSafetyData {
+    /// The message the safety data key signs, it covers the write counter and every value.
+    fn hash(&self) -> Result<HashValue> {
+        let mut buffers = vec![b"SafetyData".to_vec(), lcs::to_bytes(&self.counter)?];
+        for (key, value) in SAFETY_DATA.iter().zip(&self.values) {
+            buffers.push(key.as_bytes().to_vec());
+            buffers.push(lcs::to_bytes(value)?);
+        }
+        Ok(HashValue::from_iter_sha3(
+            buffers.iter().map(|buffer| buffer.as_slice()),
+        ))
+    }
+}
+
+fn safety_data_bytes(key: &str, value: &Value) -> Result<Vec<u8>> {
+    Ok(match value {
+        Value::HashValue(value) => lcs::to_bytes(value)?,
+        Value::String(value) => lcs::to_bytes(value)?,
+        Value::U64(value) => lcs::to_bytes(value)?,
+        _ => return Err(anyhow!("{} does not hold a SafetyData value", key)),
+    })
+}
+
+/// The signature of the SafetyData is stored as counter:signature, so that both are written at
+/// once.
+fn parse_safety_data_signature(value: &str) -> Result<(u64, Ed25519Signature)> {
+    let mut split = value.splitn(2, ':');
+    let counter = split
+        .next()
+        .ok_or_else(|| anyhow!("Malformed SafetyData signature"))?
+        .parse()?;
+    let signature = split
+        .next()
+        .ok_or_else(|| anyhow!("Malformed SafetyData signature"))?;
+    let signature = Ed25519Signature::from_encoded_string(signature)
+        .map_err(|e| anyhow!("Malformed SafetyData signature: {}", e))?;
+    Ok((counter, signature))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use libra_secure_storage::OnDiskStorage;
     use libra_types::validator_signer::ValidatorSigner;
 
     #[test]
@@ -130,11 +796,198 @@ mod tests {
         assert_eq!(storage.epoch().unwrap(), 1);
         assert_eq!(storage.last_voted_round().unwrap(), 0);
         assert_eq!(storage.preferred_round().unwrap(), 0);
+        assert_eq!(storage.highest_proposed_round().unwrap(), 0);
+        assert_eq!(storage.last_proposal().unwrap(), HashValue::zero());
         storage.set_epoch(9).unwrap();
         storage.set_last_voted_round(8).unwrap();
         storage.set_preferred_round(1).unwrap();
+        storage.set_highest_proposed_round(7).unwrap();
+        storage.set_last_proposal(HashValue::random()).unwrap();
         assert_eq!(storage.epoch().unwrap(), 9);
         assert_eq!(storage.last_voted_round().unwrap(), 8);
         assert_eq!(storage.preferred_round().unwrap(), 1);
+        assert_eq!(storage.highest_proposed_round().unwrap(), 7);
+        assert_ne!(storage.last_proposal().unwrap(), HashValue::zero());
+    }
+
+    #[test]
+    fn test_integrity() {
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let mut storage = PersistentSafetyStorage::in_memory(private_key);
+        assert!(storage.integrity_checks_enabled());
+        assert!(storage.has_safety_data_key());
+        storage.verify_integrity().unwrap();
+        assert!(
+            !PersistentSafetyStorage::new(Box::new(InMemoryStorage::new())).has_safety_data_key()
+        );
+
+        let initial_signature = storage.internal_store.get(SAFETY_DATA_SIGNATURE).unwrap();
+
+        storage.set_last_voted_round(8).unwrap();
+        storage.verify_integrity().unwrap();
+
+        // A value modified outside of SafetyRules is never signed off on by a later write
+        storage
+            .internal_store
+            .set(LAST_VOTED_ROUND, Value::U64(2))
+            .unwrap();
+        storage.verify_integrity().unwrap_err();
+        storage.set_preferred_round(1).unwrap_err();
+
+        // Restoring the last voted round along with the signature it was first written with is a
+        // rollback of the whole SafetyData
+        storage
+            .internal_store
+            .set(LAST_VOTED_ROUND, Value::U64(0))
+            .unwrap();
+        storage
+            .internal_store
+            .set(SAFETY_DATA_SIGNATURE, initial_signature.value)
+            .unwrap();
+        storage.verify_integrity().unwrap_err();
+    }
+
+    #[test]
+    fn test_enable_integrity_checks() {
+        // As provisioned by the management tooling
+        let mut internal_store = InMemoryStorage::new();
+        internal_store.set(EPOCH, Value::U64(0)).unwrap();
+        internal_store.set(LAST_VOTED_ROUND, Value::U64(0)).unwrap();
+        internal_store
+            .set(WAYPOINT, Value::String("".into()))
+            .unwrap();
+        let mut storage = PersistentSafetyStorage::new(Box::new(internal_store));
+        assert!(!storage.integrity_checks_enabled());
+        storage.set_last_voted_round(3).unwrap();
+
+        storage.enable_integrity_checks().unwrap();
+        assert!(storage.integrity_checks_enabled());
+        storage.verify_integrity().unwrap();
+        assert_eq!(storage.last_voted_round().unwrap(), 3);
+        storage.set_last_voted_round(4).unwrap();
+        storage.verify_integrity().unwrap();
+
+        storage
+            .internal_store
+            .set(LAST_VOTED_ROUND, Value::U64(2))
+            .unwrap();
+        storage.verify_integrity().unwrap_err();
+        // The storage is never signed as it is found again
+        storage.enable_integrity_checks().unwrap_err();
+    }
+
+    #[test]
+    fn test_recovery_slot() {
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let mut storage = PersistentSafetyStorage::in_memory(private_key);
+        assert_eq!(storage.recovery_slot().unwrap(), None);
+
+        storage.set_last_voted_round(8).unwrap();
+        let snapshot = storage.safety_data_snapshot().unwrap();
+        let slot = RecoverySlot::new("reset_to_waypoint", 1, snapshot.clone());
+        storage.set_recovery_slot(Some(&slot)).unwrap();
+        assert_eq!(storage.recovery_slot().unwrap(), Some(slot));
+
+        storage.reset(2, &snapshot.waypoint).unwrap();
+        assert_eq!(storage.last_voted_round().unwrap(), 0);
+        storage.restore_safety_data(&snapshot).unwrap();
+        assert_eq!(storage.safety_data_snapshot().unwrap(), snapshot);
+        storage.verify_integrity().unwrap();
+
+        storage.set_recovery_slot(None).unwrap();
+        assert_eq!(storage.recovery_slot().unwrap(), None);
+    }
+
+    #[test]
+    fn test_timeout_flush_window() {
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let mut storage = PersistentSafetyStorage::in_memory(private_key);
+        storage.set_timeout_flush_window_ms(60_000);
+        let stored = |storage: &PersistentSafetyStorage| {
+            let response = storage.internal_store.get(SIGNATURE_COUNTS).unwrap();
+            let counts: SignatureCounts =
+                lcs::from_bytes(&hex::decode(response.value.string().unwrap()).unwrap()).unwrap();
+            counts.epoch_count.total()
+        };
+
+        // Timeouts are counted in memory
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        assert_eq!(storage.signature_counts().unwrap().epoch_count.total(), 2);
+        assert_eq!(stored(&storage), 0);
+
+        // A vote writes them along with its own
+        storage.record_signature(1, SignatureKind::Vote).unwrap();
+        assert_eq!(stored(&storage), 3);
+
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        assert_eq!(stored(&storage), 3);
+        storage.flush().unwrap();
+        assert_eq!(stored(&storage), 4);
+        storage.verify_integrity().unwrap();
+
+        // Without a window every timeout is written
+        storage.set_timeout_flush_window_ms(0);
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        assert_eq!(stored(&storage), 5);
+
+        // An epoch change writes the counts before it
+        storage.set_timeout_flush_window_ms(60_000);
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        storage.set_epoch(2).unwrap();
+        assert_eq!(stored(&storage), 6);
+        storage.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn test_timeout_flush_window_restart() {
+        let temppath = libra_temppath::TempPath::new();
+        temppath.create_as_file().unwrap();
+        let open = || Box::new(OnDiskStorage::new(temppath.path().to_path_buf()));
+
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let mut storage =
+            PersistentSafetyStorage::initialize(open(), private_key, Waypoint::default());
+        storage.set_timeout_flush_window_ms(60_000);
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        storage.record_signature(1, SignatureKind::Timeout).unwrap();
+        drop(storage);
+
+        let storage = PersistentSafetyStorage::new(open());
+        storage.verify_integrity().unwrap();
+        assert_eq!(storage.signature_counts().unwrap().epoch_count.total(), 2);
+    }
+
+    #[test]
+    fn test_chain_id() {
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let mut storage = PersistentSafetyStorage::in_memory(private_key);
+        assert_eq!(storage.chain_id().unwrap(), None);
+        assert_eq!(storage.expected_chain_id(), None);
+        storage.set_expected_chain_id("testnet").unwrap();
+        assert_eq!(storage.chain_id().unwrap(), Some("testnet".to_string()));
+        // A storage bound to a chain is never rebound
+        storage.set_expected_chain_id("mainnet").unwrap();
+        assert_eq!(storage.chain_id().unwrap(), Some("testnet".to_string()));
+        assert_eq!(storage.expected_chain_id(), Some("mainnet"));
+        storage.verify_integrity().unwrap();
+
+        // Rebinding the storage outside of SafetyRules is detected
+        storage
+            .internal_store
+            .set(CHAIN_ID, Value::String("mainnet".to_string()))
+            .unwrap();
+        storage.verify_integrity().unwrap_err();
+
+        let private_key = ValidatorSigner::from_int(0).private_key().clone();
+        let storage = PersistentSafetyStorage::initialize_for_chain(
+            Box::new(InMemoryStorage::new()),
+            private_key,
+            Waypoint::default(),
+            "testnet",
+        );
+        assert_eq!(storage.chain_id().unwrap(), Some("testnet".to_string()));
+        assert_eq!(storage.expected_chain_id(), Some("testnet"));
+        storage.verify_integrity().unwrap();
     }
 }
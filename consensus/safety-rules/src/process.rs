@@ -7,7 +7,9 @@ use crate::{
     safety_rules_manager,
 };
 use consensus_types::common::{Author, Payload, Round};
-use libra_config::config::{ConsensusType, NodeConfig, SafetyRulesService};
+use libra_config::config::{
+    ConsensusType, NodeConfig, RemoteAuthentication, SafetyRulesConfig, SafetyRulesService,
+};
 use libra_types::transaction::SignedTransaction;
 use std::{marker::PhantomData, net::SocketAddr};
 
@@ -32,6 +34,7 @@ impl Process {
             consensus_type: service.consensus_type,
             data: Some(ProcessData {
                 author,
+                config: config.consensus.safety_rules.clone(),
                 server_addr,
                 storage,
             }),
@@ -48,25 +51,28 @@ impl Process {
 
     fn start_internal<T: Payload>(&mut self) {
         let data = self.data.take().expect("Unable to retrieve ProcessData");
-        remote_service::execute::<T>(data.author, data.storage, data.server_addr);
+        remote_service::execute::<T>(data.author, data.storage, data.server_addr, data.config);
     }
 }
 
 struct ProcessData {
     author: Author,
+    config: SafetyRulesConfig,
     server_addr: SocketAddr,
     storage: PersistentSafetyStorage,
 }
 
 pub struct ProcessService<T> {
     server_addr: SocketAddr,
+    authentication: Option<RemoteAuthentication>,
     phantom_data: PhantomData<T>,
 }
 
 impl<T> ProcessService<T> {
-    pub fn new(server_addr: SocketAddr) -> Self {
+    pub fn new(server_addr: SocketAddr, authentication: Option<RemoteAuthentication>) -> Self {
         Self {
             server_addr,
+            authentication,
             phantom_data: PhantomData,
         }
     }
@@ -76,4 +82,8 @@ impl<T: Payload> RemoteService<T> for ProcessService<T> {
     fn server_address(&self) -> SocketAddr {
         self.server_addr
     }
+
+    fn authentication(&self) -> Option<&RemoteAuthentication> {
+        self.authentication.as_ref()
+    }
 }
@@ -1,22 +1,31 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, ConsensusState, Error, SafetyRulesManager, TSafetyRules};
+use crate::{
+    test_utils, CommitStats, ConsensusState, Error, SafetyRulesManager, TSafetyRules,
+    TrustedCheckpoint, WaypointRecord,
+};
 use consensus_types::{
     block::Block,
     block_data::BlockData,
     common::{Payload, Round},
     quorum_cert::QuorumCert,
+    sync_info::SyncInfo,
     timeout::Timeout,
     vote::Vote,
     vote_proposal::VoteProposal,
 };
 use libra_config::{
-    config::{ConsensusType, NodeConfig, RemoteService, SafetyRulesService, SecureBackend},
+    config::{
+        ClientKey, ConsensusType, NodeConfig, RemoteAuthentication, RemoteService,
+        RequestQueueConfig, SafetyRulesService, SecureBackend,
+    },
     utils,
 };
 use libra_crypto::ed25519::Ed25519Signature;
-use libra_types::{epoch_change::EpochChangeProof, validator_signer::ValidatorSigner};
+use libra_types::{
+    epoch_change::EpochChangeProof, epoch_state::EpochState, validator_signer::ValidatorSigner,
+};
 use std::{
     any::TypeId,
     net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -48,6 +57,16 @@ impl<T: Payload> ProcessClientWrapper<T> {
         let remote_service = RemoteService {
             server_address,
             consensus_type,
+            // Exercises the authenticated channel between the client and the spawned process
+            authentication: Some(RemoteAuthentication {
+                client: "consensus".into(),
+                clients: vec![ClientKey {
+                    id: "consensus".into(),
+                    key: hex::encode(rand::random::<[u8; 32]>()),
+                }],
+            }),
+            permissions: Vec::new(),
+            request_queue: RequestQueueConfig::default(),
         };
         let mut config = NodeConfig::random();
 
@@ -85,18 +104,56 @@ impl<T: Payload> TSafetyRules<T> for ProcessClientWrapper<T> {
         self.safety_rules.consensus_state()
     }
 
+    fn commit_stats(&mut self) -> Result<CommitStats, Error> {
+        self.safety_rules.commit_stats()
+    }
+
+    fn waypoint_history(&mut self) -> Result<Vec<WaypointRecord>, Error> {
+        self.safety_rules.waypoint_history()
+    }
+
+    fn current_epoch_state(&mut self) -> Result<EpochState, Error> {
+        self.safety_rules.current_epoch_state()
+    }
+
     fn initialize(&mut self, proof: &EpochChangeProof) -> Result<(), Error> {
         self.safety_rules.initialize(proof)
     }
 
+    fn initialize_from_trusted_state(
+        &mut self,
+        trusted_state: TrustedCheckpoint,
+        proof: &EpochChangeProof,
+    ) -> Result<(), Error> {
+        self.safety_rules
+            .initialize_from_trusted_state(trusted_state, proof)
+    }
+
     fn update(&mut self, qc: &QuorumCert) -> Result<(), Error> {
         self.safety_rules.update(qc)
     }
 
+    fn update_sync_info(&mut self, sync_info: &SyncInfo) -> Result<(), Error> {
+        self.safety_rules.update_sync_info(sync_info)
+    }
+
+    fn heartbeat(&mut self, epoch: u64, round: Round) -> Result<(), Error> {
+        self.safety_rules.heartbeat(epoch, round)
+    }
+
     fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error> {
         self.safety_rules.construct_and_sign_vote(vote_proposal)
     }
 
+    fn construct_and_sign_vote_with_deadline(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        deadline_ms: Option<u64>,
+    ) -> Result<Vote, Error> {
+        self.safety_rules
+            .construct_and_sign_vote_with_deadline(vote_proposal, deadline_ms)
+    }
+
     fn sign_proposal(&mut self, block_data: BlockData<T>) -> Result<Block<T>, Error> {
         self.safety_rules.sign_proposal(block_data)
     }
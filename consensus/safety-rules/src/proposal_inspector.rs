@@ -0,0 +1,19 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use consensus_types::common::{Author, Round};
+
+/// An application-level policy over the proposals SafetyRules votes on, e.g., a limit on the
+/// size of the payload or a list of banned proposers. It is consulted after the voting rules are
+/// satisfied and before anything is persisted or signed, so a veto leaves the safety data
+/// untouched and SafetyRules may still vote on another proposal in the same round.
+pub trait ProposalInspector<T>: Send + Sync {
+    /// Returns the reason for refusing to vote on the proposal, if any. The author and payload
+    /// are absent for nil blocks.
+    fn inspect(
+        &self,
+        round: Round,
+        author: Option<Author>,
+        payload: Option<&T>,
+    ) -> Result<(), String>;
+}
@@ -0,0 +1,64 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use consensus_types::common::Round;
+use libra_crypto::HashValue;
+use libra_types::waypoint::Waypoint;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The SafetyData as it was before a destructive operation replaced it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SafetyDataSnapshot {
+    pub epoch: u64,
+    pub highest_proposed_round: Round,
+    pub last_proposal: HashValue,
+    pub last_voted_round: Round,
+    pub preferred_round: Round,
+    pub waypoint: Waypoint,
+}
+
+/// Holds the SafetyData that a destructive operation replaced until an operator confirms the
+/// operation, guarding against a mistyped command. Until then SafetyRules refuses to sign, so that
+/// restoring the snapshot can never let it sign twice in a round. Only one operation may await
+/// confirmation at a time.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RecoverySlot {
+    /// Chosen at random, so that an operation can only be confirmed by an operator who has read
+    /// its outcome
+    pub id: u64,
+    pub created_ms: u64,
+    pub operation: String,
+    pub safety_data: SafetyDataSnapshot,
+}
+
+impl RecoverySlot {
+    pub fn new(operation: &str, created_ms: u64, safety_data: SafetyDataSnapshot) -> Self {
+        Self {
+            id: rand::random(),
+            created_ms,
+            operation: operation.to_string(),
+            safety_data,
+        }
+    }
+
+    /// Whether the window for confirming the operation has passed.
+    pub fn expired(&self, window_ms: u64, now_ms: u64) -> bool {
+        now_ms >= self.created_ms.saturating_add(window_ms)
+    }
+}
+
+impl Display for RecoverySlot {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "recovery {} of {} at {} replaced epoch {}, last voted round {}, waypoint {}",
+            self.id,
+            self.operation,
+            self.created_ms,
+            self.safety_data.epoch,
+            self.safety_data.last_voted_round,
+            self.safety_data.waypoint
+        )
+    }
+}
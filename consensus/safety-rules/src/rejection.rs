@@ -0,0 +1,23 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::Error;
+use consensus_types::common::Round;
+use libra_crypto::HashValue;
+use serde::{Deserialize, Serialize};
+
+/// Describes why SafetyRules refused to vote on a proposal, along with the stored rounds that the
+/// voting rules were evaluated against.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct RejectionReport {
+    /// The id of the proposed block
+    pub block_id: HashValue,
+    pub epoch: u64,
+    pub round: Round,
+    /// The rule that the proposal failed
+    pub error: Error,
+    /// The last voted round at the time of the rejection
+    pub last_voted_round: Round,
+    /// The preferred round at the time of the rejection
+    pub preferred_round: Round,
+}
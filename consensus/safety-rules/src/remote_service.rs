@@ -1,71 +1,293 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+//! The remote service carries serialized SafetyRules requests over libra-secure-net, the same
+//! length-prefixed NetworkClient / NetworkServer transport that execution correctness and the
+//! storage service use. Consensus never opens a socket of its own to reach SafetyRules, it instead
+//! obtains a client from a RemoteService. libra-secure-net does not authenticate its peers, so
+//! once authentication is configured, both ends wrap their transport in an AuthenticatedClient or
+//! AuthenticatedServer and the service refuses every request from a client without a valid key.
+//!
+//! All I/O of the client and the server goes through a MessageChannel, which libra-secure-net
+//! implements. A deterministic network simulator may implement it instead to run the whole signer
+//! stack in a single thread and reproduce message loss, duplication and reordering.
+
 use crate::{
+    admin,
+    anomaly::Anomaly,
+    authentication::{AuthenticatedClient, AuthenticatedServer},
+    permissions::Permissions,
     persistent_safety_storage::PersistentSafetyStorage,
-    serializer::{SafetyRulesInput, SerializerClient, SerializerService, TSerializerClient},
+    request_queue::RequestQueue,
+    serializer::{
+        self, SafetyRulesRequest, SafetyRulesResponse, SerializerClient, SerializerService,
+        TSerializerClient,
+    },
     Error, SafetyRules,
 };
 use consensus_types::common::{Author, Payload};
-use libra_logger::warn;
+use libra_config::config::{
+    RemoteAuthentication, RequestQueueConfig, SafetyRulesConfig, SafetyRulesService,
+};
+use libra_logger::{debug, warn};
 use libra_secure_net::{NetworkClient, NetworkServer};
-use std::{marker::PhantomData, net::SocketAddr};
+use std::{
+    marker::PhantomData,
+    net::SocketAddr,
+    process,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// A blocking, message oriented connection carrying the SafetyRules protocol. Reads return
+/// complete messages and either side may fail an operation when the peer is unreachable.
+pub trait MessageChannel: Send + Sync {
+    fn read(&mut self) -> Result<Vec<u8>, Error>;
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error>;
+
+    /// Returns a message only if one has already arrived, without blocking
+    fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    /// The address of the peer that sent the last message read, if known
+    fn peer(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl MessageChannel for NetworkClient {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        Ok(NetworkClient::read(self)?)
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        Ok(NetworkClient::write(self, message)?)
+    }
+}
+
+impl MessageChannel for NetworkServer {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        Ok(NetworkServer::read(self)?)
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        Ok(NetworkServer::write(self, message)?)
+    }
+
+    fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(NetworkServer::try_read(self)?)
+    }
+
+    fn peer(&self) -> Option<SocketAddr> {
+        self.peer_addr()
+    }
+}
 
 pub trait RemoteService<T: Payload> {
     fn client(&self) -> SerializerClient<T> {
-        let network_client = NetworkClient::new(self.server_address());
-        let service = Box::new(RemoteClient::new(network_client));
+        let network_client = Box::new(NetworkClient::new(self.server_address()));
+        let channel: Box<dyn MessageChannel> = match self.authentication() {
+            Some(authentication) => Box::new(
+                AuthenticatedClient::new(network_client, authentication)
+                    .expect("Invalid SafetyRules client authentication"),
+            ),
+            None => network_client,
+        };
+        let service = Box::new(RemoteClient::new(channel));
         SerializerClient::new_client(service)
     }
 
     fn server_address(&self) -> SocketAddr;
+
+    /// How the client authenticates to the service, if it must
+    fn authentication(&self) -> Option<&RemoteAuthentication> {
+        None
+    }
 }
 
 pub fn execute<T: Payload>(
     author: Author,
     storage: PersistentSafetyStorage,
     listen_addr: SocketAddr,
+    config: SafetyRulesConfig,
 ) {
-    let safety_rules = SafetyRules::<T>::new(author, storage);
-    let mut serializer_service = SerializerService::new(safety_rules);
-    let mut network_server = NetworkServer::new(listen_addr);
+    let (authentication, permissions, request_queue, own_process) = match &config.service {
+        SafetyRulesService::Process(service) | SafetyRulesService::SpawnedProcess(service) => (
+            service.authentication.clone(),
+            Permissions::new(&service.permissions).expect("Invalid SafetyRules client permissions"),
+            service.request_queue.clone(),
+            true,
+        ),
+        _ => (
+            None,
+            Permissions::default(),
+            RequestQueueConfig::default(),
+            false,
+        ),
+    };
+    let safety_rules = SafetyRules::<T>::new_with_config(author, storage, &config);
+    let serializer_service = SerializerService::new(safety_rules).with_permissions(permissions);
+    let serializer_service = Arc::new(Mutex::new(serializer_service));
+    // A thread service shares the process of consensus, which handles its signals
+    if own_process {
+        flush_on_shutdown(serializer_service.clone());
+    }
+    if let Some(admin_config) = config.admin {
+        let serializer_service = serializer_service.clone();
+        thread::spawn(move || admin::execute(admin_config, serializer_service));
+    }
+    let network_server = Box::new(NetworkServer::new(listen_addr));
+    let mut channel: Box<dyn MessageChannel> = match authentication {
+        Some(authentication) => Box::new(
+            AuthenticatedServer::new(network_server, &authentication)
+                .expect("Invalid SafetyRules client authentication"),
+        ),
+        None => network_server,
+    };
+    let mut request_queue = RequestQueue::new(request_queue);
+    serve(channel.as_mut(), &serializer_service, &mut request_queue);
+}
+
+/// Writes everything SafetyRules holds in memory to storage once the process is asked to exit, the
+/// process otherwise ends without dropping SafetyRules.
+fn flush_on_shutdown<T: Payload>(serializer_service: Arc<Mutex<SerializerService<T>>>) {
+    let result = ctrlc::set_handler(move || {
+        // The lock is held until the process exits, so no request is served after the flush
+        let mut serializer_service = serializer_service
+            .lock()
+            .expect("SafetyRules lock is poisoned");
+        if let Err(e) = serializer_service.flush() {
+            warn!("Unable to flush SafetyRules storage at shutdown: {}", e);
+        }
+        process::exit(0);
+    });
+    if let Err(e) = result {
+        warn!("Unable to flush SafetyRules storage at shutdown: {}", e);
+    }
+}
 
+/// Serves the requests arriving on the channel until the process exits.
+pub fn serve<T: Payload>(
+    channel: &mut dyn MessageChannel,
+    serializer_service: &Mutex<SerializerService<T>>,
+    request_queue: &mut RequestQueue,
+) {
     loop {
-        if let Err(e) = process_one_message(&mut network_server, &mut serializer_service) {
+        if let Err(e) = process_one_message(channel, serializer_service, request_queue) {
             warn!("Warning: Failed to process message: {}", e);
         }
     }
 }
 
-fn process_one_message<T: Payload>(
-    network_server: &mut NetworkServer,
-    serializer_service: &mut SerializerService<T>,
+/// Handles the oldest pending request and writes its response. Before that, every request that
+/// has already arrived is moved into the queue, and those that do not fit are immediately answered
+/// with a retriable error, so that a flood of requests is turned away rather than held in memory.
+pub fn process_one_message<T: Payload>(
+    channel: &mut dyn MessageChannel,
+    serializer_service: &Mutex<SerializerService<T>>,
+    request_queue: &mut RequestQueue,
+) -> Result<(), Error> {
+    let result = receive::<T>(channel, request_queue);
+    match &result {
+        // The connection remains usable, the pending requests are still served
+        Err(Error::AuthenticationFailed(reason)) => serializer_service
+            .lock()
+            .expect("SafetyRules lock is poisoned")
+            .report_anomaly(Anomaly::AuthenticationFailure {
+                peer: channel.peer().map(|peer| peer.ip().to_string()),
+                request: "unauthenticated".into(),
+                reason: reason.clone(),
+            }),
+        // Only the channel fails otherwise, after which responses can no longer reach the client
+        // that sent the pending requests
+        Err(_) => request_queue.clear(),
+        Ok(()) => (),
+    }
+    result?;
+
+    let request = match request_queue.pop() {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    let peer = channel.peer().map(|peer| peer.ip());
+    let response = serializer_service
+        .lock()
+        .expect("SafetyRules lock is poisoned")
+        .handle_message_from(request, peer)?;
+    channel.write(&response)?;
+    Ok(())
+}
+
+fn receive<T: Payload>(
+    channel: &mut dyn MessageChannel,
+    request_queue: &mut RequestQueue,
 ) -> Result<(), Error> {
-    let request = network_server.read()?;
-    let response = serializer_service.handle_message(request)?;
-    network_server.write(&response)?;
+    if request_queue.is_empty() {
+        let request = channel.read()?;
+        request_queue.push(request);
+    }
+    while let Some(request) = channel.try_read()? {
+        if let Some(rejected) = request_queue.push(request) {
+            reject::<T>(channel, &rejected, request_queue.len())?;
+        }
+    }
     Ok(())
 }
 
-struct RemoteClient<T> {
-    network_client: NetworkClient,
+fn reject<T: Payload>(
+    channel: &mut dyn MessageChannel,
+    request: &[u8],
+    pending: usize,
+) -> Result<(), Error> {
+    let request: SafetyRulesRequest<T> = match lcs::from_bytes(request) {
+        Ok(request) => request,
+        // Without an id there is no one to answer, only this request is dropped
+        Err(e) => {
+            warn!("Dropping a malformed request: {}", e);
+            return Ok(());
+        }
+    };
+    warn!(
+        "[{}] Rejecting {} request, the request queue is full",
+        request.id,
+        request.input.name()
+    );
+    let response = serializer::error_response(request.id, Error::RequestQueueFull(pending))?;
+    channel.write(&response)
+}
+
+/// The client side of the protocol. A response to an earlier request, e.g., one that was
+/// duplicated or delayed in transit, is skipped rather than returned for the current request.
+pub struct RemoteClient<T> {
+    channel: Box<dyn MessageChannel>,
     marker: PhantomData<T>,
 }
 
 impl<T> RemoteClient<T> {
-    pub fn new(network_client: NetworkClient) -> Self {
+    pub fn new(channel: Box<dyn MessageChannel>) -> Self {
         Self {
-            network_client,
+            channel,
             marker: PhantomData,
         }
     }
 }
 
 impl<T: Payload> TSerializerClient<T> for RemoteClient<T> {
-    fn request(&mut self, input: SafetyRulesInput<T>) -> Result<Vec<u8>, Error> {
-        let input_message = lcs::to_bytes(&input)?;
-        self.network_client.write(&input_message)?;
-        let result = self.network_client.read()?;
-        Ok(result)
+    fn request(&mut self, request: SafetyRulesRequest<T>) -> Result<Vec<u8>, Error> {
+        let input_message = lcs::to_bytes(&request)?;
+        self.channel.write(&input_message)?;
+        loop {
+            let result = self.channel.read()?;
+            let response: SafetyRulesResponse = lcs::from_bytes(&result)?;
+            if response.id == request.id {
+                return Ok(result);
+            }
+            debug!(
+                "[{}] Skipping the stale response to {}",
+                request.id, response.id
+            );
+        }
     }
 }
@@ -0,0 +1,110 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    audit_log::AuditBatch,
+    serializer::{RequestId, SafetyRulesRequest, SafetyRulesResponse, SerializerService},
+    Error, SafetyRules,
+};
+use consensus_types::common::Payload;
+use std::fmt::{Display, Formatter};
+
+/// A request whose replayed response differs from the one recorded in the audit log. Either the
+/// decisions differ, i.e., one of them signed and the other refused or they refused for different
+/// reasons, or both signed but produced different messages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub request_id: RequestId,
+    pub request: String,
+    pub recorded: Result<(), Error>,
+    pub replayed: Result<(), Error>,
+}
+
+impl Divergence {
+    /// Whether the decision diverged, as opposed to only the signed output
+    pub fn decision_diverged(&self) -> bool {
+        self.recorded != self.replayed
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}: recorded {}, replayed {}",
+            self.request_id,
+            self.request,
+            describe(&self.recorded),
+            describe(&self.replayed),
+        )?;
+        if !self.decision_diverged() {
+            write!(f, " with different output")?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(decision: &Result<(), Error>) -> String {
+    match decision {
+        Ok(()) => "success".to_string(),
+        Err(e) => format!("error ({})", e),
+    }
+}
+
+/// The outcome of replaying an audit log.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayReport {
+    /// The number of entries replayed
+    pub replayed: u64,
+    /// The number of entries the audit log dropped, which could not be replayed
+    pub dropped: u64,
+    pub divergences: Vec<Divergence>,
+}
+
+/// Feeds the audit log back through the given SafetyRules instance, which should be fresh and
+/// backed by scratch storage provisioned with the consensus key and waypoint of the audited
+/// signer. Every response is compared with the recorded one. Entries dropped by the audit log
+/// can cause spurious divergences in the requests that follow them.
+pub fn replay<T: Payload>(safety_rules: SafetyRules<T>, batches: &[AuditBatch]) -> ReplayReport {
+    let mut service = SerializerService::new(safety_rules);
+    let mut report = ReplayReport::default();
+
+    for batch in batches {
+        report.dropped += batch.dropped;
+        for entry in &batch.entries {
+            report.replayed += 1;
+            let request = lcs::from_bytes::<SafetyRulesRequest<T>>(&entry.request)
+                .map(|request| request.input.name().to_string())
+                .unwrap_or_else(|_| "unknown".to_string());
+            let replayed = service
+                .handle_message(entry.request.clone())
+                .and_then(|response| Ok(lcs::from_bytes::<SafetyRulesResponse>(&response)?.output));
+
+            let (diverged, replayed) = match replayed {
+                Ok(output) => (output != entry.response, decision(&output)),
+                Err(e) => (true, Err(e)),
+            };
+            if diverged {
+                report.divergences.push(Divergence {
+                    request_id: entry.request_id,
+                    request,
+                    recorded: decision(&entry.response),
+                    replayed,
+                });
+            }
+        }
+    }
+    report
+}
+
+/// Every response is the LCS serialization of a Result, the outcome can be read without knowing
+/// the type of its success value.
+fn decision(output: &[u8]) -> Result<(), Error> {
+    match output.split_first() {
+        Some((0, _)) => Ok(()),
+        Some((1, error)) => Err(lcs::from_bytes(error)?),
+        _ => Err(Error::SerializationError(
+            "Malformed SafetyRules response".into(),
+        )),
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use libra_config::config::{OverflowPolicy, RequestQueueConfig};
+use std::collections::VecDeque;
+
+/// The requests the remote service has received but not yet handled. The queue never holds more
+/// than its configured depth, a request that does not fit is handed back to be rejected.
+pub struct RequestQueue {
+    config: RequestQueueConfig,
+    requests: VecDeque<Vec<u8>>,
+}
+
+impl RequestQueue {
+    pub fn new(config: RequestQueueConfig) -> Self {
+        Self {
+            config,
+            requests: VecDeque::new(),
+        }
+    }
+
+    /// Queues the request, returning the request that has to be rejected if the queue is full.
+    pub fn push(&mut self, request: Vec<u8>) -> Option<Vec<u8>> {
+        // A queue must be able to hold at least the request being handled
+        if self.requests.len() < self.config.depth.max(1) {
+            self.requests.push_back(request);
+            return None;
+        }
+        match self.config.overflow_policy {
+            OverflowPolicy::RejectNewest => Some(request),
+            OverflowPolicy::RejectOldest => {
+                let oldest = self.requests.pop_front();
+                self.requests.push_back(request);
+                oldest
+            }
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.requests.pop_front()
+    }
+
+    /// Drops all pending requests, e.g., once the client that sent them has disconnected.
+    pub fn clear(&mut self) {
+        self.requests.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(overflow_policy: OverflowPolicy) -> RequestQueue {
+        RequestQueue::new(RequestQueueConfig {
+            depth: 2,
+            overflow_policy,
+        })
+    }
+
+    #[test]
+    fn test_reject_newest() {
+        let mut queue = queue(OverflowPolicy::RejectNewest);
+        assert_eq!(queue.push(vec![0]), None);
+        assert_eq!(queue.push(vec![1]), None);
+        assert_eq!(queue.push(vec![2]), Some(vec![2]));
+        assert_eq!(queue.pop(), Some(vec![0]));
+        assert_eq!(queue.push(vec![3]), None);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn test_reject_oldest() {
+        let mut queue = queue(OverflowPolicy::RejectOldest);
+        assert_eq!(queue.push(vec![0]), None);
+        assert_eq!(queue.push(vec![1]), None);
+        assert_eq!(queue.push(vec![2]), Some(vec![0]));
+        assert_eq!(queue.pop(), Some(vec![1]));
+        assert_eq!(queue.pop(), Some(vec![2]));
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The voting, proposal, timeout and commit rules of SafetyRules as pure functions over rounds
+//! and timestamps. This module deliberately depends on nothing but `core`, neither storage, crypto
+//! nor consensus types, so that enclave and embedded signers can reuse exactly the same rule logic
+//! while keeping their trusted computing base minimal. SafetyRules reads its state, applies these
+//! rules and only then persists anything or signs.
+
+/// Same as consensus_types::common::Round, redeclared to keep this module free of dependencies.
+pub type Round = u64;
+
+/// A rule that a request to sign violates.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RuleViolation {
+    /// A different proposal has already been signed for this round
+    EquivocatingProposal { round: Round },
+    /// The block does not extend the preferred round
+    ExtendsBelowPreferredRound { preferred_round: Round },
+    /// The block does not extend the block its QC certifies
+    InvalidParent {
+        epoch: u64,
+        parent_epoch: u64,
+        parent_round: Round,
+        round: Round,
+    },
+    /// The block's timestamp is incompatible with its parent's
+    InvalidTimestamp {
+        parent_timestamp_usecs: u64,
+        timestamp_usecs: u64,
+    },
+    /// The proposal is not newer than the last voted round
+    OldProposal {
+        last_voted_round: Round,
+        proposal_round: Round,
+    },
+    /// The proposal is older than one that has already been signed
+    OldProposedRound {
+        highest_proposed_round: Round,
+        proposal_round: Round,
+    },
+    /// A QC locks on a round below the preferred round
+    QuorumCertBelowPreferredRound {
+        lock_round: Round,
+        preferred_round: Round,
+    },
+    /// The timeout is older than the last voted round
+    TimeoutBelowLastVotedRound {
+        last_voted_round: Round,
+        timeout_round: Round,
+    },
+    /// The timeout is not beyond the preferred round
+    TimeoutNotAbovePreferredRound {
+        preferred_round: Round,
+        timeout_round: Round,
+    },
+    /// The timeout is too far beyond the highest QC round
+    TimeoutRoundSkew {
+        highest_qc_round: Round,
+        max_skew: u64,
+        timeout_round: Round,
+    },
+}
+
+/// The outcome of evaluating the commit rule for a vote, B0 <- B1 <- B2 where B2 is the block
+/// being voted on, B1 its certified parent and B0 its grandparent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommitDecision {
+    /// B0 can be committed
+    Commit,
+    /// round(B0) + 1 != round(B1)
+    ParentGap,
+    /// round(B1) + 1 != round(B2)
+    ChildGap,
+    /// Neither pair of rounds is contiguous
+    BothGaps,
+}
+
+impl CommitDecision {
+    /// Evaluates the 3-chain commit rule, or the 2-chain one which ignores the round of B2.
+    pub fn new(block0: Round, block1: Round, block2: Round, two_chain: bool) -> Self {
+        let parent_gap = block0 + 1 != block1;
+        let child_gap = !two_chain && block1 + 1 != block2;
+        match (parent_gap, child_gap) {
+            (false, false) => CommitDecision::Commit,
+            (true, false) => CommitDecision::ParentGap,
+            (false, true) => CommitDecision::ChildGap,
+            (true, true) => CommitDecision::BothGaps,
+        }
+    }
+}
+
+/// The round that a QC locks on given the rounds of its certified block and that block's parent:
+/// the parent under the 3-chain rule and the certified block itself under the 2-chain rule.
+pub fn lock_round(parent_round: Round, certified_round: Round, two_chain: bool) -> Round {
+    if two_chain {
+        certified_round
+    } else {
+        parent_round
+    }
+}
+
+/// A QC may only be learned if it does not lock below the preferred round.
+pub fn verify_quorum_cert(lock_round: Round, preferred_round: Round) -> Result<(), RuleViolation> {
+    if lock_round < preferred_round {
+        Err(RuleViolation::QuorumCertBelowPreferredRound {
+            lock_round,
+            preferred_round,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// First voting rule: a block may only be voted on if its round is beyond the last voted round.
+pub fn verify_last_voted_round(
+    proposal_round: Round,
+    last_voted_round: Round,
+) -> Result<(), RuleViolation> {
+    if proposal_round <= last_voted_round {
+        Err(RuleViolation::OldProposal {
+            last_voted_round,
+            proposal_round,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Second voting rule: a block may only be voted on if the round of the block its QC certifies
+/// is at least the preferred round.
+pub fn verify_preferred_round(
+    certified_round: Round,
+    preferred_round: Round,
+) -> Result<(), RuleViolation> {
+    if certified_round < preferred_round {
+        Err(RuleViolation::ExtendsBelowPreferredRound { preferred_round })
+    } else {
+        Ok(())
+    }
+}
+
+/// A block must extend the block its QC certifies, which is in the same epoch and at a lower
+/// round. The parent id of a block is read from its QC, so the rounds and epochs are what tie an
+/// unrelated QC to it.
+pub fn verify_parent(
+    parent_epoch: u64,
+    parent_round: Round,
+    epoch: u64,
+    round: Round,
+) -> Result<(), RuleViolation> {
+    if parent_epoch == epoch && parent_round < round {
+        Ok(())
+    } else {
+        Err(RuleViolation::InvalidParent {
+            epoch,
+            parent_epoch,
+            parent_round,
+            round,
+        })
+    }
+}
+
+/// Blocks must carry strictly increasing timestamps, except for nil blocks and reconfiguration
+/// suffixes, which carry the timestamp of their parent.
+pub fn verify_timestamp(
+    parent_timestamp_usecs: u64,
+    timestamp_usecs: u64,
+    inherits_parent_timestamp: bool,
+) -> Result<(), RuleViolation> {
+    let valid = if inherits_parent_timestamp {
+        timestamp_usecs == parent_timestamp_usecs
+    } else {
+        timestamp_usecs > parent_timestamp_usecs
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(RuleViolation::InvalidTimestamp {
+            parent_timestamp_usecs,
+            timestamp_usecs,
+        })
+    }
+}
+
+/// A proposal may be signed if it is beyond the highest proposed round, or if it is the very
+/// proposal already signed for that round. Round 0 has never been proposed in.
+pub fn verify_proposal_round(
+    proposal_round: Round,
+    highest_proposed_round: Round,
+    same_as_last_proposal: bool,
+) -> Result<(), RuleViolation> {
+    if proposal_round < highest_proposed_round {
+        Err(RuleViolation::OldProposedRound {
+            highest_proposed_round,
+            proposal_round,
+        })
+    } else if proposal_round == highest_proposed_round
+        && highest_proposed_round != 0
+        && !same_as_last_proposal
+    {
+        Err(RuleViolation::EquivocatingProposal {
+            round: proposal_round,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// A timeout may be signed if it is beyond the preferred round and not older than the last voted
+/// round, signing a timeout for the last voted round again is permitted.
+pub fn verify_timeout_round(
+    timeout_round: Round,
+    preferred_round: Round,
+    last_voted_round: Round,
+) -> Result<(), RuleViolation> {
+    if timeout_round <= preferred_round {
+        Err(RuleViolation::TimeoutNotAbovePreferredRound {
+            preferred_round,
+            timeout_round,
+        })
+    } else if timeout_round < last_voted_round {
+        Err(RuleViolation::TimeoutBelowLastVotedRound {
+            last_voted_round,
+            timeout_round,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// The number of rounds a timeout is beyond the highest QC round.
+pub fn timeout_round_skew(timeout_round: Round, highest_qc_round: Round) -> u64 {
+    timeout_round.saturating_sub(highest_qc_round)
+}
+
+/// A timeout may be at most `max_skew` rounds beyond the highest QC round, if there is a limit.
+pub fn verify_timeout_round_skew(
+    timeout_round: Round,
+    highest_qc_round: Round,
+    max_skew: Option<u64>,
+) -> Result<(), RuleViolation> {
+    match max_skew {
+        Some(max_skew) if timeout_round_skew(timeout_round, highest_qc_round) > max_skew => {
+            Err(RuleViolation::TimeoutRoundSkew {
+                highest_qc_round,
+                max_skew,
+                timeout_round,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_decision() {
+        assert_eq!(CommitDecision::new(1, 2, 3, false), CommitDecision::Commit);
+        assert_eq!(
+            CommitDecision::new(1, 3, 4, false),
+            CommitDecision::ParentGap
+        );
+        assert_eq!(
+            CommitDecision::new(1, 2, 4, false),
+            CommitDecision::ChildGap
+        );
+        assert_eq!(
+            CommitDecision::new(1, 3, 5, false),
+            CommitDecision::BothGaps
+        );
+        assert_eq!(CommitDecision::new(1, 2, 4, true), CommitDecision::Commit);
+        assert_eq!(
+            CommitDecision::new(1, 3, 5, true),
+            CommitDecision::ParentGap
+        );
+    }
+
+    #[test]
+    fn test_voting_rules() {
+        verify_last_voted_round(3, 2).unwrap();
+        assert_eq!(
+            verify_last_voted_round(2, 2),
+            Err(RuleViolation::OldProposal {
+                last_voted_round: 2,
+                proposal_round: 2,
+            })
+        );
+        verify_preferred_round(2, 2).unwrap();
+        verify_preferred_round(1, 2).unwrap_err();
+        verify_parent(1, 2, 1, 3).unwrap();
+        verify_parent(1, 3, 1, 3).unwrap_err();
+        verify_parent(0, 2, 1, 3).unwrap_err();
+        verify_timestamp(1, 2, false).unwrap();
+        verify_timestamp(1, 1, false).unwrap_err();
+        verify_timestamp(1, 1, true).unwrap();
+    }
+
+    #[test]
+    fn test_proposal_rules() {
+        verify_proposal_round(1, 0, false).unwrap();
+        verify_proposal_round(2, 2, true).unwrap();
+        assert_eq!(
+            verify_proposal_round(2, 2, false),
+            Err(RuleViolation::EquivocatingProposal { round: 2 })
+        );
+        verify_proposal_round(1, 2, true).unwrap_err();
+    }
+
+    #[test]
+    fn test_timeout_rules() {
+        verify_timeout_round(3, 2, 3).unwrap();
+        verify_timeout_round(2, 2, 0).unwrap_err();
+        verify_timeout_round(3, 1, 4).unwrap_err();
+        verify_timeout_round_skew(10, 5, None).unwrap();
+        verify_timeout_round_skew(10, 5, Some(5)).unwrap();
+        verify_timeout_round_skew(11, 5, Some(5)).unwrap_err();
+    }
+}
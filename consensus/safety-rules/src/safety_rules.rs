@@ -2,26 +2,80 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    consensus_state::ConsensusState, error::Error,
-    persistent_safety_storage::PersistentSafetyStorage, t_safety_rules::TSafetyRules, COUNTERS,
+    accumulator_extension,
+    admin::Diagnostics,
+    anomaly::Anomaly,
+    audit_log::AuditLog,
+    commit_stats::CommitStats,
+    consensus_state::ConsensusState,
+    epoch_summary::{self, EpochSummary},
+    error::Error,
+    fencing::{self, Fencing},
+    heartbeat::CallerView,
+    key_endorsement,
+    latency::LatencyTracker,
+    persistent_safety_storage::PersistentSafetyStorage,
+    proposal_inspector::ProposalInspector,
+    recovery::RecoverySlot,
+    rejection::RejectionReport,
+    rules::{self, CommitDecision, RuleViolation},
+    serializer::RequestId,
+    signature_counts::SignatureKind,
+    signing_message::{self, SigningMessage},
+    t_safety_rules::TSafetyRules,
+    trusted_checkpoint::TrustedCheckpoint,
+    verification_backend::{self, VerificationBackend},
+    verified_vote_proposal::{VerifiedVoteProposal, VoteProposalVerifier},
+    waypoint_history::WaypointRecord,
+    COUNTERS,
 };
 use consensus_types::{
     block::Block,
     block_data::BlockData,
-    common::{Author, Payload},
+    common::{Author, Payload, Round},
     quorum_cert::QuorumCert,
+    sync_info::SyncInfo,
     timeout::Timeout,
     vote::Vote,
     vote_data::VoteData,
     vote_proposal::VoteProposal,
 };
-use libra_crypto::{ed25519::Ed25519Signature, hash::HashValue};
-use libra_logger::debug;
+use libra_config::config::{FeatureFlags, LatencyBudgets, SafetyRulesConfig};
+use libra_crypto::{
+    ed25519::{Ed25519PublicKey, Ed25519Signature},
+    hash::{CryptoHash, HashValue},
+    PrivateKey,
+};
+use libra_logger::{debug, error, info, warn};
 use libra_types::{
-    block_info::BlockInfo, epoch_change::EpochChangeProof, ledger_info::LedgerInfo,
-    validator_signer::ValidatorSigner, validator_verifier::ValidatorVerifier, waypoint::Waypoint,
+    block_info::BlockInfo,
+    epoch_change::EpochChangeProof,
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    transaction::Version,
+    validator_signer::ValidatorSigner,
+    validator_verifier::{ValidatorConsensusInfo, ValidatorVerifier},
+    waypoint::Waypoint,
+};
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    sync::{mpsc::Sender, Arc, Mutex},
 };
-use std::marker::PhantomData;
+
+/// The lifecycle of SafetyRules. It starts out Uninitialized and becomes Initialized once an
+/// EpochChangeProof has been accepted by initialize, thereafter update may carry it into later
+/// epochs. Nothing is signed while Uninitialized, unless waypoint-only signing is allowed. An
+/// operator may place SafetyRules into MaintenanceMode at any time, which refuses all signing
+/// until initialize is called again.
+enum State {
+    Uninitialized,
+    Initialized {
+        epoch: u64,
+        verifier: Arc<ValidatorVerifier>,
+    },
+    MaintenanceMode,
+}
 
 /// SafetyRules is responsible for the safety of the consensus:
 /// 1) voting rules
@@ -33,42 +87,620 @@ use std::marker::PhantomData;
 /// @TODO update storage with hash of ledger info (waypoint) during epoch changes (includes a new validator
 /// set)
 pub struct SafetyRules<T> {
+    allow_waypoint_only_signing: bool,
+    anomaly_reporter: Option<Mutex<Sender<Anomaly>>>,
+    audit_log: Option<AuditLog>,
+    /// The view of the consensus process as of its last heartbeat
+    caller_view: Option<CallerView>,
+    commit_stats: CommitStats,
+    consensus_key_endorser: Option<Ed25519PublicKey>,
+    /// The vote proposals refused in this epoch by reason, this is only held in memory
+    epoch_rejections: BTreeMap<String, u64>,
+    /// The consensus state from before an epoch transition that has begun but not finished
+    /// writing storage, reported in place of the partially written storage
+    epoch_transition: Option<ConsensusState>,
+    feature_flags: FeatureFlags,
+    fencing: Option<Fencing>,
+    /// The highest certified round seen in this epoch, this is only held in memory
+    highest_qc_round: Round,
+    latency: LatencyTracker,
+    latency_budgets: LatencyBudgets,
+    max_caller_epoch_lag: Option<u64>,
+    max_signatures_per_epoch: Option<u64>,
+    max_timeout_round_skew: Option<u64>,
     persistent_storage: PersistentSafetyStorage,
+    proposal_inspector: Option<Box<dyn ProposalInspector<T>>>,
+    quorum_voting_power_override: Option<u64>,
+    recovery_window_ms: u64,
+    rejection_reporter: Option<Mutex<Sender<RejectionReport>>>,
+    state: State,
+    /// The id of the destructive operation awaiting confirmation, signing is refused until then
+    unconfirmed_recovery: Option<u64>,
     validator_signer: ValidatorSigner,
-    validator_verifier: Option<ValidatorVerifier>,
+    verification_backend: Arc<dyn VerificationBackend>,
     marker: PhantomData<T>,
 }
 
 impl<T: Payload> SafetyRules<T> {
     /// Constructs a new instance of SafetyRules with the given persistent storage and the
     /// consensus private keys
-    /// @TODO replace this with an API that takes in a SafetyRulesConfig
     pub fn new(author: Author, persistent_storage: PersistentSafetyStorage) -> Self {
+        Self::new_with_config(author, persistent_storage, &SafetyRulesConfig::default())
+    }
+
+    /// Constructs a new instance of SafetyRules that applies the operational settings, such as
+    /// latency budgets, from the given config and warms up the storage.
+    pub fn new_with_config(
+        author: Author,
+        mut persistent_storage: PersistentSafetyStorage,
+        config: &SafetyRulesConfig,
+    ) -> Self {
+        persistent_storage.set_timeout_flush_window_ms(config.timeout_flush_window_ms);
         let consensus_key = persistent_storage
             .consensus_key()
             .expect("Unable to retrieve consensus private key");
         let validator_signer = ValidatorSigner::new(author, consensus_key);
-        Self {
+        let mut safety_rules = Self {
+            allow_waypoint_only_signing: config.allow_waypoint_only_signing,
+            anomaly_reporter: None,
+            audit_log: config.audit_log.clone().map(AuditLog::new),
+            caller_view: None,
+            commit_stats: CommitStats::default(),
+            consensus_key_endorser: config.consensus_key_endorser.clone(),
+            epoch_rejections: BTreeMap::new(),
+            epoch_transition: None,
+            feature_flags: config.feature_flags,
+            fencing: config.failover.clone().map(Fencing::new),
+            highest_qc_round: 0,
+            latency: LatencyTracker::default(),
+            latency_budgets: config.latency_budgets.clone(),
+            max_caller_epoch_lag: config.max_caller_epoch_lag,
+            max_signatures_per_epoch: config.max_signatures_per_epoch,
+            max_timeout_round_skew: config.max_timeout_round_skew,
             persistent_storage,
+            proposal_inspector: None,
+            quorum_voting_power_override: config.quorum_voting_power_override,
+            recovery_window_ms: config.recovery_window_ms,
+            rejection_reporter: None,
+            state: State::Uninitialized,
+            unconfirmed_recovery: None,
             validator_signer,
-            validator_verifier: None,
+            verification_backend: verification_backend::from_config(config.signature_verification),
             marker: PhantomData,
+        };
+        if let Err(e) = safety_rules.warm_up() {
+            warn!("Unable to warm up SafetyRules: {}", e);
+        }
+        if let Err(e) = safety_rules.load_unconfirmed_recovery() {
+            warn!("Unable to read the recovery slot: {}", e);
+        }
+        safety_rules
+    }
+
+    /// Delivers a RejectionReport to the given sender for every vote proposal that SafetyRules
+    /// refuses to vote on. Reports are best effort, they are dropped if the receiver is gone.
+    pub fn set_rejection_reporter(&mut self, sender: Sender<RejectionReport>) {
+        self.rejection_reporter = Some(Mutex::new(sender));
+    }
+
+    /// Delivers every Anomaly that SafetyRules detects to the given sender. Anomalies are logged
+    /// and counted regardless.
+    pub fn set_anomaly_reporter(&mut self, sender: Sender<Anomaly>) {
+        self.anomaly_reporter = Some(Mutex::new(sender));
+    }
+
+    pub(crate) fn report_anomaly(&self, anomaly: Anomaly) {
+        COUNTERS.anomalies.inc();
+        error!("Anomaly: {}", anomaly);
+        if let Some(reporter) = &self.anomaly_reporter {
+            if let Ok(sender) = reporter.lock() {
+                let _ = sender.send(anomaly);
+            }
+        }
+    }
+
+    /// Consults the given inspector before voting on any proposal, see ProposalInspector.
+    pub fn set_proposal_inspector(&mut self, inspector: Box<dyn ProposalInspector<T>>) {
+        self.proposal_inspector = Some(inspector);
+    }
+
+    /// Verifies the signatures of quorum certificates with the given backend in place of the
+    /// configured one, e.g., for an aggregated signature scheme.
+    pub fn set_verification_backend(&mut self, backend: Arc<dyn VerificationBackend>) {
+        self.verification_backend = backend;
+    }
+
+    fn report_rejection(&mut self, vote_proposal: &VoteProposal<T>, error: Error) {
+        *self
+            .epoch_rejections
+            .entry(epoch_summary::rejection_reason(&error))
+            .or_default() += 1;
+        let reporter = match &self.rejection_reporter {
+            Some(reporter) => reporter,
+            None => return,
+        };
+        let block = vote_proposal.block();
+        let report = RejectionReport {
+            block_id: block.id(),
+            epoch: block.epoch(),
+            round: block.round(),
+            error,
+            last_voted_round: self.persistent_storage.last_voted_round().unwrap_or(0),
+            preferred_round: self.persistent_storage.preferred_round().unwrap_or(0),
+        };
+        if let Ok(sender) = reporter.lock() {
+            let _ = sender.send(report);
+        }
+    }
+
+    /// Applies the voting rules to the vote proposal, verifies its QC and the accumulator
+    /// extension, and signs a vote if they are satisfied. The deadline is checked before any work
+    /// and again right before the vote is persisted, the last point at which giving up leaves the
+    /// safety data untouched.
+    fn guarded_construct_and_sign_vote(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        deadline_ms: Option<u64>,
+    ) -> Result<Vote, Error> {
+        check_deadline(deadline_ms)?;
+        debug!("Incoming vote proposal to sign.");
+        let _timer = self.latency.timer(
+            "construct_and_sign_vote",
+            self.latency_budgets.construct_and_sign_vote_ms,
+        );
+        let proposed_block = vote_proposal.block();
+        self.verify_voting_rules(proposed_block)?;
+        // Only waypoint-only signing gets this far without a verifier, it relies on the rounds
+        // in storage alone
+        if let State::Initialized { .. } = self.state {
+            self.verify_qc_signatures(proposed_block.quorum_cert())?;
+        }
+
+        let (executed_state_id, version) = self.latency.time_verification(|| {
+            accumulator_extension::verify_extension(
+                vote_proposal.accumulator_extension_proof(),
+                proposed_block.quorum_cert().certified_block(),
+            )
+        })?;
+
+        check_deadline(deadline_ms)?;
+        self.sign_vote(vote_proposal, executed_state_id, version)
+    }
+
+    /// Returns a verifier for the vote proposals of the current epoch. It may be cloned onto
+    /// other threads so that the expensive checks of a proposal run before SafetyRules is locked
+    /// for construct_and_sign_verified_vote.
+    pub fn vote_proposal_verifier(&self) -> Result<VoteProposalVerifier, Error> {
+        match &self.state {
+            State::Uninitialized => Err(Error::NotInitialized),
+            State::Initialized { epoch, verifier } => Ok(VoteProposalVerifier::new(
+                *epoch,
+                verifier.clone(),
+                self.verification_backend.clone(),
+            )),
+            State::MaintenanceMode => Err(Error::MaintenanceMode),
         }
     }
 
+    /// As construct_and_sign_vote, but for a proposal already checked by a VoteProposalVerifier,
+    /// only the voting rules are applied before signing. The proposal must have been verified in
+    /// the current epoch.
+    pub fn construct_and_sign_verified_vote(
+        &mut self,
+        verified_vote_proposal: &VerifiedVoteProposal<T>,
+    ) -> Result<Vote, Error> {
+        let vote_proposal = verified_vote_proposal.vote_proposal();
+        let result = self.guarded_construct_and_sign_verified_vote(verified_vote_proposal);
+        if let Err(error) = &result {
+            self.report_rejection(vote_proposal, error.clone());
+        }
+        result
+    }
+
+    fn guarded_construct_and_sign_verified_vote(
+        &mut self,
+        verified_vote_proposal: &VerifiedVoteProposal<T>,
+    ) -> Result<Vote, Error> {
+        debug!("Incoming verified vote proposal to sign.");
+        let _timer = self.latency.timer(
+            "construct_and_sign_vote",
+            self.latency_budgets.construct_and_sign_vote_ms,
+        );
+        if let State::Initialized { epoch, .. } = self.state {
+            if verified_vote_proposal.epoch() != epoch {
+                return Err(Error::IncorrectEpoch(verified_vote_proposal.epoch(), epoch));
+            }
+        }
+        let vote_proposal = verified_vote_proposal.vote_proposal();
+        self.verify_voting_rules(vote_proposal.block())?;
+        self.sign_vote(
+            vote_proposal,
+            verified_vote_proposal.executed_state_id(),
+            verified_vote_proposal.version(),
+        )
+    }
+
+    /// The stateful checks for voting on a block: SafetyRules may sign, the block is in the
+    /// current epoch, extends the block its QC certifies, is beyond the last voted round and
+    /// extends the preferred round, followed by the proposal inspector if one is set.
+    fn verify_voting_rules(&mut self, proposed_block: &Block<T>) -> Result<(), Error> {
+        self.verify_signing_permitted()?;
+        self.acquire_signer_lease()?;
+        self.verify_epoch(proposed_block.epoch())?;
+
+        let certified_block = proposed_block.quorum_cert().certified_block();
+        rules::verify_parent(
+            certified_block.epoch(),
+            certified_block.round(),
+            proposed_block.epoch(),
+            proposed_block.round(),
+        )?;
+
+        let last_voted_round = self.persistent_storage.last_voted_round()?;
+        if let Err(violation) =
+            rules::verify_last_voted_round(proposed_block.round(), last_voted_round)
+        {
+            debug!(
+                "Vote proposal is old {} <= {}",
+                proposed_block.round(),
+                last_voted_round
+            );
+            return Err(violation.into());
+        }
+
+        let preferred_round = self.persistent_storage.preferred_round()?;
+        let certified_round = certified_block.round();
+        if let Err(violation) = rules::verify_preferred_round(certified_round, preferred_round) {
+            debug!(
+                "Vote proposal certified round is lower than preferred round, {} < {}",
+                certified_round, preferred_round,
+            );
+            return Err(violation.into());
+        }
+
+        if self.feature_flags.timestamp_checks {
+            self.verify_timestamp(proposed_block)?;
+        }
+
+        if let Some(inspector) = &self.proposal_inspector {
+            inspector
+                .inspect(
+                    proposed_block.round(),
+                    proposed_block.author(),
+                    proposed_block.payload(),
+                )
+                .map_err(|reason| Error::ProposalVetoed {
+                    round: proposed_block.round(),
+                    reason,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Records the vote in storage and signs it over the executed state of the proposed block.
+    fn sign_vote(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        executed_state_id: HashValue,
+        version: Version,
+    ) -> Result<Vote, Error> {
+        let proposed_block = vote_proposal.block();
+        self.persistent_storage
+            .set_last_voted_round(proposed_block.round())?;
+        self.observe_qc(proposed_block.quorum_cert());
+
+        let (vote_data, ledger_info) =
+            self.construct_vote_data(vote_proposal, executed_state_id, version);
+        self.record_signature(proposed_block.epoch(), SignatureKind::Vote)?;
+        let commit_decision = self.commit_decision(proposed_block);
+        self.commit_stats.record(commit_decision);
+        Ok(self.latency.time_signing(|| {
+            Vote::new(
+                vote_data,
+                self.validator_signer.author(),
+                ledger_info,
+                &self.validator_signer,
+            )
+        }))
+    }
+
+    /// Rebinds SafetyRules to a new waypoint, such as when a network restarts from a snapshot.
+    /// The proof must lead from the stored waypoint to a ledger info that matches the new
+    /// waypoint and ends an epoch, so every ledger info along it carries a quorum of signatures
+    /// from the validator set before it. The epoch and rounds are then reset to the start of the
+    /// epoch that the ledger info begins.
+    ///
+    /// The SafetyData it replaces is held in the recovery slot, and signing is refused until the
+    /// reset is confirmed with the returned recovery id, see confirm_recovery and recover.
+    pub fn reset_to_waypoint(
+        &mut self,
+        waypoint: Waypoint,
+        proof: &EpochChangeProof,
+    ) -> Result<u64, Error> {
+        if let Some(slot) = self.persistent_storage.recovery_slot()? {
+            return Err(Error::RecoveryRefused(format!(
+                "{} awaits confirmation",
+                slot
+            )));
+        }
+        let ledger_info = self.verify_epoch_change_proof(proof)?.ledger_info();
+        waypoint
+            .verify(ledger_info)
+            .map_err(|e| Error::WaypointMismatch(format!("{}", e)))?;
+        let epoch_state = ledger_info
+            .next_epoch_state()
+            .cloned()
+            .ok_or(Error::InvalidLedgerInfo)?;
+
+        let verifier = Arc::new(self.epoch_verifier(epoch_state.verifier)?);
+        self.select_signer(&verifier)?;
+        let slot = RecoverySlot::new(
+            "reset_to_waypoint",
+            fencing::now_ms(),
+            self.persistent_storage.safety_data_snapshot()?,
+        );
+        self.persistent_storage.set_recovery_slot(Some(&slot))?;
+        self.unconfirmed_recovery = Some(slot.id);
+        // Nothing may be signed against a partially reset storage
+        self.state = State::MaintenanceMode;
+        self.persistent_storage
+            .reset(epoch_state.epoch, &waypoint)?;
+        self.highest_qc_round = 0;
+        self.state = State::Initialized {
+            epoch: epoch_state.epoch,
+            verifier,
+        };
+        warn!(
+            "Reset to waypoint {}, awaiting confirmation of {}",
+            waypoint, slot
+        );
+        Ok(slot.id)
+    }
+
+    /// Confirms the destructive operation awaiting confirmation, purging the SafetyData it
+    /// replaced and permitting signing again. A confirmation after the recovery window is refused,
+    /// the operation then has to be recovered and repeated.
+    pub fn confirm_recovery(&mut self, id: u64) -> Result<(), Error> {
+        let slot = self.recovery_slot(id)?;
+        if slot.expired(self.recovery_window_ms, fencing::now_ms()) {
+            return Err(Error::RecoveryRefused(format!(
+                "The window to confirm {} has passed, recover it and repeat the operation",
+                slot
+            )));
+        }
+        self.persistent_storage.set_recovery_slot(None)?;
+        self.unconfirmed_recovery = None;
+        info!("Confirmed {}", slot);
+        Ok(())
+    }
+
+    /// Undoes the destructive operation awaiting confirmation by restoring the SafetyData it
+    /// replaced. Nothing has been signed since the operation, so the restored rounds are as
+    /// restrictive as they were before it. SafetyRules then enters maintenance mode and has to be
+    /// initialized against the restored waypoint. The slot is purged last, so that an interrupted
+    /// recovery still refuses to sign after a restart.
+    pub fn recover(&mut self, id: u64) -> Result<(), Error> {
+        let slot = self.recovery_slot(id)?;
+        self.state = State::MaintenanceMode;
+        self.persistent_storage
+            .restore_safety_data(&slot.safety_data)?;
+        self.highest_qc_round = 0;
+        self.persistent_storage.set_recovery_slot(None)?;
+        self.unconfirmed_recovery = None;
+        warn!("Recovered {}", slot);
+        Ok(())
+    }
+
+    /// The recovery slot, if it is held for the operation with the given id.
+    fn recovery_slot(&self, id: u64) -> Result<RecoverySlot, Error> {
+        match self.persistent_storage.recovery_slot()? {
+            Some(slot) if slot.id == id => Ok(slot),
+            Some(_) => Err(Error::RecoveryRefused(format!(
+                "Recovery {} is not the operation awaiting confirmation",
+                id
+            ))),
+            None => Err(Error::RecoveryRefused(
+                "No operation awaits confirmation".into(),
+            )),
+        }
+    }
+
+    /// Restores the operation awaiting confirmation from storage, e.g., after a restart.
+    fn load_unconfirmed_recovery(&mut self) -> Result<(), Error> {
+        self.unconfirmed_recovery = self.persistent_storage.recovery_slot()?.map(|slot| slot.id);
+        Ok(())
+    }
+
+    /// Records a request served over the SafetyRules protocol and its response in the audit log,
+    /// if one is configured.
+    pub fn audit(&mut self, request_id: RequestId, request: Vec<u8>, response: Vec<u8>) {
+        if let Some(audit_log) = &mut self.audit_log {
+            audit_log.record(request_id, request, response, &mut self.persistent_storage);
+        }
+    }
+
+    /// Writes everything held in memory to storage, the buffered audit entries and the coalesced
+    /// signature counts, e.g., before the process exits.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some(audit_log) = &mut self.audit_log {
+            audit_log.flush(&mut self.persistent_storage)?;
+        }
+        Ok(self.persistent_storage.flush()?)
+    }
+
+    /// Collects a diagnostic snapshot including the given number of the most recent audit
+    /// entries.
+    pub fn diagnostics(&mut self, audit_entries: usize) -> Diagnostics {
+        let recent_entries = match &self.audit_log {
+            Some(audit_log) => audit_log
+                .recent_entries(audit_entries, &self.persistent_storage)
+                .map_err(Error::from),
+            None => Ok(Vec::new()),
+        };
+        Diagnostics {
+            timestamp_ms: fencing::now_ms(),
+            consensus_state: self.consensus_state(),
+            commit_stats: self.commit_stats.clone(),
+            highest_qc_round: self.highest_qc_round,
+            recovery_slot: self.persistent_storage.recovery_slot().map_err(Error::from),
+            storage_health: self.persistent_storage.available().map_err(Error::from),
+            waypoint_history: self.waypoint_history(),
+            audit_entries: recent_entries,
+        }
+    }
+
+    /// Refuses all further signing until SafetyRules is initialized again.
+    pub fn enter_maintenance_mode(&mut self) {
+        self.state = State::MaintenanceMode;
+    }
+
+    /// Returns the verifier for the current epoch, or an error naming the call needed to obtain
+    /// one.
+    fn verifier(&self) -> Result<&ValidatorVerifier, Error> {
+        match &self.state {
+            State::Uninitialized => Err(Error::NotInitialized),
+            State::Initialized { verifier, .. } => Ok(verifier.as_ref()),
+            State::MaintenanceMode => Err(Error::MaintenanceMode),
+        }
+    }
+
+    /// Signing of any kind requires the verifier of the current epoch, so that every operation
+    /// acts at the same level of trust, and is refused outright while in maintenance mode. Only if
+    /// waypoint-only signing is allowed may an uninitialized instance sign against the rounds in
+    /// storage. Signing is also refused while the consensus process reports an epoch too far
+    /// behind, if a maximum lag is configured, and while a destructive operation awaits
+    /// confirmation.
+    fn verify_signing_permitted(&self) -> Result<(), Error> {
+        if let Some(id) = self.unconfirmed_recovery {
+            return Err(Error::RecoveryPending(id));
+        }
+        match self.state {
+            State::Uninitialized if self.allow_waypoint_only_signing => Ok(()),
+            _ => self.verifier().map(|_| ()),
+        }?;
+        if let (State::Initialized { epoch, .. }, Some(caller_view), Some(max_lag)) =
+            (&self.state, &self.caller_view, self.max_caller_epoch_lag)
+        {
+            caller_view.check_epoch_lag(*epoch, max_lag)?;
+        }
+        Ok(())
+    }
+
+    /// The view of the consensus process as of its last heartbeat, if it has sent one.
+    pub fn caller_view(&self) -> Option<CallerView> {
+        self.caller_view
+    }
+
+    /// Counts a signature about to be issued. Once the quota of the epoch is used up, signing is
+    /// refused and SafetyRules enters maintenance mode, so that an operator has to intervene.
+    fn record_signature(&mut self, epoch: u64, kind: SignatureKind) -> Result<(), Error> {
+        if let Some(quota) = self.max_signatures_per_epoch {
+            let signature_counts = self.persistent_storage.signature_counts()?;
+            if signature_counts.epoch == epoch && signature_counts.epoch_count.total() >= quota {
+                COUNTERS.signature_quota_exceeded.inc();
+                error!(
+                    "Signature quota of {} for epoch {} used up, entering maintenance mode",
+                    quota, epoch
+                );
+                self.state = State::MaintenanceMode;
+                self.report_anomaly(Anomaly::QuotaExceeded { epoch, quota });
+                return Err(Error::SignatureQuotaExceeded { epoch, quota });
+            }
+        }
+        Ok(self.persistent_storage.record_signature(epoch, kind)?)
+    }
+
+    /// When running as part of a failover pair, only the holder of the signer lease may sign.
+    fn acquire_signer_lease(&mut self) -> Result<(), Error> {
+        match &mut self.fencing {
+            Some(fencing) => fencing.acquire(&mut self.persistent_storage),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads every value that SafetyRules depends upon, so that connections to the storage
+    /// backend are established and any missing or mismatched value is reported at startup rather
+    /// than during the first live round. The validator verifier is restored from the ledger info
+    /// that began the current epoch, if it is stored and matches the waypoint, so that SafetyRules
+    /// can sign before initialize is called again.
+    pub fn warm_up(&mut self) -> Result<(), Error> {
+        let _timer = self
+            .latency
+            .timer("warm_up", self.latency_budgets.initialize_ms);
+        self.verify_chain_id()?;
+        self.persistent_storage.epoch()?;
+        self.persistent_storage.last_voted_round()?;
+        self.persistent_storage.preferred_round()?;
+        self.persistent_storage.highest_proposed_round()?;
+        self.persistent_storage.last_proposal()?;
+        let waypoint = self.persistent_storage.waypoint()?;
+
+        if let State::Uninitialized = self.state {
+            if let Some(ledger_info) = self.persistent_storage.epoch_ending_ledger_info()? {
+                let epoch = ledger_info
+                    .ledger_info()
+                    .next_epoch_state()
+                    .ok_or(Error::InvalidLedgerInfo)?
+                    .epoch;
+                waypoint
+                    .verify(ledger_info.ledger_info())
+                    .map_err(|e| Error::WaypointMismatch(format!("{}", e)))?;
+                // An interrupted epoch change is left for initialize to complete
+                if epoch == self.persistent_storage.epoch()? {
+                    self.start_new_epoch(&ledger_info)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The vote data for the proposal executed to the given state, along with the ledger info a
+    /// vote for it commits to.
+    fn construct_vote_data(
+        &self,
+        vote_proposal: &VoteProposal<T>,
+        executed_state_id: HashValue,
+        version: Version,
+    ) -> (VoteData, LedgerInfo) {
+        let proposed_block = vote_proposal.block();
+        let vote_data = VoteData::new(
+            proposed_block.gen_block_info(
+                executed_state_id,
+                version,
+                vote_proposal.next_epoch_state().cloned(),
+            ),
+            proposed_block.quorum_cert().certified_block().clone(),
+        );
+        (vote_data, self.construct_ledger_info(proposed_block))
+    }
+
+    /// The message that a vote for the proposal would sign, without applying the voting rules or
+    /// touching storage. Only the accumulator extension is verified, as the executed state it
+    /// leads to is part of the message.
+    pub fn vote_signing_message(
+        &self,
+        vote_proposal: &VoteProposal<T>,
+    ) -> Result<SigningMessage, Error> {
+        let (executed_state_id, version) = accumulator_extension::verify_extension(
+            vote_proposal.accumulator_extension_proof(),
+            vote_proposal.block().quorum_cert().certified_block(),
+        )?;
+        let (vote_data, mut ledger_info) =
+            self.construct_vote_data(vote_proposal, executed_state_id, version);
+        ledger_info.set_consensus_data_hash(vote_data.hash());
+        Ok(signing_message::ledger_info_signing_message(&ledger_info))
+    }
+
     /// Produces a LedgerInfo that either commits a block based upon the 3-chain commit rule
     /// or an empty LedgerInfo for no commit. The 3-chain commit rule is: B0 (as well as its
     /// prefix) can be committed if there exist certified blocks B1 and B2 that satisfy:
     /// 1) B0 <- B1 <- B2 <--
     /// 2) round(B0) + 1 = round(B1), and
     /// 3) round(B1) + 1 = round(B2).
+    /// With the experimental 2-chain rule, B0 can be committed as soon as B1 is certified, so
+    /// only the first two conditions must hold.
     pub fn construct_ledger_info(&self, proposed_block: &Block<T>) -> LedgerInfo {
-        let block2 = proposed_block.round();
-        let block1 = proposed_block.quorum_cert().certified_block().round();
-        let block0 = proposed_block.quorum_cert().parent_block().round();
-
-        let commit = block0 + 1 == block1 && block1 + 1 == block2;
-        if commit {
+        if self.commit_decision(proposed_block) == CommitDecision::Commit {
             LedgerInfo::new(
                 proposed_block.quorum_cert().parent_block().clone(),
                 HashValue::zero(),
@@ -78,55 +710,306 @@ impl<T: Payload> SafetyRules<T> {
         }
     }
 
+    fn commit_decision(&self, proposed_block: &Block<T>) -> CommitDecision {
+        CommitDecision::new(
+            proposed_block.quorum_cert().parent_block().round(),
+            proposed_block.quorum_cert().certified_block().round(),
+            proposed_block.round(),
+            self.feature_flags.two_chain,
+        )
+    }
+
+    /// The round that a QC locks SafetyRules on, this is the parent of the certified block under
+    /// the 3-chain rule and the certified block itself under the experimental 2-chain rule.
+    fn lock_round(&self, qc: &QuorumCert) -> Round {
+        rules::lock_round(
+            qc.parent_block().round(),
+            qc.certified_block().round(),
+            self.feature_flags.two_chain,
+        )
+    }
+
+    fn observe_qc(&mut self, qc: &QuorumCert) {
+        self.highest_qc_round = std::cmp::max(self.highest_qc_round, qc.certified_block().round());
+    }
+
+    /// A timeout may be at most the configured number of rounds beyond the highest QC round. As
+    /// the highest QC round is not persisted, the preferred round stands in for it after a
+    /// restart, until a newer QC is seen.
+    fn verify_timeout_round_skew(&self, timeout: &Timeout) -> Result<(), Error> {
+        let highest_qc_round = std::cmp::max(
+            self.highest_qc_round,
+            self.persistent_storage.preferred_round()?,
+        );
+        let skew = rules::timeout_round_skew(timeout.round(), highest_qc_round);
+        COUNTERS.sign_timeout_round_skew.set(skew as i64);
+        rules::verify_timeout_round_skew(
+            timeout.round(),
+            highest_qc_round,
+            self.max_timeout_round_skew,
+        )
+        .map_err(|violation| {
+            COUNTERS.sign_timeout_round_skew_rejections.inc();
+            warn!(
+                "Refusing to sign a timeout for round {}, {} rounds beyond the highest QC",
+                timeout.round(),
+                skew
+            );
+            violation.into()
+        })
+    }
+
+    /// Blocks must carry strictly increasing timestamps, except for nil blocks and
+    /// reconfiguration suffixes, which carry the timestamp of their parent.
+    fn verify_timestamp(&self, proposed_block: &Block<T>) -> Result<(), Error> {
+        let parent = proposed_block.quorum_cert().certified_block();
+        Ok(rules::verify_timestamp(
+            parent.timestamp_usecs(),
+            proposed_block.timestamp_usecs(),
+            proposed_block.is_nil_block() || parent.has_reconfiguration(),
+        )?)
+    }
+
     /// This verifies a QC makes sense in the current context, specifically that this is for the
     /// current epoch and extends from the preffered round.
     fn verify_qc(&self, qc: &QuorumCert) -> Result<(), Error> {
-        let validator_verifier = self
-            .validator_verifier
-            .as_ref()
-            .ok_or(Error::NotInitialized)?;
+        self.verify_qc_signatures(qc)?;
 
-        qc.verify(validator_verifier)
-            .map_err(|e| Error::InvalidQuorumCertificate(e.to_string()))?;
+        Ok(rules::verify_quorum_cert(
+            self.lock_round(qc),
+            self.persistent_storage.preferred_round()?,
+        )?)
+    }
 
-        if qc.parent_block().round() < self.persistent_storage.preferred_round()? {
-            Err(Error::InvalidQuorumCertificate(
-                "Preferred round too early".into(),
-            ))
-        } else {
-            Ok(())
+    /// Verifies the QC against the validator set of the current epoch with the configured
+    /// verification backend.
+    fn verify_qc_signatures(&self, qc: &QuorumCert) -> Result<(), Error> {
+        let validator_verifier = self.verifier()?;
+        self.latency
+            .time_verification(|| {
+                verification_backend::verify_quorum_cert(
+                    self.verification_backend.as_ref(),
+                    qc,
+                    validator_verifier,
+                )
+            })
+            .map_err(|e| Error::InvalidQuorumCertificate(e.to_string()))
+    }
+
+    fn read_consensus_state(&self) -> Result<ConsensusState, Error> {
+        Ok(ConsensusState::new(
+            self.persistent_storage.epoch()?,
+            self.persistent_storage.last_voted_round()?,
+            self.persistent_storage.preferred_round()?,
+            self.persistent_storage.waypoint()?,
+        )
+        .with_feature_flags(self.feature_flags)
+        .with_signature_counts(self.persistent_storage.signature_counts()?))
+    }
+
+    /// Records the summary of the epoch that is ending in the audit log and metrics, before its
+    /// rounds are cleared.
+    fn summarize_epoch(&mut self, epoch: u64) -> Result<(), Error> {
+        let max_round = std::cmp::max(
+            self.highest_qc_round,
+            std::cmp::max(
+                self.persistent_storage.last_voted_round()?,
+                self.persistent_storage.highest_proposed_round()?,
+            ),
+        );
+        let summary = EpochSummary::new(
+            epoch,
+            fencing::now_ms(),
+            &self.persistent_storage.signature_counts()?,
+            std::mem::take(&mut self.epoch_rejections),
+            max_round,
+        );
+        info!("Summary of {}", summary);
+        summary.publish();
+        if let Some(audit_log) = &mut self.audit_log {
+            audit_log.record_epoch_summary(summary, &mut self.persistent_storage);
         }
+        Ok(())
     }
 
     /// This sets the current validator verifier and updates the epoch and round information
     /// if this is a new epoch ending ledger info. It also sets the current waypoint to this
-    /// LedgerInfo.
-    /// @TODO if public key does not match private key in validator set, access persistent storage
-    /// to identify new key
-    fn start_new_epoch(&mut self, ledger_info: &LedgerInfo) -> Result<(), Error> {
+    /// LedgerInfo. Once initialized, SafetyRules never moves back to an earlier epoch.
+    fn start_new_epoch(
+        &mut self,
+        ledger_info_with_sigs: &LedgerInfoWithSignatures,
+    ) -> Result<(), Error> {
+        let ledger_info = ledger_info_with_sigs.ledger_info();
         let epoch_state = ledger_info
             .next_epoch_state()
             .cloned()
             .ok_or(Error::InvalidLedgerInfo)?;
-        self.validator_verifier = Some(epoch_state.verifier);
+        if let State::Initialized { epoch, .. } = self.state {
+            if epoch_state.epoch < epoch {
+                self.report_anomaly(Anomaly::WaypointRegressionAttempt {
+                    current_epoch: epoch,
+                    epoch: epoch_state.epoch,
+                });
+                return Err(Error::IncorrectEpoch(epoch_state.epoch, epoch));
+            }
+        }
+        let verifier = Arc::new(self.epoch_verifier(epoch_state.verifier)?);
+        self.select_signer(&verifier)?;
+        self.load_unconfirmed_recovery()?;
+        self.state = State::Initialized {
+            epoch: epoch_state.epoch,
+            verifier,
+        };
         let current_epoch = self.persistent_storage.epoch()?;
 
+        // Kept for warm_up to restore the verifier of this epoch after a restart
+        let stored = self.persistent_storage.epoch_ending_ledger_info()?;
+        if stored.as_ref().map(|li| li.ledger_info()) != Some(ledger_info) {
+            self.persistent_storage
+                .set_epoch_ending_ledger_info(ledger_info_with_sigs)?;
+        }
+
         if current_epoch < epoch_state.epoch {
+            // A transition interrupted by an error keeps the state from before its first attempt
+            if self.epoch_transition.is_none() {
+                self.epoch_transition = Some(self.read_consensus_state()?);
+            }
+            self.summarize_epoch(current_epoch)?;
             // This is ordered specifically to avoid configuration issues:
             // * First set the waypoint to lock in the minimum restarting point,
             // * set the round information,
             // * finally, set the epoch information because once the epoch is set, this `if`
             // statement cannot be re-entered.
-            self.persistent_storage
-                .set_waypoint(&Waypoint::new_epoch_boundary(ledger_info)?)?;
+            self.persistent_storage.set_waypoint(
+                epoch_state.epoch,
+                &Waypoint::new_epoch_boundary(ledger_info)?,
+            )?;
             self.persistent_storage.set_last_voted_round(0)?;
             self.persistent_storage.set_preferred_round(0)?;
+            self.persistent_storage.set_highest_proposed_round(0)?;
+            self.persistent_storage
+                .set_last_proposal(HashValue::zero())?;
             self.persistent_storage.set_epoch(epoch_state.epoch)?;
+            self.epoch_transition = None;
+            self.highest_qc_round = 0;
         }
 
         Ok(())
     }
 
+    /// Signs with whichever of the consensus keys in storage the validator set advertises for
+    /// this validator. A validator may thus stage the key of a new scheme as the next consensus
+    /// key ahead of the epoch that adopts it, and keep signing with the current key until then.
+    fn select_signer(&mut self, verifier: &ValidatorVerifier) -> Result<(), Error> {
+        let author = self.validator_signer.author();
+        let public_key = match verifier.get_public_key(&author) {
+            Some(public_key) => public_key,
+            None => return Ok(()),
+        };
+        if self.validator_signer.public_key() == public_key {
+            return Ok(());
+        }
+
+        let current = (
+            self.persistent_storage.consensus_key()?,
+            self.persistent_storage.consensus_key_endorsement()?,
+        );
+        let next = match self.persistent_storage.next_consensus_key()? {
+            Some(key) => Some((
+                key,
+                self.persistent_storage.next_consensus_key_endorsement()?,
+            )),
+            None => None,
+        };
+        let consensus_key = std::iter::once(current)
+            .chain(next)
+            .find(|(consensus_key, _)| consensus_key.public_key() == public_key);
+        match consensus_key {
+            Some((consensus_key, endorsement)) => {
+                self.verify_key_endorsement(&public_key, endorsement)?;
+                info!("Switching to the consensus key advertised by the validator set");
+                self.validator_signer = ValidatorSigner::new(author, consensus_key);
+            }
+            None => warn!("No consensus key in storage matches the one in the validator set"),
+        }
+        Ok(())
+    }
+
+    /// If an endorser is configured, a consensus key is only switched to if the endorser signed
+    /// it. Otherwise whoever can write to storage could have SafetyRules sign with their key.
+    fn verify_key_endorsement(
+        &self,
+        public_key: &Ed25519PublicKey,
+        endorsement: Option<Ed25519Signature>,
+    ) -> Result<(), Error> {
+        let endorser = match &self.consensus_key_endorser {
+            Some(endorser) => endorser,
+            None => return Ok(()),
+        };
+        let endorsement = endorsement.ok_or_else(|| {
+            Error::InvalidKeyEndorsement("No endorsement found in storage".into())
+        })?;
+        let author = self.validator_signer.author();
+        key_endorsement::verify_endorsement(endorser, author, public_key, &endorsement)
+            .map_err(|e| Error::InvalidKeyEndorsement(e.to_string()))
+    }
+
+    /// Test networks may override the quorum voting power of the validator set of each new epoch.
+    fn epoch_verifier(&self, verifier: ValidatorVerifier) -> Result<ValidatorVerifier, Error> {
+        let quorum_voting_power = match self.quorum_voting_power_override {
+            Some(quorum_voting_power) => quorum_voting_power,
+            None => return Ok(verifier),
+        };
+        let validators = verifier
+            .get_ordered_account_addresses_iter()
+            .filter_map(|author| {
+                let public_key = verifier.get_public_key(&author)?;
+                let voting_power = verifier.get_voting_power(&author)?;
+                Some((
+                    author,
+                    ValidatorConsensusInfo::new(public_key, voting_power),
+                ))
+            })
+            .collect();
+        Ok(ValidatorVerifier::new_with_quorum_voting_power(
+            validators,
+            quorum_voting_power,
+        )?)
+    }
+
+    /// This checks that the backing storage still belongs to the chain this instance was opened
+    /// for, guarding against the storage being reprovisioned for another network underneath a
+    /// running SafetyRules.
+    fn verify_chain_id(&self) -> Result<(), Error> {
+        if let Some(expected_chain_id) = self.persistent_storage.expected_chain_id() {
+            let chain_id = self.persistent_storage.chain_id()?.unwrap_or_default();
+            if chain_id != expected_chain_id {
+                self.report_anomaly(Anomaly::StorageDivergence(format!(
+                    "storage holds chain id {}, expected {}",
+                    chain_id, expected_chain_id
+                )));
+                return Err(Error::IncorrectChainId(
+                    chain_id,
+                    expected_chain_id.to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the EpochChangeProof against the stored waypoint and returns the last ledger info
+    /// it proves.
+    fn verify_epoch_change_proof<'a>(
+        &self,
+        proof: &'a EpochChangeProof,
+    ) -> Result<&'a LedgerInfoWithSignatures, Error> {
+        self.verify_chain_id()?;
+        let waypoint = self.persistent_storage.waypoint()?;
+        self.latency
+            .time_verification(|| proof.verify(&waypoint))
+            .map_err(|e| Error::WaypointMismatch(format!("{}", e)))
+    }
+
     /// This checks the epoch given against storage for consistent verification
     fn verify_epoch(&self, epoch: u64) -> Result<(), Error> {
         let expected_epoch = self.persistent_storage.epoch()?;
@@ -138,108 +1021,213 @@ impl<T: Payload> SafetyRules<T> {
     }
 }
 
+fn check_deadline(deadline_ms: Option<u64>) -> Result<(), Error> {
+    match deadline_ms {
+        Some(deadline_ms) if fencing::now_ms() > deadline_ms => {
+            Err(Error::VoteDeadlineExceeded { deadline_ms })
+        }
+        _ => Ok(()),
+    }
+}
+
 impl<T: Payload> TSafetyRules<T> for SafetyRules<T> {
+    /// Reflects either the state before or after an epoch transition, never a mix of both. While
+    /// a transition has not written its last value, the state from before it is returned. That
+    /// state is only held in memory, after a restart storage is reported as is until the
+    /// transition is retried.
     fn consensus_state(&mut self) -> Result<ConsensusState, Error> {
-        Ok(ConsensusState::new(
-            self.persistent_storage.epoch()?,
-            self.persistent_storage.last_voted_round()?,
-            self.persistent_storage.preferred_round()?,
-            self.persistent_storage.waypoint()?,
-        ))
+        match &self.epoch_transition {
+            Some(consensus_state) => Ok(consensus_state.clone()),
+            None => self.read_consensus_state(),
+        }
+    }
+
+    fn commit_stats(&mut self) -> Result<CommitStats, Error> {
+        Ok(self.commit_stats.clone())
+    }
+
+    fn waypoint_history(&mut self) -> Result<Vec<WaypointRecord>, Error> {
+        Ok(self.persistent_storage.waypoint_history()?)
+    }
+
+    fn current_epoch_state(&mut self) -> Result<EpochState, Error> {
+        match &self.state {
+            State::Uninitialized => Err(Error::NotInitialized),
+            State::Initialized { epoch, verifier } => Ok(EpochState {
+                epoch: *epoch,
+                verifier: verifier.as_ref().clone(),
+            }),
+            State::MaintenanceMode => Err(Error::MaintenanceMode),
+        }
     }
 
     fn initialize(&mut self, proof: &EpochChangeProof) -> Result<(), Error> {
-        let waypoint = self.persistent_storage.waypoint()?;
-        let last_li = proof
-            .verify(&waypoint)
+        let _timer = self
+            .latency
+            .timer("initialize", self.latency_budgets.initialize_ms);
+        let last_li = self.verify_epoch_change_proof(proof)?;
+        self.start_new_epoch(last_li)
+    }
+
+    fn initialize_from_trusted_state(
+        &mut self,
+        trusted_state: TrustedCheckpoint,
+        proof: &EpochChangeProof,
+    ) -> Result<(), Error> {
+        let _timer = self
+            .latency
+            .timer("initialize", self.latency_budgets.initialize_ms);
+        let last_li = self.verify_epoch_change_proof(proof)?;
+        trusted_state
+            .verify(last_li.ledger_info())
             .map_err(|e| Error::WaypointMismatch(format!("{}", e)))?;
-        self.start_new_epoch(last_li.ledger_info())
+        self.start_new_epoch(last_li)
     }
 
     /// Verify the QC is correct and up to date, if it is either set the preferred round or start a
     /// new epoch.
     fn update(&mut self, qc: &QuorumCert) -> Result<(), Error> {
+        let _timer = self.latency.timer("update", self.latency_budgets.update_ms);
         self.verify_qc(qc)?;
         if qc.ends_epoch() {
-            self.start_new_epoch(qc.ledger_info().ledger_info())
+            self.start_new_epoch(qc.ledger_info())
         } else {
+            self.observe_qc(qc);
             self.persistent_storage
-                .set_preferred_round(qc.parent_block().round())
+                .set_preferred_round(self.lock_round(qc))
                 .map_err(|e| e.into())
         }
     }
 
-    /// @TODO verify signature on vote proposal
-    /// @TODO verify QC correctness
-    fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error> {
-        debug!("Incoming vote proposal to sign.");
-        let proposed_block = vote_proposal.block();
+    fn update_sync_info(&mut self, sync_info: &SyncInfo) -> Result<(), Error> {
+        let _timer = self
+            .latency
+            .timer("update_sync_info", self.latency_budgets.update_ms);
+        let validator_verifier = self.verifier()?;
+        self.latency
+            .time_verification(|| sync_info.verify(validator_verifier))
+            .map_err(|e| Error::InvalidQuorumCertificate(e.to_string()))?;
 
-        self.verify_epoch(proposed_block.epoch())?;
+        let hqc = sync_info.highest_quorum_cert();
+        let hcc = sync_info.highest_commit_cert();
+        if let Some(qc) = [hcc, hqc].iter().find(|qc| qc.ends_epoch()) {
+            return self.start_new_epoch(qc.ledger_info());
+        }
+        self.verify_epoch(sync_info.epoch())?;
+        self.observe_qc(hqc);
 
-        let last_voted_round = self.persistent_storage.last_voted_round()?;
-        if proposed_block.round() <= last_voted_round {
-            debug!(
-                "Vote proposal is old {} <= {}",
-                proposed_block.round(),
-                last_voted_round
-            );
-            return Err(Error::OldProposal {
-                proposal_round: proposed_block.round(),
-                last_voted_round: self.persistent_storage.last_voted_round()?,
-            });
+        // Both values only ever ratchet forward, so even if only the first write succeeds, the
+        // stored state remains consistent with the verified certificates.
+        let preferred_round = std::cmp::max(self.lock_round(hqc), self.lock_round(hcc));
+        if preferred_round > self.persistent_storage.preferred_round()? {
+            self.persistent_storage
+                .set_preferred_round(preferred_round)?;
         }
 
-        let preferred_round = self.persistent_storage.preferred_round()?;
-        if proposed_block.quorum_cert().certified_block().round() < preferred_round {
-            debug!(
-                "Vote proposal certified round is lower than preferred round, {} < {}",
-                proposed_block.quorum_cert().certified_block().round(),
-                preferred_round,
-            );
-            return Err(Error::ProposalRoundLowerThenPreferredBlock { preferred_round });
+        if let Some(tc) = sync_info.highest_timeout_certificate() {
+            if tc.round() > self.persistent_storage.last_voted_round()? {
+                self.persistent_storage.set_last_voted_round(tc.round())?;
+            }
         }
 
-        let new_tree = vote_proposal
-            .accumulator_extension_proof()
-            .verify(
-                proposed_block
-                    .quorum_cert()
-                    .certified_block()
-                    .executed_state_id(),
-            )
-            .map_err(|e| Error::InvalidAccumulatorExtension {
-                error: format!("{}", e),
-            })?;
+        Ok(())
+    }
 
-        self.persistent_storage
-            .set_last_voted_round(proposed_block.round())?;
+    fn heartbeat(&mut self, epoch: u64, round: Round) -> Result<(), Error> {
+        let caller_view = CallerView::new(epoch, round);
+        if let State::Initialized {
+            epoch: signer_epoch,
+            ..
+        } = self.state
+        {
+            let lag = caller_view.epoch_lag(signer_epoch);
+            COUNTERS.caller_epoch_lag.set(lag as i64);
+            if lag > 0 {
+                warn!(
+                    "The consensus process reports epoch {}, {} epochs behind epoch {}",
+                    epoch, lag, signer_epoch
+                );
+            }
+        }
+        self.caller_view = Some(caller_view);
+        Ok(())
+    }
 
-        Ok(Vote::new(
-            VoteData::new(
-                proposed_block.gen_block_info(
-                    new_tree.root_hash(),
-                    new_tree.version(),
-                    vote_proposal.next_epoch_state().cloned(),
-                ),
-                proposed_block.quorum_cert().certified_block().clone(),
-            ),
-            self.validator_signer.author(),
-            self.construct_ledger_info(proposed_block),
-            &self.validator_signer,
-        ))
+    /// @TODO verify signature on vote proposal
+    fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error> {
+        self.construct_and_sign_vote_with_deadline(vote_proposal, None)
     }
 
-    /// @TODO only sign blocks that are later than last_voted_round and match the current epoch
+    fn construct_and_sign_vote_with_deadline(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        deadline_ms: Option<u64>,
+    ) -> Result<Vote, Error> {
+        let result = self.guarded_construct_and_sign_vote(vote_proposal, deadline_ms);
+        if let Err(error) = &result {
+            self.report_rejection(vote_proposal, error.clone());
+        }
+        result
+    }
+
+    /// Only sign a proposal for the current epoch and at a round at or beyond the highest proposed
+    /// round. A proposal at the highest proposed round is only signed again if it is identical to
+    /// the one signed before, this prevents equivocation across restarts.
+    /// With strict proposal signing, the proposal must also be beyond the last voted round and
+    /// extend the preferred round.
     /// @TODO verify QC correctness
-    /// @TODO verify QC matches preferred round
     fn sign_proposal(&mut self, block_data: BlockData<T>) -> Result<Block<T>, Error> {
         debug!("Incoming proposal to sign.");
         COUNTERS.sign_proposal.inc();
-        Ok(Block::new_proposal_from_block_data(
-            block_data,
-            &self.validator_signer,
-        ))
+        let _timer = self
+            .latency
+            .timer("sign_proposal", self.latency_budgets.sign_proposal_ms);
+
+        self.verify_signing_permitted()?;
+        self.acquire_signer_lease()?;
+        self.verify_epoch(block_data.epoch())?;
+
+        let highest_proposed_round = self.persistent_storage.highest_proposed_round()?;
+        let proposal_hash = block_data.hash();
+        // The last proposal is only read when it is needed to tell a retry from an equivocation
+        let same_as_last_proposal = block_data.round() == highest_proposed_round
+            && highest_proposed_round != 0
+            && proposal_hash == self.persistent_storage.last_proposal()?;
+        let result = rules::verify_proposal_round(
+            block_data.round(),
+            highest_proposed_round,
+            same_as_last_proposal,
+        );
+        if let Err(RuleViolation::EquivocatingProposal { round }) = result {
+            self.report_anomaly(Anomaly::EquivocationAttempt {
+                epoch: block_data.epoch(),
+                round,
+            });
+        }
+        result?;
+
+        if self.feature_flags.strict_proposal_signing {
+            rules::verify_last_voted_round(
+                block_data.round(),
+                self.persistent_storage.last_voted_round()?,
+            )?;
+            rules::verify_preferred_round(
+                block_data.quorum_cert().certified_block().round(),
+                self.persistent_storage.preferred_round()?,
+            )?;
+        }
+
+        // Persist the round before the hash, so that a failure in between can only block a
+        // re-signing of this proposal but never permit a conflicting one.
+        self.persistent_storage
+            .set_highest_proposed_round(block_data.round())?;
+        self.persistent_storage.set_last_proposal(proposal_hash)?;
+        self.record_signature(block_data.epoch(), SignatureKind::Proposal)?;
+
+        let validator_signer = &self.validator_signer;
+        Ok(self
+            .latency
+            .time_signing(|| Block::new_proposal_from_block_data(block_data, validator_signer)))
     }
 
     /// Only sign the timeout if it is greater than or equal to the last_voted_round and ahead of
@@ -250,30 +1238,30 @@ impl<T: Payload> TSafetyRules<T> for SafetyRules<T> {
     fn sign_timeout(&mut self, timeout: &Timeout) -> Result<Ed25519Signature, Error> {
         debug!("Incoming timeout message for round {}", timeout.round());
         COUNTERS.requested_sign_timeout.inc();
+        let _timer = self
+            .latency
+            .timer("sign_timeout", self.latency_budgets.sign_timeout_ms);
 
+        self.verify_signing_permitted()?;
+        self.acquire_signer_lease()?;
         self.verify_epoch(timeout.epoch())?;
 
-        let preferred_round = self.persistent_storage.preferred_round()?;
-        if timeout.round() <= preferred_round {
-            return Err(Error::BadTimeoutPreferredRound(
-                timeout.round(),
-                preferred_round,
-            ));
-        }
-
         let last_voted_round = self.persistent_storage.last_voted_round()?;
-        if timeout.round() < last_voted_round {
-            return Err(Error::BadTimeoutLastVotedRound(
-                timeout.round(),
-                last_voted_round,
-            ));
-        }
+        rules::verify_timeout_round(
+            timeout.round(),
+            self.persistent_storage.preferred_round()?,
+            last_voted_round,
+        )?;
+        self.verify_timeout_round_skew(timeout)?;
         if timeout.round() > last_voted_round {
             self.persistent_storage
                 .set_last_voted_round(timeout.round())?;
         }
+        self.record_signature(timeout.epoch(), SignatureKind::Timeout)?;
 
-        let signature = timeout.sign(&self.validator_signer);
+        let signature = self
+            .latency
+            .time_signing(|| timeout.sign(&self.validator_signer));
         COUNTERS.sign_timeout.inc();
         debug!("Successfully signed timeout message.");
         Ok(signature)
@@ -2,22 +2,33 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    anomaly::Anomaly,
+    audit_log::AuditBatch,
     local_client::LocalClient,
     persistent_safety_storage::PersistentSafetyStorage,
     process::ProcessService,
+    proposal_inspector::ProposalInspector,
+    rejection::RejectionReport,
     remote_service::RemoteService,
+    replay::{self, ReplayReport},
     serializer::{SerializerClient, SerializerService},
     spawned_process::SpawnedProcess,
     thread::ThreadService,
-    SafetyRules, TSafetyRules,
+    waypoint_reconciliation, Error, SafetyRules, TSafetyRules,
 };
 use consensus_types::common::{Author, Payload};
-use libra_config::config::{NodeConfig, SafetyRulesService};
-use libra_secure_storage::Storage;
+use libra_config::config::{
+    NodeConfig, RemoteAuthentication, SafetyRulesConfig, SafetyRulesService,
+};
+use libra_secure_storage::{InMemoryStorage, Storage};
+use libra_types::waypoint::Waypoint;
 use std::{
-    convert::TryInto,
+    convert::TryFrom,
     net::SocketAddr,
-    sync::{Arc, RwLock},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, RwLock,
+    },
 };
 
 pub fn extract_service_inputs(config: &mut NodeConfig) -> (Author, PersistentSafetyStorage) {
@@ -27,11 +38,16 @@ pub fn extract_service_inputs(config: &mut NodeConfig) -> (Author, PersistentSaf
         .expect("Missing validator network")
         .peer_id;
 
-    let backend = &config.consensus.safety_rules.backend;
-    let internal_storage: Box<dyn Storage> =
-        backend.try_into().expect("Unable to initialize storage");
+    let sr_config = &config.consensus.safety_rules;
+    let internal_storage = open_storage(sr_config).expect("Unable to initialize storage");
+    if sr_config.quorum_voting_power_override.is_some() && config.test.is_none() {
+        panic!("A quorum voting power override is only permitted on test networks");
+    }
+    let chain_id = sr_config.chain_id.clone();
+    let require_storage_integrity = sr_config.require_storage_integrity;
+    let audit_log = sr_config.audit_log.is_some();
 
-    let storage = if let Some(test_config) = config.test.as_mut() {
+    let mut storage = if let Some(test_config) = config.test.as_mut() {
         let private_key = test_config
             .consensus_keypair
             .as_mut()
@@ -40,14 +56,109 @@ pub fn extract_service_inputs(config: &mut NodeConfig) -> (Author, PersistentSaf
             .expect("Failed to take Consensus private key, key absent or already read");
         let waypoint = config.base.waypoint.expect("Missing waypoint");
 
-        PersistentSafetyStorage::initialize(internal_storage, private_key, waypoint)
+        match &chain_id {
+            Some(chain_id) => PersistentSafetyStorage::initialize_for_chain(
+                internal_storage,
+                private_key,
+                waypoint,
+                chain_id,
+            ),
+            None => PersistentSafetyStorage::initialize(internal_storage, private_key, waypoint),
+        }
     } else {
-        PersistentSafetyStorage::new(internal_storage)
+        let mut storage = PersistentSafetyStorage::new(internal_storage);
+        if let Some(waypoint) = config.base.waypoint {
+            if let Err(e) =
+                waypoint_reconciliation::reconcile_waypoint(&mut storage, &waypoint, None)
+            {
+                panic!("Unable to reconcile the configured waypoint: {}", e);
+            }
+        }
+        storage
     };
 
+    // A storage bound to another chain fails SafetyRules initialize with IncorrectChainId
+    if let Some(chain_id) = chain_id {
+        if let Err(e) = storage.set_expected_chain_id(&chain_id) {
+            panic!("Unable to use SafetyRules storage: {}", e);
+        }
+    }
+
+    // A storage set up without a safety data key, e.g., by the management tooling, is signed as
+    // it is found the first time it is opened
+    if require_storage_integrity && !storage.has_safety_data_key() {
+        if let Err(e) = storage.enable_integrity_checks() {
+            panic!("Unable to provision the safety data key: {}", e);
+        }
+    }
+
+    // Audit batches are signed by the safety data key, without it every batch would be dropped
+    if audit_log && !storage.has_safety_data_key() {
+        panic!("An audit log requires a safety data key in SafetyRules storage");
+    }
+
+    if require_storage_integrity || storage.integrity_checks_enabled() {
+        if let Err(e) = storage.verify_integrity() {
+            panic!("SafetyRules storage failed its integrity check: {}", e);
+        }
+    }
+
     (author, storage)
 }
 
+/// Opens the storage backend of SafetyRules. Distinct networks or validators may share a backend
+/// by each configuring a namespace of the backend. A storage locked by a running instance fails
+/// with StorageLocked.
+fn open_storage(config: &SafetyRulesConfig) -> Result<Box<dyn Storage>, Error> {
+    <Box<dyn Storage>>::try_from(&config.backend).map_err(|e| Error::InternalError {
+        error: format!("Unable to open storage: {}", e),
+    })
+}
+
+/// Reads the retained audit log batches of the SafetyRules instance with the given config.
+pub fn export_audit_log(config: &SafetyRulesConfig) -> Result<Vec<AuditBatch>, Error> {
+    let audit_log = config
+        .audit_log
+        .as_ref()
+        .ok_or_else(|| Error::InternalError {
+            error: "SafetyRules has no audit log configured".to_string(),
+        })?;
+    let storage = PersistentSafetyStorage::new(open_storage(config)?);
+    Ok(storage.audit_batches(audit_log)?)
+}
+
+/// Replays the audit log through a fresh SafetyRules instance backed by scratch in-memory storage,
+/// provisioned with the consensus key of the configured storage and the oldest waypoint it has
+/// been bound to, or the given waypoint if it has no history. The configured storage is only
+/// read, the audit log and failover settings are not applied to the replay.
+pub fn replay_audit_log<T: Payload>(
+    config: &SafetyRulesConfig,
+    author: Author,
+    waypoint: Option<Waypoint>,
+    batches: &[AuditBatch],
+) -> Result<ReplayReport, Error> {
+    let storage = PersistentSafetyStorage::new(open_storage(config)?);
+    let waypoint = storage
+        .waypoint_history()?
+        .first()
+        .map(|record| record.waypoint)
+        .or(waypoint)
+        .ok_or_else(|| Error::InternalError {
+            error: "No waypoint to replay the audit log from".to_string(),
+        })?;
+    let scratch_storage = PersistentSafetyStorage::initialize(
+        Box::new(InMemoryStorage::new()),
+        storage.consensus_key()?,
+        waypoint,
+    );
+
+    let mut config = config.clone();
+    config.audit_log = None;
+    config.failover = None;
+    let safety_rules = SafetyRules::new_with_config(author, scratch_storage, &config);
+    Ok(replay::replay(safety_rules, batches))
+}
+
 enum SafetyRulesWrapper<T> {
     Local(Arc<RwLock<SafetyRules<T>>>),
     Process(ProcessService<T>),
@@ -63,37 +174,52 @@ pub struct SafetyRulesManager<T> {
 impl<T: Payload> SafetyRulesManager<T> {
     pub fn new(config: &mut NodeConfig) -> Self {
         match &config.consensus.safety_rules.service {
-            SafetyRulesService::Process(conf) => return Self::new_process(conf.server_address),
+            SafetyRulesService::Process(conf) => {
+                return Self::new_process(conf.server_address, conf.authentication.clone())
+            }
             SafetyRulesService::SpawnedProcess(_) => return Self::new_spawned_process(config),
             _ => (),
         };
 
         let (author, storage) = extract_service_inputs(config);
-        let sr_config = &config.consensus.safety_rules;
+        let sr_config = config.consensus.safety_rules.clone();
         match sr_config.service {
-            SafetyRulesService::Local => Self::new_local(author, storage),
-            SafetyRulesService::Serializer => Self::new_serializer(author, storage),
-            SafetyRulesService::Thread => Self::new_thread(author, storage),
+            SafetyRulesService::Local => {
+                Self::local(SafetyRules::new_with_config(author, storage, &sr_config))
+            }
+            SafetyRulesService::Serializer => {
+                Self::serializer(SafetyRules::new_with_config(author, storage, &sr_config))
+            }
+            SafetyRulesService::Thread => Self::thread(author, storage, sr_config),
             _ => panic!("Unimplemented SafetyRulesService: {:?}", sr_config.service),
         }
     }
 
     pub fn new_local(author: Author, storage: PersistentSafetyStorage) -> Self {
-        let safety_rules = SafetyRules::new(author, storage);
+        Self::local(SafetyRules::new(author, storage))
+    }
+
+    fn local(safety_rules: SafetyRules<T>) -> Self {
         Self {
             internal_safety_rules: SafetyRulesWrapper::Local(Arc::new(RwLock::new(safety_rules))),
         }
     }
 
-    pub fn new_process(server_addr: SocketAddr) -> Self {
-        let process_service = ProcessService::<T>::new(server_addr);
+    pub fn new_process(
+        server_addr: SocketAddr,
+        authentication: Option<RemoteAuthentication>,
+    ) -> Self {
+        let process_service = ProcessService::<T>::new(server_addr, authentication);
         Self {
             internal_safety_rules: SafetyRulesWrapper::Process(process_service),
         }
     }
 
     pub fn new_serializer(author: Author, storage: PersistentSafetyStorage) -> Self {
-        let safety_rules = SafetyRules::new(author, storage);
+        Self::serializer(SafetyRules::new(author, storage))
+    }
+
+    fn serializer(safety_rules: SafetyRules<T>) -> Self {
         let serializer_service = SerializerService::new(safety_rules);
         Self {
             internal_safety_rules: SafetyRulesWrapper::Serializer(Arc::new(RwLock::new(
@@ -110,12 +236,67 @@ impl<T: Payload> SafetyRulesManager<T> {
     }
 
     pub fn new_thread(author: Author, storage: PersistentSafetyStorage) -> Self {
-        let thread = ThreadService::<T>::new(author, storage);
+        Self::thread(author, storage, SafetyRulesConfig::default())
+    }
+
+    fn thread(author: Author, storage: PersistentSafetyStorage, config: SafetyRulesConfig) -> Self {
+        let thread = ThreadService::<T>::new(author, storage, config);
         Self {
             internal_safety_rules: SafetyRulesWrapper::Thread(thread),
         }
     }
 
+    /// Subscribes to the anomalies SafetyRules detects. This is only available when SafetyRules
+    /// runs in the same process as consensus, a separate SafetyRules process logs and counts them
+    /// in the anomalies metric.
+    pub fn anomalies(&self) -> Option<Receiver<Anomaly>> {
+        let (sender, receiver) = mpsc::channel();
+        match &self.internal_safety_rules {
+            SafetyRulesWrapper::Local(safety_rules) => {
+                safety_rules.write().unwrap().set_anomaly_reporter(sender)
+            }
+            SafetyRulesWrapper::Serializer(serializer_service) => serializer_service
+                .write()
+                .unwrap()
+                .set_anomaly_reporter(sender),
+            _ => return None,
+        }
+        Some(receiver)
+    }
+
+    /// Subscribes to reports explaining why SafetyRules refused to vote. This is only available
+    /// when SafetyRules runs locally, i.e., in the same thread as consensus.
+    pub fn rejection_reports(&self) -> Option<Receiver<RejectionReport>> {
+        match &self.internal_safety_rules {
+            SafetyRulesWrapper::Local(safety_rules) => {
+                let (sender, receiver) = mpsc::channel();
+                safety_rules.write().unwrap().set_rejection_reporter(sender);
+                Some(receiver)
+            }
+            _ => None,
+        }
+    }
+
+    /// Installs an inspector that may veto proposals before SafetyRules votes on them. This is
+    /// only available when SafetyRules runs locally, as the inspector lives in the embedder.
+    pub fn set_proposal_inspector(
+        &self,
+        inspector: Box<dyn ProposalInspector<T>>,
+    ) -> Result<(), Error> {
+        match &self.internal_safety_rules {
+            SafetyRulesWrapper::Local(safety_rules) => {
+                safety_rules
+                    .write()
+                    .unwrap()
+                    .set_proposal_inspector(inspector);
+                Ok(())
+            }
+            _ => Err(Error::InternalError {
+                error: "A proposal inspector requires a local SafetyRules".to_string(),
+            }),
+        }
+    }
+
     pub fn client(&self) -> Box<dyn TSafetyRules<T> + Send + Sync> {
         match &self.internal_safety_rules {
             SafetyRulesWrapper::Local(safety_rules) => {
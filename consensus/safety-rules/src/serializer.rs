@@ -1,15 +1,61 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{ConsensusState, Error, SafetyRules, TSafetyRules};
+use crate::{
+    admin::Diagnostics, anomaly::Anomaly, permissions::Permissions, CommitStats, ConsensusState,
+    Error, SafetyRules, TSafetyRules, TrustedCheckpoint, WaypointRecord,
+};
 use consensus_types::{
-    block::Block, block_data::BlockData, common::Payload, quorum_cert::QuorumCert,
-    timeout::Timeout, vote::Vote, vote_proposal::VoteProposal,
+    block::Block,
+    block_data::BlockData,
+    common::{Payload, Round},
+    quorum_cert::QuorumCert,
+    sync_info::SyncInfo,
+    timeout::Timeout,
+    vote::Vote,
+    vote_proposal::VoteProposal,
 };
 use libra_crypto::ed25519::Ed25519Signature;
-use libra_types::epoch_change::EpochChangeProof;
+use libra_logger::{debug, warn};
+use libra_types::{epoch_change::EpochChangeProof, epoch_state::EpochState, waypoint::Waypoint};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
+use std::{
+    fmt::{Display, Formatter},
+    net::IpAddr,
+    sync::{mpsc::Sender, Arc, RwLock},
+};
+
+/// Identifies a request and its response across the SafetyRules protocol. It is chosen by the
+/// client, logged on both sides and recorded in the audit log, so that a request can be traced
+/// from the consensus logs to the signer's audit trail.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct RequestId(u64);
+
+impl RequestId {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SafetyRulesRequest<T> {
+    pub id: RequestId,
+    #[serde(bound = "T: Payload")]
+    pub input: SafetyRulesInput<T>,
+}
+
+/// The serialized result of a request, along with the id of that request.
+#[derive(Deserialize, Serialize)]
+pub struct SafetyRulesResponse {
+    pub id: RequestId,
+    pub output: Vec<u8>,
+}
 
 #[derive(Deserialize, Serialize)]
 pub enum SafetyRulesInput<T> {
@@ -17,59 +63,234 @@ pub enum SafetyRulesInput<T> {
     Initialize(Box<EpochChangeProof>),
     Update(Box<QuorumCert>),
     #[serde(bound = "T: Payload")]
-    ConstructAndSignVote(Box<VoteProposal<T>>),
+    ConstructAndSignVote(Box<VoteProposal<T>>, Option<u64>),
     #[serde(bound = "T: Payload")]
     SignProposal(Box<BlockData<T>>),
     SignTimeout(Box<Timeout>),
+    // The variant index is on the wire, new operations must only be appended.
+    UpdateSyncInfo(Box<SyncInfo>),
+    CommitStats,
+    WaypointHistory,
+    InitializeFromTrustedState(Box<TrustedCheckpoint>, Box<EpochChangeProof>),
+    CurrentEpochState,
+    Heartbeat(u64, Round),
+}
+
+/// The names of every operation of the protocol, see SafetyRulesInput::name.
+pub const OPERATIONS: [&str; 12] = [
+    "consensus_state",
+    "initialize",
+    "update",
+    "construct_and_sign_vote",
+    "sign_proposal",
+    "sign_timeout",
+    "update_sync_info",
+    "commit_stats",
+    "waypoint_history",
+    "initialize_from_trusted_state",
+    "current_epoch_state",
+    "heartbeat",
+];
+
+impl<T> SafetyRulesInput<T> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SafetyRulesInput::ConsensusState => "consensus_state",
+            SafetyRulesInput::Initialize(_) => "initialize",
+            SafetyRulesInput::Update(_) => "update",
+            SafetyRulesInput::ConstructAndSignVote(..) => "construct_and_sign_vote",
+            SafetyRulesInput::SignProposal(_) => "sign_proposal",
+            SafetyRulesInput::SignTimeout(_) => "sign_timeout",
+            SafetyRulesInput::UpdateSyncInfo(_) => "update_sync_info",
+            SafetyRulesInput::CommitStats => "commit_stats",
+            SafetyRulesInput::WaypointHistory => "waypoint_history",
+            SafetyRulesInput::InitializeFromTrustedState(..) => "initialize_from_trusted_state",
+            SafetyRulesInput::CurrentEpochState => "current_epoch_state",
+            SafetyRulesInput::Heartbeat(..) => "heartbeat",
+        }
+    }
+}
+
+/// The response to a request that was rejected before reaching SafetyRules. Every response holds
+/// a serialized Result and an Err does not depend on the type of the success value.
+pub fn error_response(id: RequestId, error: Error) -> Result<Vec<u8>, Error> {
+    let output = lcs::to_bytes(&Result::<(), Error>::Err(error))?;
+    Ok(lcs::to_bytes(&SafetyRulesResponse { id, output })?)
 }
 
 pub struct SerializerService<T> {
     internal: SafetyRules<T>,
+    permissions: Permissions,
 }
 
 impl<T: Payload> SerializerService<T> {
     pub fn new(internal: SafetyRules<T>) -> Self {
-        Self { internal }
+        Self {
+            internal,
+            permissions: Permissions::default(),
+        }
+    }
+
+    /// Restricts the operations each client may request, see handle_message_from.
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn set_anomaly_reporter(&mut self, sender: Sender<Anomaly>) {
+        self.internal.set_anomaly_reporter(sender);
+    }
+
+    pub(crate) fn report_anomaly(&self, anomaly: Anomaly) {
+        self.internal.report_anomaly(anomaly);
+    }
+
+    pub fn diagnostics(&mut self, audit_entries: usize) -> Diagnostics {
+        self.internal.diagnostics(audit_entries)
+    }
+
+    pub fn confirm_recovery(&mut self, id: u64) -> Result<(), Error> {
+        self.internal.confirm_recovery(id)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.internal.flush()
+    }
+
+    pub fn recover(&mut self, id: u64) -> Result<(), Error> {
+        self.internal.recover(id)
+    }
+
+    pub fn reset_to_waypoint(
+        &mut self,
+        waypoint: Waypoint,
+        proof: &EpochChangeProof,
+    ) -> Result<u64, Error> {
+        self.internal.reset_to_waypoint(waypoint, proof)
     }
 
     pub fn handle_message(&mut self, input_message: Vec<u8>) -> Result<Vec<u8>, Error> {
-        let input = lcs::from_bytes(&input_message)?;
+        self.handle_message_from(input_message, None)
+    }
+
+    /// Handles a request from the client at the given address. A request the client is not
+    /// permitted to make is answered with an error without reaching SafetyRules.
+    pub fn handle_message_from(
+        &mut self,
+        input_message: Vec<u8>,
+        peer: Option<IpAddr>,
+    ) -> Result<Vec<u8>, Error> {
+        let SafetyRulesRequest { id, input } = lcs::from_bytes(&input_message)?;
+        debug!("[{}] Handling {} request", id, input.name());
+        if let Err(e) = self.permissions.check(peer, input.name()) {
+            warn!("[{}] Rejecting {} request: {}", id, input.name(), e);
+            self.internal
+                .report_anomaly(Anomaly::AuthenticationFailure {
+                    peer: peer.map(|peer| peer.to_string()),
+                    request: input.name().to_string(),
+                    reason: e.to_string(),
+                });
+            return error_response(id, e);
+        }
+        let audited = !matches!(
+            input,
+            SafetyRulesInput::ConsensusState
+                | SafetyRulesInput::CommitStats
+                | SafetyRulesInput::WaypointHistory
+                | SafetyRulesInput::CurrentEpochState
+        );
 
         let output = match input {
             SafetyRulesInput::ConsensusState => lcs::to_bytes(&self.internal.consensus_state()),
+            SafetyRulesInput::CommitStats => lcs::to_bytes(&self.internal.commit_stats()),
+            SafetyRulesInput::WaypointHistory => lcs::to_bytes(&self.internal.waypoint_history()),
+            SafetyRulesInput::CurrentEpochState => {
+                lcs::to_bytes(&self.internal.current_epoch_state())
+            }
             SafetyRulesInput::Initialize(li) => lcs::to_bytes(&self.internal.initialize(&li)),
+            SafetyRulesInput::InitializeFromTrustedState(trusted_state, proof) => lcs::to_bytes(
+                &self
+                    .internal
+                    .initialize_from_trusted_state(*trusted_state, &proof),
+            ),
             SafetyRulesInput::Update(qc) => lcs::to_bytes(&self.internal.update(&qc)),
-            SafetyRulesInput::ConstructAndSignVote(vote_proposal) => {
-                lcs::to_bytes(&self.internal.construct_and_sign_vote(&vote_proposal))
+            SafetyRulesInput::UpdateSyncInfo(sync_info) => {
+                lcs::to_bytes(&self.internal.update_sync_info(&sync_info))
+            }
+            SafetyRulesInput::Heartbeat(epoch, round) => {
+                lcs::to_bytes(&self.internal.heartbeat(epoch, round))
             }
+            SafetyRulesInput::ConstructAndSignVote(vote_proposal, deadline_ms) => lcs::to_bytes(
+                &self
+                    .internal
+                    .construct_and_sign_vote_with_deadline(&vote_proposal, deadline_ms),
+            ),
             SafetyRulesInput::SignProposal(block_data) => {
                 lcs::to_bytes(&self.internal.sign_proposal(*block_data))
             }
             SafetyRulesInput::SignTimeout(timeout) => {
                 lcs::to_bytes(&self.internal.sign_timeout(&timeout))
             }
-        };
+        }?;
 
-        Ok(output?)
+        if audited {
+            self.internal.audit(id, input_message, output.clone());
+        }
+        Ok(lcs::to_bytes(&SafetyRulesResponse { id, output })?)
     }
 }
 
 pub struct SerializerClient<T> {
     service: Box<dyn TSerializerClient<T>>,
+    /// Request ids are random unless the client has been given a sequence to follow
+    next_request_id: Option<u64>,
 }
 
 impl<T: Payload> SerializerClient<T> {
     pub fn new(serializer_service: Arc<RwLock<SerializerService<T>>>) -> Self {
         let service = Box::new(LocalService { serializer_service });
-        Self { service }
+        Self::new_client(service)
     }
 
     pub fn new_client(service: Box<dyn TSerializerClient<T>>) -> Self {
-        Self { service }
+        Self {
+            service,
+            next_request_id: None,
+        }
+    }
+
+    /// Numbers requests consecutively starting at `first` instead of choosing random ids, which
+    /// keeps the messages exchanged reproducible, e.g., within a deterministic network simulator.
+    pub fn with_sequential_request_ids(mut self, first: u64) -> Self {
+        self.next_request_id = Some(first);
+        self
+    }
+
+    fn next_request_id(&mut self) -> RequestId {
+        match &mut self.next_request_id {
+            Some(next) => {
+                let id = RequestId(*next);
+                *next = next.wrapping_add(1);
+                id
+            }
+            None => RequestId::random(),
+        }
     }
 
     fn request(&mut self, input: SafetyRulesInput<T>) -> Result<Vec<u8>, Error> {
-        self.service.request(input)
+        let id = self.next_request_id();
+        debug!("[{}] Requesting {}", id, input.name());
+        let response = self.service.request(SafetyRulesRequest { id, input })?;
+        let response: SafetyRulesResponse = lcs::from_bytes(&response)?;
+        if response.id != id {
+            return Err(Error::InternalError {
+                error: format!(
+                    "Received the response to {} for request {}",
+                    response.id, id
+                ),
+            });
+        }
+        Ok(response.output)
     }
 }
 
@@ -79,23 +300,71 @@ impl<T: Payload> TSafetyRules<T> for SerializerClient<T> {
         lcs::from_bytes(&response)?
     }
 
+    fn commit_stats(&mut self) -> Result<CommitStats, Error> {
+        let response = self.request(SafetyRulesInput::CommitStats)?;
+        lcs::from_bytes(&response)?
+    }
+
+    fn waypoint_history(&mut self) -> Result<Vec<WaypointRecord>, Error> {
+        let response = self.request(SafetyRulesInput::WaypointHistory)?;
+        lcs::from_bytes(&response)?
+    }
+
+    fn current_epoch_state(&mut self) -> Result<EpochState, Error> {
+        let response = self.request(SafetyRulesInput::CurrentEpochState)?;
+        lcs::from_bytes(&response)?
+    }
+
     fn initialize(&mut self, proof: &EpochChangeProof) -> Result<(), Error> {
         let response = self.request(SafetyRulesInput::Initialize(Box::new(proof.clone())))?;
         lcs::from_bytes(&response)?
     }
 
+    fn initialize_from_trusted_state(
+        &mut self,
+        trusted_state: TrustedCheckpoint,
+        proof: &EpochChangeProof,
+    ) -> Result<(), Error> {
+        let response = self.request(SafetyRulesInput::InitializeFromTrustedState(
+            Box::new(trusted_state),
+            Box::new(proof.clone()),
+        ))?;
+        lcs::from_bytes(&response)?
+    }
+
     fn update(&mut self, qc: &QuorumCert) -> Result<(), Error> {
         let response = self.request(SafetyRulesInput::Update(Box::new(qc.clone())))?;
         lcs::from_bytes(&response)?
     }
 
-    fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error> {
-        let response = self.request(SafetyRulesInput::ConstructAndSignVote(Box::new(
-            vote_proposal.clone(),
+    fn update_sync_info(&mut self, sync_info: &SyncInfo) -> Result<(), Error> {
+        let response = self.request(SafetyRulesInput::UpdateSyncInfo(Box::new(
+            sync_info.clone(),
         )))?;
         lcs::from_bytes(&response)?
     }
 
+    fn heartbeat(&mut self, epoch: u64, round: Round) -> Result<(), Error> {
+        let response = self.request(SafetyRulesInput::Heartbeat(epoch, round))?;
+        lcs::from_bytes(&response)?
+    }
+
+    fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error> {
+        self.construct_and_sign_vote_with_deadline(vote_proposal, None)
+    }
+
+    fn construct_and_sign_vote_with_deadline(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        deadline_ms: Option<u64>,
+    ) -> Result<Vote, Error> {
+        let response = self.request(SafetyRulesInput::ConstructAndSignVote(
+            Box::new(vote_proposal.clone()),
+            deadline_ms,
+        ))?;
+        lcs::from_bytes(&response)?
+    }
+
     fn sign_proposal(&mut self, block_data: BlockData<T>) -> Result<Block<T>, Error> {
         let response = self.request(SafetyRulesInput::SignProposal(Box::new(block_data)))?;
         lcs::from_bytes(&response)?
@@ -108,7 +377,7 @@ impl<T: Payload> TSafetyRules<T> for SerializerClient<T> {
 }
 
 pub trait TSerializerClient<T>: Send + Sync {
-    fn request(&mut self, input: SafetyRulesInput<T>) -> Result<Vec<u8>, Error>;
+    fn request(&mut self, request: SafetyRulesRequest<T>) -> Result<Vec<u8>, Error>;
 }
 
 struct LocalService<T> {
@@ -116,8 +385,8 @@ struct LocalService<T> {
 }
 
 impl<T: Payload> TSerializerClient<T> for LocalService<T> {
-    fn request(&mut self, input: SafetyRulesInput<T>) -> Result<Vec<u8>, Error> {
-        let input_message = lcs::to_bytes(&input)?;
+    fn request(&mut self, request: SafetyRulesRequest<T>) -> Result<Vec<u8>, Error> {
+        let input_message = lcs::to_bytes(&request)?;
         self.serializer_service
             .write()
             .unwrap()
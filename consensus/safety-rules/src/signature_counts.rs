@@ -0,0 +1,112 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::counters::COUNTERS;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The kinds of messages that SafetyRules signs with the consensus key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignatureKind {
+    Proposal,
+    Timeout,
+    Vote,
+}
+
+/// The number of signatures issued of each kind.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SignatureCount {
+    pub proposals: u64,
+    pub timeouts: u64,
+    pub votes: u64,
+}
+
+impl SignatureCount {
+    fn increment(&mut self, kind: SignatureKind) {
+        match kind {
+            SignatureKind::Proposal => self.proposals += 1,
+            SignatureKind::Timeout => self.timeouts += 1,
+            SignatureKind::Vote => self.votes += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.proposals + self.timeouts + self.votes
+    }
+}
+
+impl Display for SignatureCount {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "proposals = {}, timeouts = {}, votes = {}",
+            self.proposals, self.timeouts, self.votes
+        )
+    }
+}
+
+/// Every signature ever issued by the consensus key of this storage, both over its lifetime and
+/// within the epoch it last signed in. The counts are persisted before each signature is produced,
+/// so a crash may overstate them by one but they never understate the signer's activity, unless
+/// the counts of timeouts are held in memory for a flush window.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SignatureCounts {
+    /// The epoch that `epoch_count` covers
+    pub epoch: u64,
+    pub epoch_count: SignatureCount,
+    pub lifetime_count: SignatureCount,
+}
+
+impl SignatureCounts {
+    /// Counts a signature issued in the given epoch, the epoch count restarts with every new
+    /// epoch.
+    pub fn record(&mut self, epoch: u64, kind: SignatureKind) {
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.epoch_count = SignatureCount::default();
+        }
+        self.epoch_count.increment(kind);
+        self.lifetime_count.increment(kind);
+
+        match kind {
+            SignatureKind::Proposal => COUNTERS.signed_proposals.inc(),
+            SignatureKind::Timeout => COUNTERS.signed_timeouts.inc(),
+            SignatureKind::Vote => COUNTERS.signed_votes.inc(),
+        }
+        COUNTERS
+            .epoch_signatures
+            .set(self.epoch_count.total() as i64);
+        COUNTERS
+            .lifetime_signatures
+            .set(self.lifetime_count.total() as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut counts = SignatureCounts::default();
+        counts.record(1, SignatureKind::Proposal);
+        counts.record(1, SignatureKind::Vote);
+        counts.record(1, SignatureKind::Vote);
+        assert_eq!(counts.epoch, 1);
+        assert_eq!(counts.epoch_count, counts.lifetime_count);
+        assert_eq!(counts.epoch_count.votes, 2);
+
+        counts.record(2, SignatureKind::Timeout);
+        assert_eq!(counts.epoch, 2);
+        assert_eq!(counts.epoch_count.total(), 1);
+        assert_eq!(counts.epoch_count.timeouts, 1);
+        assert_eq!(
+            counts.lifetime_count,
+            SignatureCount {
+                proposals: 1,
+                timeouts: 1,
+                votes: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The messages SafetyRules signs, computed without signing them, so that an external verifier or
+//! a hardware signer can confirm it produces byte-for-byte the same messages. Every message is the
+//! domain separated hash of an LCS serialized value:
+//!
+//! `SHA3-256(SHA3-256("LIBRA::" || domain) || lcs)`
+//!
+//! where the domain is the name of the type of the value. See SafetyRules::vote_signing_message
+//! for the message of a vote.
+
+use consensus_types::{block_data::BlockData, common::Payload, timeout::Timeout};
+use libra_crypto::hash::{CryptoHash, HashValue};
+use libra_types::ledger_info::LedgerInfo;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SigningMessage {
+    /// The name of the type of the value, which separates the hashes of different types
+    pub domain: String,
+    /// The LCS serialization of the value
+    pub lcs: Vec<u8>,
+    /// The message that is signed
+    pub hash: HashValue,
+}
+
+impl SigningMessage {
+    fn new<V: CryptoHash + Serialize>(domain: &str, value: &V) -> Self {
+        Self {
+            domain: domain.to_string(),
+            lcs: lcs::to_bytes(value).expect("Unable to serialize signed value"),
+            hash: value.hash(),
+        }
+    }
+}
+
+/// The message signed for a proposal.
+pub fn proposal_signing_message<T: Payload>(block_data: &BlockData<T>) -> SigningMessage {
+    SigningMessage::new("BlockData", block_data)
+}
+
+/// The message signed for a timeout.
+pub fn timeout_signing_message(timeout: &Timeout) -> SigningMessage {
+    SigningMessage::new("Timeout", timeout)
+}
+
+/// The message signed for a vote, the ledger info the vote commits to, which must already carry
+/// the hash of the vote data.
+pub(crate) fn ledger_info_signing_message(ledger_info: &LedgerInfo) -> SigningMessage {
+    SigningMessage::new("LedgerInfo", ledger_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_vectors;
+
+    /// Derives the hash as documented, independently of CryptoHash
+    fn derive_hash(message: &SigningMessage) -> HashValue {
+        let domain = HashValue::from_sha3_256(format!("LIBRA::{}", message.domain).as_bytes());
+        HashValue::from_sha3_256(&[domain.as_ref(), &message.lcs[..]].concat())
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let messages = vec![
+            proposal_signing_message(&test_vectors::proposal()),
+            timeout_signing_message(&test_vectors::timeout()),
+            ledger_info_signing_message(&test_vectors::commit_ledger_info()),
+        ];
+        for message in &messages {
+            assert_eq!(derive_hash(message), message.hash);
+        }
+
+        let vectors = test_vectors::generate().vectors;
+        let vector = |name: &str| vectors.iter().find(|v| v.name == name).unwrap().clone();
+        for (message, name) in messages
+            .iter()
+            .zip(&["proposal", "timeout", "commit_ledger_info"])
+        {
+            let vector = vector(name);
+            assert_eq!(hex::encode(&message.lcs), vector.lcs);
+            assert_eq!(message.hash.to_hex(), vector.signing_message);
+        }
+    }
+}
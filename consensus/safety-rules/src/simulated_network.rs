@@ -0,0 +1,158 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A deterministic stand-in for libra-secure-net that runs a remote SafetyRules client and its
+//! server within a single thread. The server processes every request as soon as the client asks
+//! to read, and all faults are drawn from a seeded generator, so a seed reproduces exactly the
+//! same sequence of lost, duplicated and reordered messages.
+
+use crate::{
+    remote_service::{self, MessageChannel, RemoteClient},
+    request_queue::RequestQueue,
+    serializer::{SerializerClient, SerializerService},
+    Error, SafetyRules,
+};
+use consensus_types::common::Payload;
+use libra_config::config::RequestQueueConfig;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// The likelihood, in percent, of each fault being applied to a message in transit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetworkFaults {
+    pub drop_percent: u64,
+    pub duplicate_percent: u64,
+    pub reorder_percent: u64,
+}
+
+pub struct SimulatedNetwork<T> {
+    state: Arc<Mutex<NetworkState<T>>>,
+}
+
+impl<T: Payload> SimulatedNetwork<T> {
+    pub fn new(safety_rules: SafetyRules<T>, seed: u64, faults: NetworkFaults) -> Self {
+        let state = NetworkState {
+            faults,
+            // Xorshift must not be seeded with 0
+            rng: seed | 1,
+            queue: RequestQueue::new(RequestQueueConfig::default()),
+            server: Mutex::new(SerializerService::new(safety_rules)),
+            to_client: VecDeque::new(),
+            to_server: VecDeque::new(),
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// A client whose request ids, and hence messages, are reproducible.
+    pub fn client(&self) -> SerializerClient<T> {
+        let channel = SimulatedChannel {
+            state: self.state.clone(),
+        };
+        SerializerClient::new_client(Box::new(RemoteClient::new(Box::new(channel))))
+            .with_sequential_request_ids(0)
+    }
+}
+
+struct NetworkState<T> {
+    faults: NetworkFaults,
+    rng: u64,
+    queue: RequestQueue,
+    server: Mutex<SerializerService<T>>,
+    to_client: VecDeque<Vec<u8>>,
+    to_server: VecDeque<Vec<u8>>,
+}
+
+impl<T: Payload> NetworkState<T> {
+    fn chance(&mut self, percent: u64) -> bool {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng % 100 < percent
+    }
+
+    /// Delivers the message subject to the configured faults.
+    fn send(&mut self, message: Vec<u8>, to_server: bool) {
+        if self.chance(self.faults.drop_percent) {
+            return;
+        }
+        let copies = if self.chance(self.faults.duplicate_percent) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let reorder = self.chance(self.faults.reorder_percent);
+            let queue = if to_server {
+                &mut self.to_server
+            } else {
+                &mut self.to_client
+            };
+            if reorder {
+                queue.push_front(message.clone());
+            } else {
+                queue.push_back(message.clone());
+            }
+        }
+    }
+
+    /// Lets the server process everything it has received.
+    fn run_server(&mut self) {
+        while let Some(request) = self.to_server.pop_front() {
+            let mut channel = ServerChannel {
+                request: Some(request),
+                response: None,
+            };
+            let _ =
+                remote_service::process_one_message(&mut channel, &self.server, &mut self.queue);
+            if let Some(response) = channel.response {
+                self.send(response, false);
+            }
+        }
+    }
+}
+
+struct SimulatedChannel<T> {
+    state: Arc<Mutex<NetworkState<T>>>,
+}
+
+impl<T: Payload> MessageChannel for SimulatedChannel<T> {
+    /// A lost message surfaces as a failed read, as a disconnect would with libra-secure-net.
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        let mut state = self.state.lock().unwrap();
+        state.run_server();
+        state
+            .to_client
+            .pop_front()
+            .ok_or_else(|| Error::InternalError {
+                error: "Simulated message loss".into(),
+            })
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.state.lock().unwrap().send(message.to_vec(), true);
+        Ok(())
+    }
+}
+
+/// Hands a single request to the server and captures its response.
+struct ServerChannel {
+    request: Option<Vec<u8>>,
+    response: Option<Vec<u8>>,
+}
+
+impl MessageChannel for ServerChannel {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        self.request.take().ok_or_else(|| Error::InternalError {
+            error: "No request".into(),
+        })
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.response = Some(message.to_vec());
+        Ok(())
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A soak test for SafetyRules intended to be run by operators before upgrades. It drives a single
+//! validator through thousands of synthetic epochs backed by an on-disk store, churning the
+//! validator set, rotating the consensus key and restarting SafetyRules at random points. After
+//! every restart it verifies that the persisted state still refuses to sign anything that would
+//! conflict with what was signed before the restart.
+//!
+//! Usage: cargo run -p safety-rules --features testing --bin safety-rules-soak -- --epochs 10000
+
+#![forbid(unsafe_code)]
+
+use consensus_types::{
+    block::Block, block_data::BlockData, common::Round, quorum_cert::QuorumCert, timeout::Timeout,
+    vote_proposal::VoteProposal,
+};
+use libra_crypto::{
+    ed25519::Ed25519PrivateKey,
+    hash::{CryptoHash, HashValue, ACCUMULATOR_PLACEHOLDER_HASH},
+    Uniform,
+};
+use libra_secure_storage::OnDiskStorage;
+use libra_temppath::TempPath;
+use libra_types::{
+    block_info::BlockInfo,
+    epoch_change::EpochChangeProof,
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    validator_signer::ValidatorSigner,
+    validator_verifier::{ValidatorConsensusInfo, ValidatorVerifier},
+    waypoint::Waypoint,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use safety_rules::{test_utils, Error, PersistentSafetyStorage, SafetyRules, TSafetyRules};
+use std::{collections::BTreeMap, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "SafetyRules soak test",
+    about = "Loop SafetyRules through synthetic epochs with validator set churn, key rotations and restarts"
+)]
+struct Options {
+    /// Number of epochs to run
+    #[structopt(long, default_value = "1000")]
+    epochs: u64,
+
+    /// Maximum number of rounds within an epoch
+    #[structopt(long, default_value = "10")]
+    max_rounds: u64,
+
+    /// Size of the pool from which the other validators of each epoch are drawn
+    #[structopt(long, default_value = "8")]
+    validators: u8,
+
+    /// Percent chance of restarting SafetyRules after each operation
+    #[structopt(long, default_value = "5")]
+    restart_percent: u8,
+
+    /// Percent chance of rotating the consensus key at each epoch change
+    #[structopt(long, default_value = "10")]
+    rotation_percent: u8,
+
+    /// Percent chance of timing out instead of continuing the chain within a round
+    #[structopt(long, default_value = "10")]
+    timeout_percent: u8,
+
+    /// Seed for reproducing a previous run, a random one is chosen if omitted
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// On-disk storage file to use, a temporary file is used if omitted
+    #[structopt(long)]
+    path: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Stats {
+    epochs: u64,
+    proposals: u64,
+    restarts: u64,
+    rotations: u64,
+    timeouts: u64,
+    votes: u64,
+}
+
+/// The soak test keeps its own record of what has been signed within the current epoch and
+/// compares it to what SafetyRules is willing to sign after every restart.
+struct Soak {
+    epoch: u64,
+    epoch_change_proof: Vec<LedgerInfoWithSignatures>,
+    highest_proposed_round: Round,
+    last_voted_round: Round,
+    options: Options,
+    path: PathBuf,
+    pool: Vec<ValidatorSigner>,
+    rng: StdRng,
+    safety_rules: SafetyRules<Round>,
+    signer: ValidatorSigner,
+    stats: Stats,
+    validators: Vec<usize>,
+}
+
+impl Soak {
+    fn new(options: Options, path: PathBuf, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let pool: Vec<_> = (0..options.validators)
+            .map(|_| ValidatorSigner::random(rng.gen::<[u8; 32]>()))
+            .collect();
+        let validators = churn(&mut rng, pool.len());
+        let author = ValidatorSigner::random(rng.gen::<[u8; 32]>()).author();
+        let signer = ValidatorSigner::new(author, Ed25519PrivateKey::generate(&mut rng));
+
+        let genesis = BlockInfo::new(
+            0,
+            0,
+            HashValue::zero(),
+            *ACCUMULATOR_PLACEHOLDER_HASH,
+            0,
+            0,
+            Some(epoch_state(1, &signer, &pool, &validators)),
+        );
+        let genesis = LedgerInfo::new(genesis, HashValue::zero());
+        let waypoint = Waypoint::new_epoch_boundary(&genesis).unwrap();
+        let storage = PersistentSafetyStorage::initialize(
+            Box::new(OnDiskStorage::new(path.clone())),
+            signer.private_key().clone(),
+            waypoint,
+        );
+
+        let mut soak = Self {
+            epoch: 1,
+            epoch_change_proof: vec![LedgerInfoWithSignatures::new(genesis, BTreeMap::new())],
+            highest_proposed_round: 0,
+            last_voted_round: 0,
+            options,
+            path,
+            pool,
+            rng,
+            safety_rules: SafetyRules::new(signer.author(), storage),
+            signer,
+            stats: Stats::default(),
+            validators,
+        };
+        soak.initialize();
+        soak
+    }
+
+    fn run(&mut self) {
+        for _ in 0..self.options.epochs {
+            self.run_epoch();
+            self.change_epoch();
+        }
+    }
+
+    /// Extends a chain from the genesis of the current epoch, voting and proposing at each round
+    /// and occasionally timing out a round instead.
+    fn run_epoch(&mut self) {
+        let (genesis_qc, genesis_round) = self.genesis_qc();
+        let mut chain: Vec<VoteProposal<Round>> = vec![];
+        let mut round = genesis_round;
+        let rounds = self.rng.gen_range(1, self.options.max_rounds + 1);
+
+        for _ in 0..rounds {
+            round += 1;
+            if self.chance(self.options.timeout_percent) {
+                self.safety_rules
+                    .sign_timeout(&Timeout::new(self.epoch, round))
+                    .unwrap();
+                self.last_voted_round = round;
+                self.stats.timeouts += 1;
+                self.maybe_restart();
+                continue;
+            }
+
+            let proposal = match chain.last() {
+                None => test_utils::make_proposal_with_qc(round, genesis_qc.clone(), &self.signer),
+                Some(parent) => {
+                    let committed = chain.len().checked_sub(3).map(|i| &chain[i]);
+                    test_utils::make_proposal_with_parent(
+                        round,
+                        round,
+                        parent,
+                        committed,
+                        &self.signer,
+                    )
+                }
+            };
+            let qc = proposal.block().quorum_cert().clone();
+
+            let block_data =
+                BlockData::new_proposal(round, self.signer.author(), round, round, qc.clone());
+            self.safety_rules.sign_proposal(block_data).unwrap();
+            self.highest_proposed_round = round;
+            self.stats.proposals += 1;
+            self.maybe_restart();
+
+            self.safety_rules.update(&qc).unwrap();
+            self.safety_rules
+                .construct_and_sign_vote(&proposal)
+                .unwrap();
+            self.last_voted_round = round;
+            self.stats.votes += 1;
+            chain.push(proposal);
+            self.maybe_restart();
+        }
+    }
+
+    /// Ends the current epoch with a ledger info signed by its validators and moves to a new
+    /// validator set, which may include a newly rotated consensus key for this validator.
+    fn change_epoch(&mut self) {
+        let rotated_key = if self.chance(self.options.rotation_percent) {
+            Some(Ed25519PrivateKey::generate(&mut self.rng))
+        } else {
+            None
+        };
+        // The ending epoch still knows this validator by its previous key
+        let previous_signer = rotated_key.as_ref().map(|key| {
+            let signer = ValidatorSigner::new(self.signer.author(), key.clone());
+            std::mem::replace(&mut self.signer, signer)
+        });
+        let previous_validators = self.validators.clone();
+        self.validators = churn(&mut self.rng, self.pool.len());
+
+        let commit_info = BlockInfo::new(
+            self.epoch,
+            self.last_voted_round,
+            HashValue::zero(),
+            *ACCUMULATOR_PLACEHOLDER_HASH,
+            self.epoch,
+            0,
+            Some(epoch_state(
+                self.epoch + 1,
+                &self.signer,
+                &self.pool,
+                &self.validators,
+            )),
+        );
+        let ledger_info = LedgerInfo::new(commit_info, HashValue::zero());
+        let signatures: BTreeMap<_, _> = previous_validators
+            .iter()
+            .map(|index| &self.pool[*index])
+            .chain(std::iter::once(
+                previous_signer.as_ref().unwrap_or(&self.signer),
+            ))
+            .map(|signer| (signer.author(), signer.sign_message(ledger_info.hash())))
+            .collect();
+
+        // Only the proof from the current waypoint onwards is needed to initialize
+        let current = self.epoch_change_proof.pop().unwrap();
+        self.epoch_change_proof = vec![
+            current,
+            LedgerInfoWithSignatures::new(ledger_info, signatures),
+        ];
+        self.epoch += 1;
+        self.highest_proposed_round = 0;
+        self.last_voted_round = 0;
+        self.stats.epochs += 1;
+
+        if let Some(key) = rotated_key {
+            // SafetyRules only loads its key on startup, so a rotation requires a restart
+            let mut storage = PersistentSafetyStorage::new(Box::new(self.storage()));
+            storage.set_consensus_key(key).unwrap();
+            self.stats.rotations += 1;
+            self.restart();
+        } else if self.chance(self.options.restart_percent) {
+            self.restart();
+        } else {
+            self.initialize();
+        }
+    }
+
+    fn maybe_restart(&mut self) {
+        if self.chance(self.options.restart_percent) {
+            self.restart();
+        }
+    }
+
+    /// Simulates a crash by dropping SafetyRules and reopening it from disk, then verifies that
+    /// nothing that conflicts with what has already been signed can be signed again.
+    fn restart(&mut self) {
+        self.stats.restarts += 1;
+        let storage = PersistentSafetyStorage::new(Box::new(self.storage()));
+        storage
+            .verify_integrity()
+            .unwrap_or_else(|e| panic!("Storage integrity lost after restart: {}", e));
+        self.safety_rules = SafetyRules::new(self.signer.author(), storage);
+        self.initialize();
+
+        let state = self.safety_rules.consensus_state().unwrap();
+        assert_eq!(state.epoch(), self.epoch, "Epoch regressed after restart");
+        assert!(
+            state.last_voted_round() >= self.last_voted_round,
+            "Last voted round regressed after restart: {} < {}",
+            state.last_voted_round(),
+            self.last_voted_round,
+        );
+
+        let (genesis_qc, _) = self.genesis_qc();
+        if self.last_voted_round > 0 {
+            let conflicting_vote = test_utils::make_proposal_with_qc_and_proof(
+                Round::max_value(),
+                self.last_voted_round,
+                test_utils::empty_proof(),
+                genesis_qc.clone(),
+                &self.signer,
+            );
+            match self.safety_rules.construct_and_sign_vote(&conflicting_vote) {
+                Err(Error::OldProposal { .. }) => (),
+                result => panic!("Double vote at {}: {:?}", self.last_voted_round, result),
+            }
+        }
+        if self.highest_proposed_round > 0 {
+            let conflicting_proposal = BlockData::new_proposal(
+                Round::max_value(),
+                self.signer.author(),
+                self.highest_proposed_round,
+                0,
+                genesis_qc,
+            );
+            match self.safety_rules.sign_proposal(conflicting_proposal) {
+                Err(Error::EquivocatingProposal(_)) => (),
+                result => panic!(
+                    "Double proposal at {}: {:?}",
+                    self.highest_proposed_round, result
+                ),
+            }
+        }
+    }
+
+    fn initialize(&mut self) {
+        let proof = EpochChangeProof::new(self.epoch_change_proof.clone(), false);
+        self.safety_rules.initialize(&proof).unwrap();
+    }
+
+    fn genesis_qc(&self) -> (QuorumCert, Round) {
+        let ledger_info = self.epoch_change_proof.last().unwrap().ledger_info();
+        let genesis = Block::<Round>::make_genesis_block_from_ledger_info(ledger_info);
+        let qc = QuorumCert::certificate_for_genesis_from_ledger_info(ledger_info, genesis.id());
+        (qc, genesis.round())
+    }
+
+    fn storage(&self) -> OnDiskStorage {
+        OnDiskStorage::new(self.path.clone())
+    }
+
+    fn chance(&mut self, percent: u8) -> bool {
+        self.rng.gen_range(0, 100) < percent
+    }
+}
+
+/// Draws a new set of other validators from the pool, each validator has an even chance of being
+/// part of the next epoch.
+fn churn(rng: &mut StdRng, pool_size: usize) -> Vec<usize> {
+    (0..pool_size).filter(|_| rng.gen()).collect()
+}
+
+/// The validator under test always holds a quorum by itself, so that the certificates produced by
+/// `test_utils` remain valid regardless of the churn around it.
+fn epoch_state(
+    epoch: u64,
+    signer: &ValidatorSigner,
+    pool: &[ValidatorSigner],
+    validators: &[usize],
+) -> EpochState {
+    let mut infos = BTreeMap::new();
+    for index in validators {
+        let validator = &pool[*index];
+        infos.insert(
+            validator.author(),
+            ValidatorConsensusInfo::new(validator.public_key(), 1),
+        );
+    }
+    let voting_power = 3 * validators.len() as u64 + 1;
+    infos.insert(
+        signer.author(),
+        ValidatorConsensusInfo::new(signer.public_key(), voting_power),
+    );
+    EpochState {
+        epoch,
+        verifier: ValidatorVerifier::new(infos),
+    }
+}
+
+fn main() {
+    let options = Options::from_args();
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("Running SafetyRules soak test with seed {}", seed);
+
+    let temppath = TempPath::new();
+    let path = match &options.path {
+        Some(path) => path.clone(),
+        None => {
+            temppath.create_as_file().unwrap();
+            temppath.path().to_path_buf()
+        }
+    };
+
+    let mut soak = Soak::new(options, path, seed);
+    soak.run();
+
+    let stats = soak.stats;
+    println!(
+        "Completed {} epochs: {} proposals, {} votes, {} timeouts, {} restarts, {} key rotations",
+        stats.epochs, stats.proposals, stats.votes, stats.timeouts, stats.restarts, stats.rotations,
+    );
+}
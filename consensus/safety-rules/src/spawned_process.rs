@@ -3,13 +3,16 @@
 
 use crate::remote_service::RemoteService;
 use consensus_types::common::Payload;
-use libra_config::config::{NodeConfig, PersistableConfig, SafetyRulesService};
+use libra_config::config::{
+    NodeConfig, PersistableConfig, RemoteAuthentication, SafetyRulesService,
+};
 use libra_temppath::TempPath;
 use std::{marker::PhantomData, net::SocketAddr, process::Child};
 
 pub struct SpawnedProcess<T> {
     handle: Child,
     server_addr: SocketAddr,
+    authentication: Option<RemoteAuthentication>,
     _config_path: TempPath,
     marker: PhantomData<T>,
 }
@@ -22,15 +25,16 @@ impl<T: Payload> SpawnedProcess<T> {
         config.save_config(&config_path).unwrap();
 
         let service = &config.consensus.safety_rules.service;
-        let server_addr = if let SafetyRulesService::SpawnedProcess(process_config) = service {
-            process_config.server_address
+        let process_config = if let SafetyRulesService::SpawnedProcess(process_config) = service {
+            process_config
         } else {
             panic!("Invalid SafeRulesService, expected SpawnedProcess.");
         };
 
         Self {
             handle: runner::run(&config_path.path()),
-            server_addr,
+            server_addr: process_config.server_address,
+            authentication: process_config.authentication.clone(),
             _config_path: config_path,
             marker: PhantomData,
         }
@@ -41,6 +45,10 @@ impl<T: Payload> RemoteService<T> for SpawnedProcess<T> {
     fn server_address(&self) -> SocketAddr {
         self.server_addr
     }
+
+    fn authentication(&self) -> Option<&RemoteAuthentication> {
+        self.authentication.as_ref()
+    }
 }
 
 /// Kill SafetyRules process upon this object going out of scope
@@ -1,13 +1,13 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{ConsensusState, Error};
+use crate::{CommitStats, ConsensusState, Error, TrustedCheckpoint, WaypointRecord};
 use consensus_types::{
-    block::Block, block_data::BlockData, quorum_cert::QuorumCert, timeout::Timeout, vote::Vote,
-    vote_proposal::VoteProposal,
+    block::Block, block_data::BlockData, common::Round, quorum_cert::QuorumCert,
+    sync_info::SyncInfo, timeout::Timeout, vote::Vote, vote_proposal::VoteProposal,
 };
 use libra_crypto::ed25519::Ed25519Signature;
-use libra_types::epoch_change::EpochChangeProof;
+use libra_types::{epoch_change::EpochChangeProof, epoch_state::EpochState};
 
 /// Interface for SafetyRules
 pub trait TSafetyRules<T> {
@@ -15,19 +15,61 @@ pub trait TSafetyRules<T> {
     /// not include sensitive data like private keys.
     fn consensus_state(&mut self) -> Result<ConsensusState, Error>;
 
+    /// Provides how often the votes signed since startup committed a block, and why the others
+    /// did not.
+    fn commit_stats(&mut self) -> Result<CommitStats, Error>;
+
+    /// Provides the most recent waypoints that SafetyRules has been bound to, one per epoch
+    /// transition, from oldest to newest.
+    fn waypoint_history(&mut self) -> Result<Vec<WaypointRecord>, Error>;
+
+    /// Provides the epoch and the full validator set SafetyRules currently verifies against, so
+    /// that external monitoring can compare the signer's view with the on-chain state.
+    fn current_epoch_state(&mut self) -> Result<EpochState, Error>;
+
     /// Initialize SafetyRules using an Epoch ending LedgerInfo, this should map to what was
     /// provided in consensus_state. It will be used to initialize the ValidatorSet.
     /// This uses a EpochChangeProof because there's a possibility that consensus migrated to a
     /// new epoch but SafetyRules did not.
     fn initialize(&mut self, proof: &EpochChangeProof) -> Result<(), Error>;
 
+    /// Initialize SafetyRules from the trusted state that state synchronization reached, along
+    /// with the EpochChangeProof that leads to it. The proof is verified as in initialize and must
+    /// end in the epoch change ledger info described by the trusted state.
+    fn initialize_from_trusted_state(
+        &mut self,
+        trusted_state: TrustedCheckpoint,
+        proof: &EpochChangeProof,
+    ) -> Result<(), Error>;
+
     /// Learn about a new quorum certificate. This can lead to updating the preferred round or the
     /// validator verifier if this ends an epoch and increments the epoch as well.
     fn update(&mut self, qc: &QuorumCert) -> Result<(), Error>;
 
-    /// Attempts to vote for a given proposal following the voting rules.
+    /// Learn about all the certificates in a SyncInfo at once. Every certificate is verified
+    /// before any state is modified, after which the preferred round advances to the highest
+    /// 2-chain head and the last voted round advances to the highest timeout certificate. This
+    /// can also start a new epoch if one of the quorum certificates ends the current one.
+    fn update_sync_info(&mut self, sync_info: &SyncInfo) -> Result<(), Error>;
+
+    /// Reports the epoch and round the consensus process is in, which it sends periodically. If
+    /// configured, SafetyRules refuses to sign while the last reported epoch lags its own by too
+    /// much.
+    fn heartbeat(&mut self, epoch: u64, round: Round) -> Result<(), Error>;
+
+    /// Attempts to vote for a given proposal following the voting rules. The signatures on the
+    /// QC of the proposal are verified as in update, but the QC is not otherwise learned from.
     fn construct_and_sign_vote(&mut self, vote_proposal: &VoteProposal<T>) -> Result<Vote, Error>;
 
+    /// As construct_and_sign_vote, but gives up with a retriable error, before the vote is
+    /// persisted, once the deadline in milliseconds since the Unix epoch has passed. Consensus can
+    /// then time out the round instead of receiving a vote too late to matter.
+    fn construct_and_sign_vote_with_deadline(
+        &mut self,
+        vote_proposal: &VoteProposal<T>,
+        deadline_ms: Option<u64>,
+    ) -> Result<Vote, Error>;
+
     /// As the holder of the private key, SafetyRules also signs proposals or blocks.
     /// A Block is a signed BlockData along with some additional metadata.
     fn sign_proposal(&mut self, block_data: BlockData<T>) -> Result<Block<T>, Error>;
@@ -27,6 +27,9 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
+#[path = "test_utils/scenario.rs"]
+pub mod scenario;
+
 pub type Proof = AccumulatorExtensionProof<TransactionAccumulatorHasher>;
 
 pub fn empty_proof() -> Proof {
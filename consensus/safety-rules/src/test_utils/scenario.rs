@@ -0,0 +1,244 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builds internally consistent chains for safety tests. A Scenario tracks the accumulator each
+//! proposed block executes to, so that every VoteProposal carries a valid accumulator extension
+//! proof, and certifies blocks, timeouts and epoch changes with the signatures of its whole
+//! validator set. For example, a 3-chain that commits its first block:
+//!
+//! ```ignore
+//! let mut scenario = Scenario::<Round>::new(4);
+//! let a1 = scenario.propose(1, &scenario.root_qc());
+//! let a2 = scenario.propose(2, &scenario.certify(&a1));
+//! let a3 = scenario.propose(3, &scenario.certify(&a2));
+//! let a4 = scenario.propose(4, &scenario.certify(&a3));
+//! ```
+
+use crate::{persistent_safety_storage::PersistentSafetyStorage, test_utils::Proof};
+use consensus_types::{
+    block::Block,
+    common::{Payload, Round},
+    quorum_cert::QuorumCert,
+    timeout::Timeout,
+    timeout_certificate::TimeoutCertificate,
+    vote::Vote,
+    vote_data::VoteData,
+    vote_proposal::VoteProposal,
+};
+use libra_crypto::hash::{CryptoHash, HashValue, TransactionAccumulatorHasher};
+use libra_secure_storage::InMemoryStorage;
+use libra_types::{
+    block_info::BlockInfo,
+    epoch_change::EpochChangeProof,
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    on_chain_config::ValidatorSet,
+    proof::accumulator::InMemoryAccumulator,
+    validator_info::ValidatorInfo,
+    validator_signer::ValidatorSigner,
+    waypoint::Waypoint,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+type Accumulator = InMemoryAccumulator<TransactionAccumulatorHasher>;
+
+pub struct Scenario<T> {
+    signers: Vec<ValidatorSigner>,
+    /// The epoch ending ledger infos from genesis to the current epoch
+    ledger_infos: Vec<LedgerInfoWithSignatures>,
+    /// The certificate of the genesis block of the current epoch
+    root_qc: QuorumCert,
+    /// The accumulator each block executes to, by block id
+    accumulators: HashMap<HashValue, Accumulator>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Payload> Scenario<T> {
+    /// A scenario with the given number of validators of equal voting power, the signers are
+    /// those of ValidatorSigner::from_int.
+    pub fn new(num_validators: u8) -> Self {
+        let signers: Vec<_> = (0..num_validators).map(ValidatorSigner::from_int).collect();
+        let infos = signers
+            .iter()
+            .map(|v| ValidatorInfo::new_with_test_network_keys(v.author(), v.public_key(), 1))
+            .collect();
+        let genesis = LedgerInfo::mock_genesis(Some(ValidatorSet::new(infos)));
+        let mut scenario = Self {
+            signers,
+            ledger_infos: vec![],
+            root_qc: QuorumCert::certificate_for_genesis_from_ledger_info(
+                &genesis,
+                HashValue::zero(),
+            ),
+            accumulators: HashMap::new(),
+            marker: PhantomData,
+        };
+        scenario.start_epoch(
+            LedgerInfoWithSignatures::new(genesis, BTreeMap::new()),
+            Accumulator::default(),
+        );
+        scenario
+    }
+
+    pub fn signers(&self) -> &[ValidatorSigner] {
+        &self.signers
+    }
+
+    /// The epoch that proposals are made in.
+    pub fn epoch(&self) -> u64 {
+        self.root_qc.certified_block().epoch()
+    }
+
+    /// The waypoint of genesis.
+    pub fn waypoint(&self) -> Waypoint {
+        Waypoint::new_epoch_boundary(self.ledger_infos[0].ledger_info()).unwrap()
+    }
+
+    /// Storage for the validator at the given index, bound to the waypoint of genesis.
+    pub fn storage(&self, index: usize) -> PersistentSafetyStorage {
+        PersistentSafetyStorage::initialize(
+            Box::new(InMemoryStorage::new()),
+            self.signers[index].private_key().clone(),
+            self.waypoint(),
+        )
+    }
+
+    /// The proof from genesis to the current epoch.
+    pub fn epoch_change_proof(&self) -> EpochChangeProof {
+        EpochChangeProof::new(self.ledger_infos.clone(), false)
+    }
+
+    /// The certificate of the genesis block of the current epoch.
+    pub fn root_qc(&self) -> QuorumCert {
+        self.root_qc.clone()
+    }
+
+    /// The leader of a round, the validators take turns.
+    pub fn leader(&self, round: Round) -> &ValidatorSigner {
+        &self.signers[round as usize % self.signers.len()]
+    }
+
+    /// Proposes a block extending the block certified by the QC. Each block executes a single
+    /// transaction.
+    pub fn propose(&mut self, round: Round, qc: &QuorumCert) -> VoteProposal<T> {
+        let leaf = HashValue::random();
+        let block = Block::new_proposal(
+            T::default(),
+            round,
+            qc.certified_block().timestamp_usecs() + 1,
+            qc.clone(),
+            self.leader(round),
+        );
+        let parent = self.accumulator(qc.certified_block().id());
+        let proof = Proof::new(
+            parent.frozen_subtree_roots().clone(),
+            parent.num_leaves(),
+            vec![leaf],
+        );
+        let accumulator = parent.append(&[leaf]);
+        self.accumulators.insert(block.id(), accumulator);
+        VoteProposal::new(proof, block, None)
+    }
+
+    /// The QC on the proposed block, signed by every validator. The QC commits the grandparent of
+    /// the block if the three of them have contiguous rounds.
+    pub fn certify(&self, vote_proposal: &VoteProposal<T>) -> QuorumCert {
+        let block = vote_proposal.block();
+        let qc = block.quorum_cert();
+        let vote_data = VoteData::new(
+            self.block_info(vote_proposal, None),
+            qc.certified_block().clone(),
+        );
+        let commit_info = if block.round() == qc.certified_block().round() + 1
+            && qc.certified_block().round() == qc.parent_block().round() + 1
+        {
+            qc.parent_block().clone()
+        } else {
+            BlockInfo::empty()
+        };
+        let mut ledger_info = LedgerInfoWithSignatures::new(
+            LedgerInfo::new(commit_info, vote_data.hash()),
+            BTreeMap::new(),
+        );
+        for signer in &self.signers {
+            let vote = Vote::new(
+                vote_data.clone(),
+                signer.author(),
+                ledger_info.ledger_info().clone(),
+                signer,
+            );
+            ledger_info.add_signature(signer.author(), vote.signature().clone());
+        }
+        QuorumCert::new(vote_data, ledger_info)
+    }
+
+    /// A timeout certificate for the round of the current epoch, signed by every validator.
+    pub fn timeout_certificate(&self, round: Round) -> TimeoutCertificate {
+        let timeout = Timeout::new(self.epoch(), round);
+        let mut timeout_certificate = TimeoutCertificate::new(timeout.clone());
+        for signer in &self.signers {
+            timeout_certificate.add_signature(signer.author(), timeout.sign(signer));
+        }
+        timeout_certificate
+    }
+
+    /// Ends the current epoch with the proposed block, the next epoch keeps the same validators.
+    /// Returns the proof from genesis to the next epoch.
+    pub fn end_epoch(&mut self, vote_proposal: &VoteProposal<T>) -> EpochChangeProof {
+        let genesis = self.ledger_infos[0].ledger_info();
+        let next_epoch_state = EpochState {
+            epoch: self.epoch() + 1,
+            verifier: genesis.next_epoch_state().unwrap().verifier.clone(),
+        };
+        let ledger_info = LedgerInfo::new(
+            self.block_info(vote_proposal, Some(next_epoch_state)),
+            HashValue::zero(),
+        );
+        let mut ledger_info_with_sigs =
+            LedgerInfoWithSignatures::new(ledger_info.clone(), BTreeMap::new());
+        for signer in &self.signers {
+            ledger_info_with_sigs
+                .add_signature(signer.author(), signer.sign_message(ledger_info.hash()));
+        }
+        let accumulator = self.accumulator(vote_proposal.block().id());
+        let accumulator = Accumulator::new(
+            accumulator.frozen_subtree_roots().clone(),
+            accumulator.num_leaves(),
+        )
+        .unwrap();
+        self.start_epoch(ledger_info_with_sigs, accumulator);
+        self.epoch_change_proof()
+    }
+
+    fn start_epoch(&mut self, ledger_info: LedgerInfoWithSignatures, accumulator: Accumulator) {
+        let genesis = Block::<T>::make_genesis_block_from_ledger_info(ledger_info.ledger_info());
+        self.root_qc = QuorumCert::certificate_for_genesis_from_ledger_info(
+            ledger_info.ledger_info(),
+            genesis.id(),
+        );
+        self.accumulators.insert(genesis.id(), accumulator);
+        self.ledger_infos.push(ledger_info);
+    }
+
+    fn accumulator(&self, block_id: HashValue) -> &Accumulator {
+        self.accumulators
+            .get(&block_id)
+            .expect("The block was not proposed in this scenario")
+    }
+
+    fn block_info(
+        &self,
+        vote_proposal: &VoteProposal<T>,
+        next_epoch_state: Option<EpochState>,
+    ) -> BlockInfo {
+        let accumulator = self.accumulator(vote_proposal.block().id());
+        vote_proposal.block().gen_block_info(
+            accumulator.root_hash(),
+            accumulator.version(),
+            next_epoch_state,
+        )
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Canonical test vectors for the messages SafetyRules signs: proposals (BlockData), VoteData,
+//! the commit LedgerInfo carried by a vote, and timeouts. Each vector holds the LCS serialization
+//! of a fixed value, the CryptoHash of that value which is the message being signed, and the
+//! signature of a fixed key over it. The vectors are checked into test_vectors/signing.json so that
+//! alternative implementations, such as an enclave signer or one written in another language, can
+//! prove byte-for-byte signing compatibility without running this crate.
+//!
+//! Set UPDATE_BASELINE=1 when running the test_vectors tests to regenerate the file after an
+//! intentional change to the format of a signed message.
+
+use anyhow::{ensure, Result};
+use consensus_types::{
+    block_data::BlockData, quorum_cert::QuorumCert, timeout::Timeout, vote_data::VoteData,
+};
+use libra_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519Signature},
+    hash::{CryptoHash, HashValue},
+    Signature,
+};
+use libra_types::{
+    account_address::AccountAddress,
+    block_info::BlockInfo,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    validator_signer::ValidatorSigner,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, convert::TryFrom, path::PathBuf};
+
+/// The location of the checked in test vectors, relative to the root of this crate.
+pub const TEST_VECTORS_PATH: &str = "test_vectors/signing.json";
+
+/// A single signed message, all fields are hex encoded.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TestVector {
+    pub name: String,
+    /// The LCS serialization of the value
+    pub lcs: String,
+    /// The CryptoHash of the value, which is the message that is signed
+    pub signing_message: String,
+    /// The Ed25519 signature of the signer over the signing message
+    pub signature: String,
+}
+
+/// The test vectors along with the identity of the signer that produced them.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TestVectors {
+    pub author: String,
+    pub public_key: String,
+    pub vectors: Vec<TestVector>,
+}
+
+/// The signer of the test vectors, its private key is 32 bytes of 0x01.
+pub fn signer() -> ValidatorSigner {
+    let private_key = Ed25519PrivateKey::try_from(&[1u8; 32][..]).unwrap();
+    ValidatorSigner::new(
+        AccountAddress::new([1; AccountAddress::LENGTH]),
+        private_key,
+    )
+}
+
+fn block_info(round: u64, id: u8, version: u64) -> BlockInfo {
+    BlockInfo::new(
+        1,
+        round,
+        HashValue::new([id; HashValue::LENGTH]),
+        HashValue::new([id + 1; HashValue::LENGTH]),
+        version,
+        round * 1000,
+        None,
+    )
+}
+
+/// The proposal in round 3 of epoch 1 along with the QC it extends, a QC without signatures for
+/// its parent in round 2, itself the child of a block in round 1.
+pub fn proposal() -> BlockData<Vec<u8>> {
+    let qc_vote_data = VoteData::new(block_info(2, 0x21, 2), block_info(1, 0x11, 1));
+    let qc_ledger_info = LedgerInfo::new(BlockInfo::empty(), qc_vote_data.hash());
+    let qc = QuorumCert::new(
+        qc_vote_data,
+        LedgerInfoWithSignatures::new(qc_ledger_info, BTreeMap::new()),
+    );
+    BlockData::new_proposal(vec![1, 2, 3], signer().author(), 3, 3000, qc)
+}
+
+/// The vote data for the proposal.
+pub fn vote_data() -> VoteData {
+    VoteData::new(block_info(3, 0x31, 3), block_info(2, 0x21, 2))
+}
+
+/// The ledger info a vote for the proposal signs, it commits the block in round 1.
+pub fn commit_ledger_info() -> LedgerInfo {
+    LedgerInfo::new(block_info(1, 0x11, 1), vote_data().hash())
+}
+
+pub fn timeout() -> Timeout {
+    Timeout::new(1, 4)
+}
+
+fn test_vector<V: CryptoHash + Serialize>(name: &str, value: &V) -> TestVector {
+    let signing_message = value.hash();
+    TestVector {
+        name: name.to_string(),
+        lcs: hex::encode(lcs::to_bytes(value).expect("Unable to serialize test vector")),
+        signing_message: signing_message.to_hex(),
+        signature: hex::encode(signer().sign_message(signing_message).to_bytes()),
+    }
+}
+
+/// Generates the canonical test vectors.
+pub fn generate() -> TestVectors {
+    let signer = signer();
+    TestVectors {
+        author: hex::encode(signer.author()),
+        public_key: hex::encode(signer.public_key().to_bytes()),
+        vectors: vec![
+            test_vector("proposal", &proposal()),
+            test_vector("vote_data", &vote_data()),
+            test_vector("commit_ledger_info", &commit_ledger_info()),
+            test_vector("timeout", &timeout()),
+        ],
+    }
+}
+
+/// Verifies that every signature of the test vectors is valid for its signing message and that
+/// the vectors are exactly those this implementation generates.
+pub fn verify(test_vectors: &TestVectors) -> Result<()> {
+    let public_key = signer().public_key();
+    for vector in &test_vectors.vectors {
+        let message = HashValue::from_slice(&hex::decode(&vector.signing_message)?)?;
+        let signature = Ed25519Signature::try_from(&hex::decode(&vector.signature)?[..])?;
+        signature.verify(&message, &public_key)?;
+    }
+
+    let expected = generate();
+    for (expected, actual) in expected.vectors.iter().zip(&test_vectors.vectors) {
+        ensure!(
+            expected == actual,
+            "Test vector {} differs, expected {:?}",
+            actual.name,
+            expected
+        );
+    }
+    ensure!(
+        expected == *test_vectors,
+        "Test vectors differ from the generated ones"
+    );
+    Ok(())
+}
+
+/// The path of the checked in test vectors.
+pub fn test_vectors_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(TEST_VECTORS_PATH)
+}
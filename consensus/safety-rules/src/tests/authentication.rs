@@ -0,0 +1,292 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    remote_service,
+    request_queue::RequestQueue,
+    serializer::{SafetyRulesInput, SafetyRulesRequest, SafetyRulesResponse, SerializerService},
+    test_utils, Anomaly, AuthenticatedClient, AuthenticatedServer, ConsensusState, Error,
+    MessageChannel, RequestId, SafetyRules,
+};
+use consensus_types::common::Round;
+use libra_config::config::{ClientKey, RemoteAuthentication, RequestQueueConfig};
+use libra_types::validator_signer::ValidatorSigner;
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Mutex},
+};
+
+/// One end of an in-memory connection, the messages written to it are read from the other end.
+#[derive(Clone)]
+struct Pipe {
+    incoming: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    outgoing: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+fn pipe() -> (Pipe, Pipe) {
+    let a = Arc::new(Mutex::new(VecDeque::new()));
+    let b = Arc::new(Mutex::new(VecDeque::new()));
+    let left = Pipe {
+        incoming: a.clone(),
+        outgoing: b.clone(),
+    };
+    let right = Pipe {
+        incoming: b,
+        outgoing: a,
+    };
+    (left, right)
+}
+
+impl MessageChannel for Pipe {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        self.try_read()?.ok_or_else(|| Error::InternalError {
+            error: "No message".into(),
+        })
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.outgoing.lock().unwrap().push_back(message.to_vec());
+        Ok(())
+    }
+
+    fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.incoming.lock().unwrap().pop_front())
+    }
+}
+
+fn authentication(client: &str, key: u8) -> RemoteAuthentication {
+    RemoteAuthentication {
+        client: client.into(),
+        clients: vec![
+            ClientKey {
+                id: "consensus".into(),
+                key: hex::encode([key; 32]),
+            },
+            ClientKey {
+                id: "monitor".into(),
+                key: hex::encode([key + 1; 32]),
+            },
+        ],
+    }
+}
+
+type Handler = Box<dyn FnMut(&mut AuthenticatedServer) -> Result<(), Error> + Send + Sync>;
+
+struct Server {
+    pipe: Pipe,
+    channel: AuthenticatedServer,
+    handler: Handler,
+}
+
+/// A client's end of a connection to an AuthenticatedServer, which handles everything written to
+/// it whenever a client reads, so that a client and the server run within a single thread.
+#[derive(Clone)]
+struct Loopback {
+    pipe: Pipe,
+    server: Arc<Mutex<Server>>,
+    /// Every frame written by a client
+    frames: Arc<Mutex<Vec<Vec<u8>>>>,
+    /// Every error of the handler
+    errors: Arc<Mutex<Vec<Error>>>,
+}
+
+impl Loopback {
+    fn new(handler: Handler) -> Self {
+        let (client_end, server_end) = pipe();
+        let channel = AuthenticatedServer::new(
+            Box::new(server_end.clone()),
+            &authentication("consensus", 1),
+        )
+        .unwrap();
+        let server = Server {
+            pipe: server_end,
+            channel,
+            handler,
+        };
+        Self {
+            pipe: client_end,
+            server: Arc::new(Mutex::new(server)),
+            frames: Arc::new(Mutex::new(Vec::new())),
+            errors: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn echo() -> Self {
+        Self::new(Box::new(|server| {
+            if let Some(message) = server.try_read()? {
+                server.write(&message)?;
+            }
+            Ok(())
+        }))
+    }
+
+    fn client(&self, id: &str, key: u8) -> AuthenticatedClient {
+        AuthenticatedClient::new(Box::new(self.clone()), &authentication(id, key)).unwrap()
+    }
+
+    /// Writes a frame as if a client had sent it.
+    fn inject(&self, frame: Vec<u8>) {
+        self.pipe.clone().write(&frame).unwrap();
+    }
+
+    fn last_frame(&self) -> Vec<u8> {
+        self.frames.lock().unwrap().last().unwrap().clone()
+    }
+
+    /// Counts, and forgets, the authentication failures of the server.
+    fn authentication_failures(&self) -> usize {
+        self.errors
+            .lock()
+            .unwrap()
+            .drain(..)
+            .filter(|error| matches!(error, Error::AuthenticationFailed(_)))
+            .count()
+    }
+}
+
+impl MessageChannel for Loopback {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        {
+            let mut server = self.server.lock().unwrap();
+            let Server {
+                pipe,
+                channel,
+                handler,
+            } = &mut *server;
+            while !pipe.incoming.lock().unwrap().is_empty() {
+                if let Err(error) = handler(channel) {
+                    self.errors.lock().unwrap().push(error);
+                }
+            }
+        }
+        self.pipe.read()
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.frames.lock().unwrap().push(message.to_vec());
+        self.pipe.write(message)
+    }
+}
+
+fn is_authentication_failure<T>(result: Result<T, Error>) -> bool {
+    matches!(result, Err(Error::AuthenticationFailed(_)))
+}
+
+fn intruder(network: &Loopback) -> AuthenticatedClient {
+    let config = RemoteAuthentication {
+        client: "intruder".into(),
+        clients: vec![ClientKey {
+            id: "intruder".into(),
+            key: hex::encode([1u8; 32]),
+        }],
+    };
+    AuthenticatedClient::new(Box::new(network.clone()), &config).unwrap()
+}
+
+#[test]
+fn test_round_trip() {
+    let network = Loopback::echo();
+    let mut consensus = network.client("consensus", 1);
+    consensus.write(b"first").unwrap();
+    assert_eq!(consensus.read().unwrap(), b"first");
+    consensus.write(b"second").unwrap();
+    assert_eq!(consensus.read().unwrap(), b"second");
+
+    // Another client, with a key of its own, takes over the connection
+    let mut monitor = network.client("monitor", 1);
+    monitor.write(b"monitor").unwrap();
+    assert_eq!(monitor.read().unwrap(), b"monitor");
+
+    // Which ends the session of the first client, it then opens a new one
+    consensus.write(b"third").unwrap();
+    assert!(consensus.read().is_err());
+    assert_eq!(network.authentication_failures(), 1);
+    consensus.write(b"third").unwrap();
+    assert_eq!(consensus.read().unwrap(), b"third");
+    assert_eq!(network.authentication_failures(), 0);
+}
+
+#[test]
+fn test_invalid_config() {
+    let (client_end, _server_end) = pipe();
+    let mut config = authentication("consensus", 1);
+    config.client = "unknown".into();
+    assert!(AuthenticatedClient::new(Box::new(client_end.clone()), &config).is_err());
+    let mut config = authentication("consensus", 1);
+    config.clients[0].key = "not hex".into();
+    assert!(AuthenticatedClient::new(Box::new(client_end.clone()), &config).is_err());
+    config.clients[0].key = hex::encode([1u8; 16]);
+    assert!(AuthenticatedServer::new(Box::new(client_end), &config).is_err());
+}
+
+#[test]
+fn test_refused_clients() {
+    let network = Loopback::echo();
+
+    // The service does not hold this key, so the client refuses its welcome
+    let mut client = network.client("consensus", 2);
+    assert!(is_authentication_failure(client.write(b"request")));
+    assert_eq!(network.authentication_failures(), 0);
+
+    // An unknown client is not welcomed at all
+    assert!(intruder(&network).write(b"request").is_err());
+    assert_eq!(network.authentication_failures(), 1);
+}
+
+#[test]
+fn test_refused_messages() {
+    let network = Loopback::echo();
+    let mut client = network.client("consensus", 1);
+    client.write(b"request").unwrap();
+    assert_eq!(client.read().unwrap(), b"request");
+    let request = network.last_frame();
+
+    // A replayed and a tampered request within the session
+    network.inject(request.clone());
+    let mut tampered = request.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    network.inject(tampered);
+    client.write(b"next").unwrap();
+    assert_eq!(client.read().unwrap(), b"next");
+    assert_eq!(network.authentication_failures(), 2);
+
+    // A request replayed into a later session
+    let mut client = network.client("consensus", 1);
+    client.write(b"again").unwrap();
+    assert_eq!(client.read().unwrap(), b"again");
+    network.inject(request);
+    client.write(b"last").unwrap();
+    assert_eq!(client.read().unwrap(), b"last");
+    assert_eq!(network.authentication_failures(), 1);
+}
+
+#[test]
+fn test_unauthenticated_request_is_reported() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut service = SerializerService::new(SafetyRules::<Round>::new(signer.author(), storage));
+    let (sender, anomalies) = mpsc::channel();
+    service.set_anomaly_reporter(sender);
+    let service = Mutex::new(service);
+    let mut queue = RequestQueue::new(RequestQueueConfig::default());
+    let network = Loopback::new(Box::new(move |server| {
+        remote_service::process_one_message(server, &service, &mut queue)
+    }));
+
+    assert!(intruder(&network).write(b"request").is_err());
+    match anomalies.try_recv().unwrap() {
+        Anomaly::AuthenticationFailure { request, .. } => assert_eq!(request, "unauthenticated"),
+        anomaly => panic!("Unexpected anomaly: {}", anomaly),
+    }
+
+    let mut client = network.client("consensus", 1);
+    let request = SafetyRulesRequest::<Round> {
+        id: RequestId::random(),
+        input: SafetyRulesInput::ConsensusState,
+    };
+    client.write(&lcs::to_bytes(&request).unwrap()).unwrap();
+    let response: SafetyRulesResponse = lcs::from_bytes(&client.read().unwrap()).unwrap();
+    let output: Result<ConsensusState, Error> = lcs::from_bytes(&response.output).unwrap();
+    output.unwrap();
+    assert!(anomalies.try_recv().is_err());
+}
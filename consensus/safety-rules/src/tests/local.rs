@@ -1,8 +1,8 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, tests::suite, SafetyRulesManager, TSafetyRules};
-use consensus_types::common::{Payload, Round};
+use crate::{test_utils, tests::suite, Error, ProposalInspector, SafetyRulesManager, TSafetyRules};
+use consensus_types::common::{Author, Payload, Round};
 use libra_types::validator_signer::ValidatorSigner;
 
 #[test]
@@ -17,3 +17,92 @@ fn safety_rules<T: Payload>() -> (Box<dyn TSafetyRules<T>>, ValidatorSigner) {
     let safety_rules = safety_rules_manager.client();
     (safety_rules, signer)
 }
+
+#[test]
+fn test_rejection_reports() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let safety_rules_manager = SafetyRulesManager::<Round>::new_local(signer.author(), storage);
+    let reports = safety_rules_manager.rejection_reports().unwrap();
+    let mut safety_rules = safety_rules_manager.client();
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+
+    safety_rules.initialize(&proof).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    assert!(reports.try_recv().is_err());
+
+    let error = Error::OldProposal {
+        last_voted_round: round + 1,
+        proposal_round: round + 1,
+    };
+    assert_eq!(
+        safety_rules.construct_and_sign_vote(&a1),
+        Err(error.clone())
+    );
+
+    let report = reports.try_recv().unwrap();
+    assert_eq!(report.block_id, a1.block().id());
+    assert_eq!(report.round, round + 1);
+    assert_eq!(report.error, error);
+    assert_eq!(report.last_voted_round, round + 1);
+}
+
+struct MaxPayloadBytes(usize);
+
+impl ProposalInspector<Vec<u8>> for MaxPayloadBytes {
+    fn inspect(
+        &self,
+        _round: Round,
+        _author: Option<Author>,
+        payload: Option<&Vec<u8>>,
+    ) -> Result<(), String> {
+        match payload {
+            Some(payload) if payload.len() > self.0 => {
+                Err(format!("{} payload bytes exceed {}", payload.len(), self.0))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_proposal_inspector() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let safety_rules_manager = SafetyRulesManager::<Vec<u8>>::new_local(signer.author(), storage);
+    safety_rules_manager
+        .set_proposal_inspector(Box::new(MaxPayloadBytes(2)))
+        .unwrap();
+    let mut safety_rules = safety_rules_manager.client();
+
+    let (proof, genesis_qc) = suite::make_genesis::<Vec<u8>>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let a1 = test_utils::make_proposal_with_qc_and_proof(
+        vec![0; 3],
+        round + 1,
+        test_utils::empty_proof(),
+        genesis_qc.clone(),
+        &signer,
+    );
+    let b1 = test_utils::make_proposal_with_qc_and_proof(
+        vec![0; 2],
+        round + 1,
+        test_utils::empty_proof(),
+        genesis_qc,
+        &signer,
+    );
+
+    safety_rules.initialize(&proof).unwrap();
+    assert_eq!(
+        safety_rules.construct_and_sign_vote(&a1),
+        Err(Error::ProposalVetoed {
+            round: round + 1,
+            reason: "3 payload bytes exceed 2".to_string(),
+        })
+    );
+    // A veto does not count as a vote in the round
+    safety_rules.construct_and_sign_vote(&b1).unwrap();
+}
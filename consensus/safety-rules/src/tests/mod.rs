@@ -1,11 +1,15 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+mod authentication;
 mod local;
+mod model_checker;
 mod networking;
 mod safety_rules;
 mod serializer;
+mod simulated_network;
 mod spawned_process;
 mod suite;
+mod test_vectors;
 mod thread;
 mod vault;
@@ -1,9 +1,16 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, SafetyRulesManager};
+use crate::{
+    remote_service,
+    request_queue::RequestQueue,
+    serializer::{SafetyRulesInput, SafetyRulesRequest, SafetyRulesResponse, SerializerService},
+    test_utils, Error, MessageChannel, RequestId, SafetyRules, SafetyRulesManager,
+};
 use consensus_types::common::Round;
+use libra_config::config::{OverflowPolicy, RequestQueueConfig};
 use libra_types::validator_signer::ValidatorSigner;
+use std::{collections::VecDeque, sync::Mutex};
 
 #[test]
 fn test_reconnect() {
@@ -16,3 +23,68 @@ fn test_reconnect() {
     let state1 = safety_rules_manager.client().consensus_state().unwrap();
     assert_eq!(state0, state1);
 }
+
+/// Delivers requests that have all arrived before the first read and records the responses.
+struct ScriptedChannel {
+    requests: VecDeque<Vec<u8>>,
+    responses: Vec<Vec<u8>>,
+}
+
+impl MessageChannel for ScriptedChannel {
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        self.requests
+            .pop_front()
+            .ok_or_else(|| Error::InternalError {
+                error: "No request".into(),
+            })
+    }
+
+    fn write(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.responses.push(message.to_vec());
+        Ok(())
+    }
+
+    fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.requests.pop_front())
+    }
+}
+
+#[test]
+fn test_malformed_request_keeps_queue() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let service = SerializerService::new(SafetyRules::<Round>::new(signer.author(), storage));
+    let service = Mutex::new(service);
+    let mut queue = RequestQueue::new(RequestQueueConfig {
+        depth: 1,
+        overflow_policy: OverflowPolicy::RejectNewest,
+    });
+
+    let request = |id| {
+        lcs::to_bytes(&SafetyRulesRequest::<Round> {
+            id,
+            input: SafetyRulesInput::ConsensusState,
+        })
+        .unwrap()
+    };
+    let (queued, rejected) = (RequestId::random(), RequestId::random());
+    let mut channel = ScriptedChannel {
+        requests: vec![request(queued), vec![0xff; 4], request(rejected)].into(),
+        responses: Vec::new(),
+    };
+    remote_service::process_one_message(&mut channel, &service, &mut queue).unwrap();
+
+    // The malformed request is dropped, the full queue turns the other one away and the queued
+    // request is still served
+    let responses: Vec<SafetyRulesResponse> = channel
+        .responses
+        .iter()
+        .map(|response| lcs::from_bytes(response).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0].id, rejected);
+    let output: Result<(), Error> = lcs::from_bytes(&responses[0].output).unwrap();
+    assert_eq!(output, Err(Error::RequestQueueFull(1)));
+    assert_eq!(responses[1].id, queued);
+    assert!(queue.is_empty());
+}
@@ -1,9 +1,40 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, tests::suite, SafetyRules, TSafetyRules};
-use consensus_types::common::{Payload, Round};
-use libra_types::validator_signer::ValidatorSigner;
+use crate::{
+    persistent_safety_storage::PersistentSafetyStorage,
+    proposal_signing_message, reconcile_waypoint,
+    test_utils::{self, scenario::Scenario, Proof},
+    tests::{model_checker, suite},
+    timeout_signing_message, Anomaly, Error, SafetyRules, TSafetyRules, WaypointReconciliation,
+};
+use consensus_types::{
+    block::Block,
+    block_data::BlockData,
+    common::{Payload, Round},
+    quorum_cert::QuorumCert,
+    timeout::Timeout,
+    vote_proposal::VoteProposal,
+};
+use libra_config::config::{AuditLogConfig, FailoverConfig, FeatureFlags, SafetyRulesConfig};
+use libra_crypto::{
+    hash::{CryptoHash, HashValue},
+    Signature,
+};
+use libra_global_constants::{CHAIN_ID, EPOCH};
+use libra_secure_storage::{
+    Fault, InMemoryStorage, KVStorage, OnDiskStorage, Operation, ProxyStorage, Value,
+};
+use libra_temppath::TempPath;
+use libra_types::{
+    block_info::BlockInfo,
+    epoch_change::EpochChangeProof,
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    validator_signer::ValidatorSigner,
+    waypoint::Waypoint,
+};
+use std::{collections::BTreeMap, sync::mpsc};
 
 #[test]
 fn test() {
@@ -16,3 +47,1093 @@ fn safety_rules<T: Payload>() -> (Box<dyn TSafetyRules<T>>, ValidatorSigner) {
     let safety_rules = Box::new(SafetyRules::<T>::new(signer.author(), storage));
     (safety_rules, signer)
 }
+
+#[test]
+fn test_chain_id() {
+    let signer = ValidatorSigner::from_int(0);
+    let (proof, _genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+
+    let temppath = TempPath::new();
+    temppath.create_as_file().unwrap();
+    let internal_storage = Box::new(OnDiskStorage::new(temppath.path().to_path_buf()));
+    let mut storage = PersistentSafetyStorage::initialize(
+        internal_storage,
+        signer.private_key().clone(),
+        waypoint,
+    );
+    storage.set_expected_chain_id("testnet").unwrap();
+
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+    safety_rules.warm_up().unwrap();
+    safety_rules.initialize(&proof).unwrap();
+
+    // Reprovision the same backend for a different network
+    let mut other_storage = OnDiskStorage::new(temppath.path().to_path_buf());
+    other_storage
+        .set(CHAIN_ID, Value::String("mainnet".to_string()))
+        .unwrap();
+
+    assert_eq!(
+        safety_rules.warm_up(),
+        Err(Error::IncorrectChainId(
+            "mainnet".to_string(),
+            "testnet".to_string()
+        ))
+    );
+    assert_eq!(
+        safety_rules.initialize(&proof),
+        Err(Error::IncorrectChainId(
+            "mainnet".to_string(),
+            "testnet".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_warm_up_restores_verifier() {
+    let signer = ValidatorSigner::from_int(0);
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let round = genesis_qc.certified_block().round();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let a2 = test_utils::make_proposal_with_parent(round + 2, round + 2, &a1, None, &signer);
+
+    let temppath = TempPath::new();
+    temppath.create_as_file().unwrap();
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(OnDiskStorage::new(temppath.path().to_path_buf())),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+    safety_rules.initialize(&proof).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+
+    // After a restart, the verifier of the epoch is available before initialize
+    let storage =
+        PersistentSafetyStorage::new(Box::new(OnDiskStorage::new(temppath.path().to_path_buf())));
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+    assert_eq!(safety_rules.current_epoch_state().unwrap().epoch, 1);
+    safety_rules.update(a2.block().quorum_cert()).unwrap();
+    safety_rules.construct_and_sign_vote(&a2).unwrap();
+}
+
+#[test]
+fn test_chain_id_mismatch_at_initialize() {
+    let signer = ValidatorSigner::from_int(0);
+    let (proof, _genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+
+    // Storage bootstrapped for one network and opened for another
+    let mut storage = PersistentSafetyStorage::initialize_for_chain(
+        Box::new(InMemoryStorage::new()),
+        signer.private_key().clone(),
+        waypoint,
+        "mainnet",
+    );
+    storage.set_expected_chain_id("testnet").unwrap();
+
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+    assert_eq!(
+        safety_rules.initialize(&proof),
+        Err(Error::IncorrectChainId(
+            "mainnet".to_string(),
+            "testnet".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_maintenance_mode() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+
+    assert_eq!(
+        safety_rules.update(a1.block().quorum_cert()),
+        Err(Error::NotInitialized)
+    );
+    safety_rules.initialize(&proof).unwrap();
+    safety_rules.update(a1.block().quorum_cert()).unwrap();
+
+    safety_rules.enter_maintenance_mode();
+    assert_eq!(
+        safety_rules.update(a1.block().quorum_cert()),
+        Err(Error::MaintenanceMode)
+    );
+    assert_eq!(
+        safety_rules.construct_and_sign_vote(&a1),
+        Err(Error::MaintenanceMode)
+    );
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(epoch, round + 1)),
+        Err(Error::MaintenanceMode)
+    );
+
+    safety_rules.initialize(&proof).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+}
+
+#[test]
+fn test_waypoint_only_signing() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let config = SafetyRulesConfig {
+        allow_waypoint_only_signing: true,
+        ..Default::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+
+    let (_proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+
+    // The rounds in storage are still enforced, but certificates cannot be verified
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    safety_rules
+        .sign_timeout(&Timeout::new(epoch, round + 1))
+        .unwrap();
+    match safety_rules.construct_and_sign_vote(&a1) {
+        Err(Error::OldProposal { .. }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(
+        safety_rules.update(a1.block().quorum_cert()),
+        Err(Error::NotInitialized)
+    );
+
+    safety_rules.enter_maintenance_mode();
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(epoch, round + 1)),
+        Err(Error::MaintenanceMode)
+    );
+}
+
+#[test]
+fn test_timeout_round_skew() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let config = SafetyRulesConfig {
+        max_timeout_round_skew: Some(2),
+        ..Default::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let a2 = test_utils::make_proposal_with_parent(Round::default(), round + 2, &a1, None, &signer);
+
+    safety_rules
+        .sign_timeout(&Timeout::new(epoch, round + 2))
+        .unwrap();
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(epoch, round + 3)),
+        Err(Error::BadTimeoutRoundSkew {
+            highest_qc_round: round,
+            max_skew: 2,
+            timeout_round: round + 3,
+        })
+    );
+
+    // A newer QC lets timeouts advance further
+    safety_rules.update(a2.block().quorum_cert()).unwrap();
+    safety_rules
+        .sign_timeout(&Timeout::new(epoch, round + 3))
+        .unwrap();
+}
+
+#[test]
+fn test_next_consensus_key() {
+    let signer = ValidatorSigner::from_int(0);
+    let next_key = ValidatorSigner::from_int(1).private_key().clone();
+    let next_signer = ValidatorSigner::new(signer.author(), next_key.clone());
+
+    // The validator set has adopted the next key while storage still signs with the current one
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&next_signer]);
+    let mut storage = PersistentSafetyStorage::initialize(
+        Box::new(InMemoryStorage::new()),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    storage.set_next_consensus_key(next_key).unwrap();
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&next_signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &next_signer);
+    let vote = safety_rules.construct_and_sign_vote(&a1).unwrap();
+
+    let li = test_utils::validator_signers_to_ledger_info(&[&next_signer]);
+    let verifier = &li.next_epoch_state().unwrap().verifier;
+    vote.verify(verifier).unwrap();
+}
+
+#[test]
+fn test_key_endorsement() {
+    let signer = ValidatorSigner::from_int(0);
+    let operator = ValidatorSigner::from_int(2);
+    let next_key = ValidatorSigner::from_int(1).private_key().clone();
+    let next_signer = ValidatorSigner::new(signer.author(), next_key.clone());
+    let config = SafetyRulesConfig {
+        consensus_key_endorser: Some(operator.public_key()),
+        ..Default::default()
+    };
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&next_signer]);
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&next_signer);
+
+    let safety_rules = |endorser: Option<&ValidatorSigner>| {
+        let mut storage = PersistentSafetyStorage::initialize(
+            Box::new(InMemoryStorage::new()),
+            signer.private_key().clone(),
+            waypoint,
+        );
+        storage.set_next_consensus_key(next_key.clone()).unwrap();
+        if let Some(endorser) = endorser {
+            let endorsement = crate::endorse_consensus_key(
+                endorser.private_key(),
+                signer.author(),
+                &next_signer.public_key(),
+            )
+            .unwrap();
+            storage
+                .set_next_consensus_key_endorsement(&endorsement)
+                .unwrap();
+        }
+        SafetyRules::<Round>::new_with_config(signer.author(), storage, &config)
+    };
+
+    // The next key is not adopted without an endorsement, nor with one by another key, such as
+    // one an attacker injected alongside it
+    for endorser in &[None, Some(&next_signer)] {
+        assert!(matches!(
+            safety_rules(*endorser).initialize(&proof),
+            Err(Error::InvalidKeyEndorsement(_))
+        ));
+    }
+
+    let mut safety_rules = safety_rules(Some(&operator));
+    safety_rules.initialize(&proof).unwrap();
+    let round = genesis_qc.certified_block().round();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &next_signer);
+    let vote = safety_rules.construct_and_sign_vote(&a1).unwrap();
+    let li = test_utils::validator_signers_to_ledger_info(&[&next_signer]);
+    vote.verify(&li.next_epoch_state().unwrap().verifier)
+        .unwrap();
+}
+
+#[test]
+fn test_interrupted_epoch_change() {
+    let signer = ValidatorSigner::from_int(0);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let proxy = ProxyStorage::new(InMemoryStorage::new());
+    let faults = proxy.faults();
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(proxy),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (genesis_proof, _genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let next_epoch_proof = model_checker::make_next_epoch_proof(&signer, &genesis_proof);
+    safety_rules.initialize(&genesis_proof).unwrap();
+
+    // Crash before the epoch is written, the waypoint has already moved on to the new epoch
+    faults.inject(Fault::error(Operation::Set).with_key(EPOCH).with_count(1));
+    match safety_rules.initialize(&next_epoch_proof) {
+        Err(Error::InternalError { .. }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // The consensus state is that from before the epoch change until it completes
+    let state = safety_rules.consensus_state().unwrap();
+    assert_eq!(state.epoch(), 1);
+    assert_eq!(state.waypoint(), waypoint);
+    match safety_rules.initialize(&genesis_proof) {
+        Err(Error::WaypointMismatch(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Retrying completes the epoch change
+    safety_rules.initialize(&next_epoch_proof).unwrap();
+    assert_eq!(safety_rules.consensus_state().unwrap().epoch(), 2);
+}
+
+#[test]
+fn test_signature_quota() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let config = SafetyRulesConfig {
+        max_signatures_per_epoch: Some(2),
+        ..Default::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    safety_rules
+        .sign_timeout(&Timeout::new(epoch, round + 1))
+        .unwrap();
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(epoch, round + 1)),
+        Err(Error::SignatureQuotaExceeded { epoch, quota: 2 })
+    );
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(epoch, round + 1)),
+        Err(Error::MaintenanceMode)
+    );
+
+    // Reinitializing does not grant a new quota within the same epoch
+    safety_rules.initialize(&proof).unwrap();
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(epoch, round + 1)),
+        Err(Error::SignatureQuotaExceeded { epoch, quota: 2 })
+    );
+}
+
+#[test]
+fn test_anomalies() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let config = SafetyRulesConfig {
+        max_signatures_per_epoch: Some(2),
+        ..Default::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+    let (sender, anomalies) = mpsc::channel();
+    safety_rules.set_anomaly_reporter(sender);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    safety_rules.initialize(&proof).unwrap();
+
+    // Ordinary rejections are not anomalies
+    let p1 = BlockData::new_proposal(1, signer.author(), round + 1, 1, genesis_qc.clone());
+    safety_rules.sign_proposal(p1.clone()).unwrap();
+    safety_rules.sign_proposal(p1).unwrap();
+    let old = BlockData::new_proposal(2, signer.author(), round, 1, genesis_qc.clone());
+    safety_rules.sign_proposal(old).unwrap_err();
+    assert!(anomalies.try_recv().is_err());
+
+    let p1_prime = BlockData::new_proposal(3, signer.author(), round + 1, 1, genesis_qc);
+    safety_rules.sign_proposal(p1_prime).unwrap_err();
+    assert_eq!(
+        anomalies.try_recv().unwrap(),
+        Anomaly::EquivocationAttempt {
+            epoch,
+            round: round + 1
+        }
+    );
+
+    safety_rules
+        .sign_timeout(&Timeout::new(epoch, round + 1))
+        .unwrap_err();
+    assert_eq!(
+        anomalies.try_recv().unwrap(),
+        Anomaly::QuotaExceeded { epoch, quota: 2 }
+    );
+    assert!(anomalies.try_recv().is_err());
+}
+
+#[test]
+fn test_failover_fencing() {
+    let signer = ValidatorSigner::from_int(0);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let temppath = TempPath::new();
+    temppath.create_as_file().unwrap();
+
+    let primary_storage = PersistentSafetyStorage::initialize(
+        Box::new(OnDiskStorage::new(temppath.path().to_path_buf())),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    let standby_storage =
+        PersistentSafetyStorage::new(Box::new(OnDiskStorage::new(temppath.path().to_path_buf())));
+
+    let config = |signer_id: &str, lease_duration_ms| {
+        let mut config = SafetyRulesConfig::default();
+        config.failover = Some(FailoverConfig {
+            signer_id: signer_id.to_string(),
+            lease_duration_ms,
+        });
+        config
+    };
+    // The primary's lease expires immediately, so that the standby can take over
+    let mut primary = SafetyRules::<Round>::new_with_config(
+        signer.author(),
+        primary_storage,
+        &config("primary", 0),
+    );
+    let mut standby = SafetyRules::<Round>::new_with_config(
+        signer.author(),
+        standby_storage,
+        &config("standby", 60_000),
+    );
+
+    let timeout = Timeout::new(1, 1);
+    primary.sign_timeout(&timeout).unwrap();
+    standby.sign_timeout(&timeout).unwrap();
+
+    // The primary has been fenced by the standby's takeover
+    match primary.sign_timeout(&timeout) {
+        Err(Error::NotLeaseHolder { holder, .. }) => assert_eq!(holder, "standby"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    standby.sign_timeout(&timeout).unwrap();
+}
+
+#[test]
+fn test_feature_flags() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut config = SafetyRulesConfig::default();
+    config.feature_flags = FeatureFlags {
+        strict_proposal_signing: true,
+        ..FeatureFlags::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+    assert_eq!(
+        safety_rules.consensus_state().unwrap().feature_flags(),
+        config.feature_flags
+    );
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc.clone(), &signer);
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+
+    // Proposals are no longer signed at or below the last voted round
+    let p1 = BlockData::new_proposal(1, signer.author(), round + 1, 1, genesis_qc.clone());
+    assert_eq!(
+        safety_rules.sign_proposal(p1).unwrap_err(),
+        Error::OldProposal {
+            last_voted_round: round + 1,
+            proposal_round: round + 1,
+        }
+    );
+    let p2 = BlockData::new_proposal(2, signer.author(), round + 2, 2, genesis_qc);
+    safety_rules.sign_proposal(p2).unwrap();
+}
+
+fn safety_rules_with_flags(
+    signer: &ValidatorSigner,
+    feature_flags: FeatureFlags,
+) -> SafetyRules<Round> {
+    let storage = test_utils::test_storage(signer);
+    let mut config = SafetyRulesConfig::default();
+    config.feature_flags = feature_flags;
+    SafetyRules::<Round>::new_with_config(signer.author(), storage, &config)
+}
+
+#[test]
+fn test_two_chain() {
+    // build a tree of the following form:
+    //
+    // genesis---a1   a2
+    //             \__/
+    //
+    // a1 and a2 are not at consecutive rounds, so the vote for a2 only commits genesis under the
+    // 2-chain rule, which also locks on a1 instead of genesis
+    let signer = ValidatorSigner::from_int(0);
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let genesis_id = genesis_qc.certified_block().id();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let a2 = test_utils::make_proposal_with_parent(round + 3, round + 3, &a1, None, &signer);
+
+    for &two_chain in &[false, true] {
+        let mut safety_rules = safety_rules_with_flags(
+            &signer,
+            FeatureFlags {
+                two_chain,
+                ..FeatureFlags::default()
+            },
+        );
+        safety_rules.initialize(&proof).unwrap();
+        safety_rules.construct_and_sign_vote(&a1).unwrap();
+        safety_rules.update(a2.block().quorum_cert()).unwrap();
+        let vote = safety_rules.construct_and_sign_vote(&a2).unwrap();
+
+        let preferred_round = safety_rules.consensus_state().unwrap().preferred_round();
+        if two_chain {
+            assert_eq!(vote.ledger_info().consensus_block_id(), genesis_id);
+            assert_eq!(preferred_round, round + 1);
+        } else {
+            assert_eq!(vote.ledger_info().consensus_block_id(), HashValue::zero());
+            assert_eq!(preferred_round, round);
+        }
+    }
+}
+
+#[test]
+fn test_timestamp_checks() {
+    // b2 extends a1 but carries the timestamp of a1, which is only rejected with timestamp checks
+    let signer = ValidatorSigner::from_int(0);
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let a2 = test_utils::make_proposal_with_parent(round + 2, round + 2, &a1, None, &signer);
+    let timestamp_usecs = a1.block().timestamp_usecs();
+    let b2 = VoteProposal::new(
+        a2.accumulator_extension_proof().clone(),
+        Block::new_proposal(
+            round + 2,
+            round + 2,
+            timestamp_usecs,
+            a2.block().quorum_cert().clone(),
+            &signer,
+        ),
+        None,
+    );
+
+    for &timestamp_checks in &[false, true] {
+        let mut safety_rules = safety_rules_with_flags(
+            &signer,
+            FeatureFlags {
+                timestamp_checks,
+                ..FeatureFlags::default()
+            },
+        );
+        safety_rules.initialize(&proof).unwrap();
+        safety_rules.construct_and_sign_vote(&a1).unwrap();
+
+        let result = safety_rules.construct_and_sign_vote(&b2);
+        if timestamp_checks {
+            assert_eq!(
+                result.unwrap_err(),
+                Error::InvalidTimestamp {
+                    parent_timestamp_usecs: timestamp_usecs,
+                    timestamp_usecs,
+                }
+            );
+        } else {
+            result.unwrap();
+        }
+    }
+}
+
+/// A ledger info that ends the epoch of the given block, as a snapshot a network restarts from
+/// would, along with its waypoint and a proof that leads to it from the genesis waypoint, in which
+/// the signer signed it.
+fn epoch_ending_snapshot(
+    signer: &ValidatorSigner,
+    block: &Block<Round>,
+) -> (LedgerInfo, Waypoint, EpochChangeProof) {
+    let genesis_li = test_utils::validator_signers_to_ledger_info(&[signer]);
+    let next_epoch_state = EpochState {
+        epoch: 2,
+        verifier: genesis_li.next_epoch_state().unwrap().verifier.clone(),
+    };
+    let li = LedgerInfo::new(
+        BlockInfo::new(
+            1,
+            block.round(),
+            block.id(),
+            HashValue::zero(),
+            1,
+            0,
+            Some(next_epoch_state),
+        ),
+        HashValue::zero(),
+    );
+    let waypoint = Waypoint::new_epoch_boundary(&li).unwrap();
+    let mut signatures = BTreeMap::new();
+    signatures.insert(signer.author(), signer.sign_message(li.hash()));
+    let proof = EpochChangeProof::new(
+        vec![
+            LedgerInfoWithSignatures::new(genesis_li, BTreeMap::new()),
+            LedgerInfoWithSignatures::new(li.clone(), signatures),
+        ],
+        false,
+    );
+    (li, waypoint, proof)
+}
+
+#[test]
+fn test_reset_to_waypoint() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+
+    // A snapshot that ends the current epoch
+    let (li, waypoint, snapshot_proof) = epoch_ending_snapshot(&signer, a1.block());
+
+    // The proof has to end at the waypoint and lead to it from the stored waypoint, with every
+    // ledger info signed by the validators of its epoch
+    let genesis_waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let signed_li = snapshot_proof.ledger_info_with_sigs[1].clone();
+    let unsigned_li = LedgerInfoWithSignatures::new(li.clone(), BTreeMap::new());
+    let genesis_li = proof.ledger_info_with_sigs[0].clone();
+    let invalid_proofs = vec![
+        (genesis_waypoint, snapshot_proof.clone()),
+        (waypoint, EpochChangeProof::new(vec![signed_li], false)),
+        (
+            waypoint,
+            EpochChangeProof::new(vec![genesis_li, unsigned_li], false),
+        ),
+    ];
+    for (waypoint, proof) in invalid_proofs {
+        match safety_rules.reset_to_waypoint(waypoint, &proof) {
+            Err(Error::WaypointMismatch(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+    assert_eq!(safety_rules.consensus_state().unwrap().epoch(), 1);
+
+    let recovery_id = safety_rules
+        .reset_to_waypoint(waypoint, &snapshot_proof)
+        .unwrap();
+    let state = safety_rules.consensus_state().unwrap();
+    assert_eq!(state.epoch(), 2);
+    assert_eq!(state.last_voted_round(), 0);
+    assert_eq!(state.preferred_round(), 0);
+    assert_eq!(state.waypoint(), waypoint);
+
+    // Nothing is signed until the reset is confirmed
+    let genesis = Block::<Round>::make_genesis_block_from_ledger_info(&li);
+    let genesis_qc = QuorumCert::certificate_for_genesis_from_ledger_info(&li, genesis.id());
+    let b1 = test_utils::make_proposal_with_qc(genesis.round() + 1, genesis_qc, &signer);
+    match safety_rules.construct_and_sign_vote(&b1) {
+        Err(Error::RecoveryPending(id)) if id == recovery_id => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match safety_rules.confirm_recovery(recovery_id.wrapping_add(1)) {
+        Err(Error::RecoveryRefused(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    safety_rules.confirm_recovery(recovery_id).unwrap();
+
+    // Voting resumes from the genesis of the new epoch
+    safety_rules.construct_and_sign_vote(&b1).unwrap();
+}
+
+#[test]
+fn test_recover_reset_to_waypoint() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc.clone(), &signer);
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    let before = safety_rules.consensus_state().unwrap();
+
+    let (_, waypoint, snapshot_proof) = epoch_ending_snapshot(&signer, a1.block());
+    let recovery_id = safety_rules
+        .reset_to_waypoint(waypoint, &snapshot_proof)
+        .unwrap();
+    // A second destructive operation has to wait for the first to be confirmed
+    match safety_rules.reset_to_waypoint(waypoint, &snapshot_proof) {
+        Err(Error::RecoveryRefused(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    safety_rules.recover(recovery_id).unwrap();
+    let state = safety_rules.consensus_state().unwrap();
+    assert_eq!(state.epoch(), before.epoch());
+    assert_eq!(state.last_voted_round(), before.last_voted_round());
+    assert_eq!(state.waypoint(), before.waypoint());
+    match safety_rules.confirm_recovery(recovery_id) {
+        Err(Error::RecoveryRefused(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // SafetyRules resumes once initialized against the restored waypoint, and the restored rounds
+    // refuse to vote again on the block voted on before the reset
+    match safety_rules.construct_and_sign_vote(&a1) {
+        Err(Error::MaintenanceMode) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    safety_rules.initialize(&proof).unwrap();
+    match safety_rules.construct_and_sign_vote(&a1) {
+        Err(Error::OldProposal { .. }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    let a2 = test_utils::make_proposal_with_qc(round + 2, genesis_qc, &signer);
+    safety_rules.construct_and_sign_vote(&a2).unwrap();
+}
+
+#[test]
+fn test_recovery_window() {
+    let signer = ValidatorSigner::from_int(0);
+    let config = SafetyRulesConfig {
+        recovery_window_ms: 0,
+        ..Default::default()
+    };
+    let storage = test_utils::test_storage(&signer);
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let (_, waypoint, snapshot_proof) = epoch_ending_snapshot(&signer, a1.block());
+    let recovery_id = safety_rules
+        .reset_to_waypoint(waypoint, &snapshot_proof)
+        .unwrap();
+
+    // Once the window has passed, the reset can only be recovered
+    match safety_rules.confirm_recovery(recovery_id) {
+        Err(Error::RecoveryRefused(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    safety_rules.recover(recovery_id).unwrap();
+    safety_rules.initialize(&proof).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+}
+
+#[test]
+fn test_quorum_voting_power_override() {
+    let signer = ValidatorSigner::from_int(0);
+    let other = ValidatorSigner::from_int(1);
+    let genesis_li = test_utils::validator_signers_to_ledger_info(&[&signer, &other]);
+    let waypoint = Waypoint::new_epoch_boundary(&genesis_li).unwrap();
+    let genesis = Block::<Round>::make_genesis_block_from_ledger_info(&genesis_li);
+    let genesis_qc =
+        QuorumCert::certificate_for_genesis_from_ledger_info(&genesis_li, genesis.id());
+    let proof = EpochChangeProof::new(
+        vec![LedgerInfoWithSignatures::new(genesis_li, BTreeMap::new())],
+        false,
+    );
+
+    let a1 = test_utils::make_proposal_with_qc(genesis.round() + 1, genesis_qc, &signer);
+    // Only certified by this validator, which lacks a quorum by default
+    let a2 = test_utils::make_proposal_with_parent(
+        genesis.round() + 2,
+        genesis.round() + 2,
+        &a1,
+        None,
+        &signer,
+    );
+
+    let safety_rules = |quorum_voting_power_override| {
+        let storage = PersistentSafetyStorage::initialize(
+            Box::new(InMemoryStorage::new()),
+            signer.private_key().clone(),
+            waypoint,
+        );
+        let mut config = SafetyRulesConfig::default();
+        config.quorum_voting_power_override = quorum_voting_power_override;
+        let mut safety_rules =
+            SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+        safety_rules.initialize(&proof).unwrap();
+        safety_rules.construct_and_sign_vote(&a1).unwrap();
+        safety_rules
+    };
+
+    match safety_rules(None).update(a2.block().quorum_cert()) {
+        Err(Error::InvalidQuorumCertificate(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match safety_rules(None).construct_and_sign_vote(&a2) {
+        Err(Error::InvalidQuorumCertificate(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    let mut safety_rules = safety_rules(Some(1));
+    safety_rules.update(a2.block().quorum_cert()).unwrap();
+    safety_rules.construct_and_sign_vote(&a2).unwrap();
+}
+
+#[test]
+fn test_verified_vote() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc.clone(), &signer);
+    let a2 = test_utils::make_proposal_with_qc_and_proof(
+        Round::default(),
+        round + 2,
+        Proof::new(vec![HashValue::random()], 1, vec![]),
+        genesis_qc,
+        &signer,
+    );
+
+    // Verification requires no access to SafetyRules
+    let verifier = safety_rules.vote_proposal_verifier().unwrap();
+    let handle = std::thread::spawn(move || (verifier.verify(a1), verifier.verify(a2)));
+    let (verified_a1, result_a2) = handle.join().unwrap();
+    match result_a2 {
+        Err(Error::InvalidAccumulatorExtension { .. }) => (),
+        Err(e) => panic!("Unexpected error: {:?}", e),
+        Ok(_) => panic!("Unexpected success"),
+    }
+
+    let verified_a1 = verified_a1.unwrap();
+    let vote = safety_rules
+        .construct_and_sign_verified_vote(&verified_a1)
+        .unwrap();
+    assert_eq!(
+        vote.vote_data().proposed().id(),
+        verified_a1.vote_proposal().block().id()
+    );
+    match safety_rules.construct_and_sign_verified_vote(&verified_a1) {
+        Err(Error::OldProposal { .. }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    safety_rules.enter_maintenance_mode();
+    assert_eq!(
+        safety_rules.vote_proposal_verifier().err(),
+        Some(Error::MaintenanceMode)
+    );
+}
+
+#[test]
+fn test_waypoint_reconciliation() {
+    let signer = ValidatorSigner::from_int(0);
+    let (genesis_proof, _) = suite::make_genesis::<Round>(&signer);
+    let next_epoch_proof = model_checker::make_next_epoch_proof(&signer, &genesis_proof);
+    let genesis_waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let next_waypoint =
+        Waypoint::new_epoch_boundary(next_epoch_proof.ledger_info_with_sigs[1].ledger_info())
+            .unwrap();
+
+    // Equal waypoints
+    let mut storage = test_utils::test_storage(&signer);
+    assert_eq!(
+        reconcile_waypoint(&mut storage, &genesis_waypoint, None),
+        Ok(WaypointReconciliation::Unchanged)
+    );
+
+    // A newer waypoint is only adopted along with a proof that leads to it
+    assert_eq!(
+        reconcile_waypoint(&mut storage, &next_waypoint, None),
+        Ok(WaypointReconciliation::Unproven)
+    );
+    assert_eq!(storage.waypoint().unwrap(), genesis_waypoint);
+    reconcile_waypoint(&mut storage, &next_waypoint, Some(&genesis_proof)).unwrap_err();
+    assert_eq!(storage.waypoint().unwrap(), genesis_waypoint);
+    let recovery_id =
+        match reconcile_waypoint(&mut storage, &next_waypoint, Some(&next_epoch_proof)) {
+            Ok(WaypointReconciliation::Ratcheted(id)) => id,
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    assert_eq!(storage.waypoint().unwrap(), next_waypoint);
+    let slot = storage.recovery_slot().unwrap().unwrap();
+    assert_eq!(slot.id, recovery_id);
+    assert_eq!(slot.safety_data.waypoint, genesis_waypoint);
+    assert_eq!(storage.waypoint_history().unwrap().last().unwrap().epoch, 2);
+
+    // An older waypoint is ignored
+    assert_eq!(
+        reconcile_waypoint(&mut storage, &genesis_waypoint, Some(&genesis_proof)),
+        Ok(WaypointReconciliation::Outdated)
+    );
+    assert_eq!(storage.waypoint().unwrap(), next_waypoint);
+
+    // A different waypoint at the same version is refused
+    let block_info = BlockInfo::new(1, 0, HashValue::zero(), HashValue::zero(), 1, 0, None);
+    let conflicting = Waypoint::new_any(&LedgerInfo::new(block_info, HashValue::zero()));
+    assert_eq!(conflicting.version(), next_waypoint.version());
+    match reconcile_waypoint(&mut storage, &conflicting, None) {
+        Err(Error::WaypointMismatch(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(storage.waypoint().unwrap(), next_waypoint);
+
+    // SafetyRules starts from the ratcheted waypoint, and signs once the ratchet is confirmed
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+    safety_rules.initialize(&next_epoch_proof).unwrap();
+    assert_eq!(safety_rules.consensus_state().unwrap().epoch(), 2);
+    let li = next_epoch_proof.ledger_info_with_sigs[1].ledger_info();
+    let genesis = Block::<Round>::make_genesis_block_from_ledger_info(li);
+    let genesis_qc = QuorumCert::certificate_for_genesis_from_ledger_info(li, genesis.id());
+    let a1 = test_utils::make_proposal_with_qc(genesis.round() + 1, genesis_qc, &signer);
+    match safety_rules.construct_and_sign_vote(&a1) {
+        Err(Error::RecoveryPending(id)) if id == recovery_id => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    safety_rules.confirm_recovery(recovery_id).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+}
+
+#[test]
+fn test_signing_messages() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    safety_rules.initialize(&proof).unwrap();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+
+    // The message is computed without voting
+    let message = safety_rules.vote_signing_message(&a1).unwrap();
+    assert_eq!(
+        safety_rules.consensus_state().unwrap().last_voted_round(),
+        round
+    );
+    let vote = safety_rules.construct_and_sign_vote(&a1).unwrap();
+    assert_eq!(vote.ledger_info().hash(), message.hash);
+    vote.signature()
+        .verify(&message.hash, &signer.public_key())
+        .unwrap();
+
+    let timeout = Timeout::new(epoch, round + 2);
+    let message = timeout_signing_message(&timeout);
+    let signature = safety_rules.sign_timeout(&timeout).unwrap();
+    signature
+        .verify(&message.hash, &signer.public_key())
+        .unwrap();
+
+    let qc = a1.block().quorum_cert().clone();
+    let block_data = BlockData::new_proposal(3, signer.author(), round + 3, 3, qc);
+    let message = proposal_signing_message(&block_data);
+    assert_eq!(message.lcs, lcs::to_bytes(&block_data).unwrap());
+    let block = safety_rules.sign_proposal(block_data).unwrap();
+    assert_eq!(block.id(), message.hash);
+    block
+        .signature()
+        .unwrap()
+        .verify(&message.hash, &signer.public_key())
+        .unwrap();
+}
+
+#[test]
+fn test_caller_epoch_lag() {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let config = SafetyRulesConfig {
+        max_caller_epoch_lag: Some(0),
+        ..Default::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+
+    let (genesis_proof, _) = suite::make_genesis::<Round>(&signer);
+    safety_rules.initialize(&genesis_proof).unwrap();
+    let next_epoch_proof = model_checker::make_next_epoch_proof(&signer, &genesis_proof);
+    safety_rules.initialize(&next_epoch_proof).unwrap();
+
+    // Without a heartbeat, signing is not restricted
+    safety_rules.sign_timeout(&Timeout::new(2, 1)).unwrap();
+
+    safety_rules.heartbeat(1, 5).unwrap();
+    let caller_view = safety_rules.caller_view().unwrap();
+    assert_eq!((caller_view.epoch, caller_view.round), (1, 5));
+    assert_eq!(
+        safety_rules.sign_timeout(&Timeout::new(2, 2)),
+        Err(Error::CallerEpochLag {
+            caller_epoch: 1,
+            epoch: 2,
+            max_lag: 0,
+        })
+    );
+
+    safety_rules.heartbeat(2, 1).unwrap();
+    safety_rules.sign_timeout(&Timeout::new(2, 2)).unwrap();
+}
+
+#[test]
+fn test_scenario() {
+    let mut scenario = Scenario::<Round>::new(4);
+    let signer = scenario.signers()[0].clone();
+    let storage = scenario.storage(0);
+    let mut safety_rules = SafetyRules::<Round>::new(signer.author(), storage);
+    safety_rules
+        .initialize(&scenario.epoch_change_proof())
+        .unwrap();
+
+    // A 3-chain commits its first block
+    let a1 = scenario.propose(1, &scenario.root_qc());
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    let qc1 = scenario.certify(&a1);
+    let a2 = scenario.propose(2, &qc1);
+    safety_rules.construct_and_sign_vote(&a2).unwrap();
+    let a3 = scenario.propose(3, &scenario.certify(&a2));
+    let vote = safety_rules.construct_and_sign_vote(&a3).unwrap();
+    assert_eq!(vote.ledger_info().commit_info(), qc1.certified_block());
+    let a4 = scenario.propose(4, &scenario.certify(&a3));
+    safety_rules.construct_and_sign_vote(&a4).unwrap();
+
+    let tc = scenario.timeout_certificate(5);
+    tc.verify(&safety_rules.current_epoch_state().unwrap().verifier)
+        .unwrap();
+
+    // The next epoch starts from the block ending this one
+    let proof = scenario.end_epoch(&a4);
+    safety_rules.initialize(&proof).unwrap();
+    assert_eq!(safety_rules.consensus_state().unwrap().epoch(), 2);
+    let b1 = scenario.propose(1, &scenario.root_qc());
+    assert_eq!(b1.block().epoch(), 2);
+    safety_rules.construct_and_sign_vote(&b1).unwrap();
+}
+
+#[test]
+fn test_epoch_summary() {
+    let mut scenario = Scenario::<Round>::new(4);
+    let signer = scenario.signers()[0].clone();
+    let temppath = TempPath::new();
+    temppath.create_as_file().unwrap();
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(OnDiskStorage::new(temppath.path().to_path_buf())),
+        signer.private_key().clone(),
+        scenario.waypoint(),
+    );
+    let audit_log = AuditLogConfig::default();
+    let config = SafetyRulesConfig {
+        audit_log: Some(audit_log.clone()),
+        ..Default::default()
+    };
+    let mut safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+    safety_rules
+        .initialize(&scenario.epoch_change_proof())
+        .unwrap();
+
+    let a1 = scenario.propose(1, &scenario.root_qc());
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap_err();
+    let a2 = scenario.propose(2, &scenario.certify(&a1));
+    safety_rules.construct_and_sign_vote(&a2).unwrap();
+    let proof = scenario.end_epoch(&a2);
+    safety_rules.initialize(&proof).unwrap();
+
+    // The summary of the first epoch is written to the audit log as the second one starts
+    let storage =
+        PersistentSafetyStorage::new(Box::new(OnDiskStorage::new(temppath.path().to_path_buf())));
+    let summaries: Vec<_> = storage
+        .audit_batches(&audit_log)
+        .unwrap()
+        .into_iter()
+        .flat_map(|batch| batch.epoch_summaries)
+        .collect();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].epoch, 1);
+    assert_eq!(summaries[0].signatures.votes, 2);
+    assert_eq!(summaries[0].rejections["OldProposal"], 1);
+    assert_eq!(summaries[0].max_round, 2);
+}
@@ -1,8 +1,20 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, tests::suite, SafetyRulesManager, TSafetyRules};
-use consensus_types::common::{Payload, Round};
+use crate::{
+    persistent_safety_storage::PersistentSafetyStorage,
+    serializer::{SafetyRulesInput, SafetyRulesRequest, SafetyRulesResponse, SerializerService},
+    test_utils,
+    tests::suite,
+    Error, RequestId, SafetyRules, SafetyRulesManager, TSafetyRules,
+};
+use consensus_types::{
+    common::{Payload, Round},
+    timeout::Timeout,
+};
+use libra_config::config::{AuditLogConfig, OnDiskStorageConfig, SafetyRulesConfig, SecureBackend};
+use libra_secure_storage::OnDiskStorage;
+use libra_temppath::TempPath;
 use libra_types::validator_signer::ValidatorSigner;
 
 #[test]
@@ -17,3 +29,135 @@ fn safety_rules<T: Payload>() -> (Box<dyn TSafetyRules<T>>, ValidatorSigner) {
     let safety_rules = safety_rules_manager.client();
     (safety_rules, signer)
 }
+
+#[test]
+fn test_request_id() {
+    let signer = ValidatorSigner::from_int(0);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let temppath = TempPath::new();
+    temppath.create_as_file().unwrap();
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(OnDiskStorage::new(temppath.path().to_path_buf())),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    let audit_log = AuditLogConfig {
+        batch_size: 1,
+        ..Default::default()
+    };
+    let config = SafetyRulesConfig {
+        audit_log: Some(audit_log.clone()),
+        ..Default::default()
+    };
+    let safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+    let mut service = SerializerService::new(safety_rules);
+
+    let (proof, _genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let id = RequestId::random();
+    let request = SafetyRulesRequest {
+        id,
+        input: SafetyRulesInput::Initialize(Box::new(proof)),
+    };
+    let response = service
+        .handle_message(lcs::to_bytes(&request).unwrap())
+        .unwrap();
+    let response: SafetyRulesResponse = lcs::from_bytes(&response).unwrap();
+    assert_eq!(response.id, id);
+    let output: Result<(), Error> = lcs::from_bytes(&response.output).unwrap();
+    output.unwrap();
+
+    // The same id is found in the signer's audit trail
+    let storage =
+        PersistentSafetyStorage::new(Box::new(OnDiskStorage::new(temppath.path().to_path_buf())));
+    let batches = storage.audit_batches(&audit_log).unwrap();
+    assert_eq!(batches[0].entries[0].request_id, id);
+}
+
+#[test]
+fn test_replay() {
+    let signer = ValidatorSigner::from_int(0);
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let temppath = TempPath::new();
+    temppath.create_as_file().unwrap();
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(OnDiskStorage::new(temppath.path().to_path_buf())),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    let audit_log = AuditLogConfig {
+        batch_size: 1,
+        ..Default::default()
+    };
+    let config = SafetyRulesConfig {
+        audit_log: Some(audit_log.clone()),
+        backend: SecureBackend::OnDiskStorage(OnDiskStorageConfig {
+            path: temppath.path().to_path_buf(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let safety_rules = SafetyRules::<Round>::new_with_config(signer.author(), storage, &config);
+    let mut service = SerializerService::new(safety_rules);
+
+    let (proof, _genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let inputs = vec![
+        SafetyRulesInput::Initialize(Box::new(proof)),
+        SafetyRulesInput::SignTimeout(Box::new(Timeout::new(1, 1))),
+        // Rejected, the timeout is not beyond the preferred round
+        SafetyRulesInput::SignTimeout(Box::new(Timeout::new(1, 0))),
+    ];
+    for input in inputs {
+        let request = SafetyRulesRequest {
+            id: RequestId::random(),
+            input,
+        };
+        service
+            .handle_message(lcs::to_bytes(&request).unwrap())
+            .unwrap();
+    }
+
+    let mut batches = crate::export_audit_log(&config).unwrap();
+    assert_eq!(batches.len(), 3);
+    let report =
+        crate::replay_audit_log::<Round>(&config, signer.author(), None, &batches).unwrap();
+    assert_eq!(report.replayed, 3);
+    assert!(report.divergences.is_empty());
+
+    // A timeout recorded as signed that the replay rejects diverges in its decision
+    let signed = batches[1].entries[0].response.clone();
+    let rejected = &mut batches[2].entries[0];
+    let request_id = rejected.request_id;
+    rejected.response = signed;
+    let report =
+        crate::replay_audit_log::<Round>(&config, signer.author(), None, &batches).unwrap();
+    assert_eq!(report.divergences.len(), 1);
+    let divergence = &report.divergences[0];
+    assert_eq!(divergence.request_id, request_id);
+    assert_eq!(divergence.request, "sign_timeout");
+    assert!(divergence.decision_diverged());
+    assert!(divergence.replayed.is_err());
+
+    // Storage held by a running instance is reported rather than aborting the tools
+    let _running = OnDiskStorage::new_locked(temppath.path().to_path_buf()).unwrap();
+    let mut locked_config = config.clone();
+    if let SecureBackend::OnDiskStorage(on_disk) = &mut locked_config.backend {
+        on_disk.lock = true;
+    }
+    assert!(crate::export_audit_log(&locked_config).is_err());
+    assert!(
+        crate::replay_audit_log::<Round>(&locked_config, signer.author(), None, &batches).is_err()
+    );
+}
+
+#[test]
+fn test_input_variant_indices() {
+    // LCS encodes the variant index as a ULEB128 prefix, the original operations keep theirs
+    let index = |input: SafetyRulesInput<Round>| lcs::to_bytes(&input).unwrap()[0];
+    assert_eq!(index(SafetyRulesInput::ConsensusState), 0);
+    assert_eq!(
+        index(SafetyRulesInput::SignTimeout(Box::new(Timeout::new(1, 1)))),
+        5
+    );
+    assert_eq!(index(SafetyRulesInput::CommitStats), 7);
+    assert_eq!(index(SafetyRulesInput::Heartbeat(1, 1)), 11);
+}
@@ -0,0 +1,96 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    simulated_network::{NetworkFaults, SimulatedNetwork},
+    test_utils,
+    tests::suite,
+    SafetyRules, TSafetyRules,
+};
+use consensus_types::{
+    common::{Payload, Round},
+    vote_proposal::VoteProposal,
+};
+use libra_types::{epoch_change::EpochChangeProof, validator_signer::ValidatorSigner};
+
+#[test]
+fn test() {
+    suite::run_test_suite(safety_rules::<Round>, safety_rules::<Vec<u8>>);
+}
+
+fn safety_rules<T: Payload>() -> (Box<dyn TSafetyRules<T>>, ValidatorSigner) {
+    let signer = ValidatorSigner::from_int(0);
+    let storage = test_utils::test_storage(&signer);
+    let safety_rules = SafetyRules::new(signer.author(), storage);
+    let network = SimulatedNetwork::new(safety_rules, 0, NetworkFaults::default());
+    (Box::new(network.client()), signer)
+}
+
+/// Votes on each proposal in turn over a faulty network, retrying a bounded number of times, and
+/// returns the outcome of every attempt.
+fn vote_over_faulty_network(
+    signer: &ValidatorSigner,
+    proof: &EpochChangeProof,
+    proposals: &[VoteProposal<Round>],
+    seed: u64,
+) -> Vec<Result<Round, String>> {
+    let faults = NetworkFaults {
+        drop_percent: 20,
+        duplicate_percent: 20,
+        reorder_percent: 20,
+    };
+    let safety_rules = SafetyRules::new(signer.author(), test_utils::test_storage(signer));
+    let network = SimulatedNetwork::new(safety_rules, seed, faults);
+    let mut client = network.client();
+
+    let mut outcomes = Vec::new();
+    for _ in 0..10 {
+        let result = client.initialize(proof);
+        outcomes.push(result.clone().map(|_| 0).map_err(|e| e.to_string()));
+        if result.is_ok() {
+            break;
+        }
+    }
+    for proposal in proposals {
+        for _ in 0..3 {
+            let result = client.construct_and_sign_vote(proposal);
+            let outcome = result
+                .map(|vote| vote.vote_data().proposed().round())
+                .map_err(|e| e.to_string());
+            let voted = outcome.is_ok();
+            outcomes.push(outcome);
+            if voted {
+                break;
+            }
+        }
+    }
+    outcomes
+}
+
+#[test]
+fn test_faulty_network() {
+    let signer = ValidatorSigner::from_int(0);
+    let (proof, genesis_qc) = suite::make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let mut proposals = vec![test_utils::make_proposal_with_qc(
+        round + 1,
+        genesis_qc,
+        &signer,
+    )];
+    for i in 2..10 {
+        let parent = proposals.last().unwrap();
+        let proposal = test_utils::make_proposal_with_parent(i, round + i, parent, None, &signer);
+        proposals.push(proposal);
+    }
+
+    let outcomes = vote_over_faulty_network(&signer, &proof, &proposals, 7);
+    // The same seed reproduces the same faults and hence the same outcomes
+    assert_eq!(
+        outcomes,
+        vote_over_faulty_network(&signer, &proof, &proposals, 7)
+    );
+    // Duplicated, reordered or lost messages never produce two votes for the same round
+    let mut voted_rounds: Vec<_> = outcomes.into_iter().filter_map(Result::ok).collect();
+    voted_rounds.retain(|round| *round != 0);
+    assert!(voted_rounds.windows(2).all(|rounds| rounds[0] < rounds[1]));
+}
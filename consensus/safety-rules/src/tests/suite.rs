@@ -1,12 +1,15 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, Error, TSafetyRules};
+use crate::{test_utils, tests::model_checker, CommitStats, Error, SignatureCount, TSafetyRules};
 use consensus_types::{
     block::Block,
+    block_data::BlockData,
     common::{Payload, Round},
     quorum_cert::QuorumCert,
+    sync_info::SyncInfo,
     timeout::Timeout,
+    timeout_certificate::TimeoutCertificate,
     vote_proposal::VoteProposal,
 };
 use libra_crypto::hash::{CryptoHash, HashValue};
@@ -16,13 +19,14 @@ use libra_types::{
     on_chain_config::ValidatorSet,
     validator_info::ValidatorInfo,
     validator_signer::ValidatorSigner,
+    waypoint::Waypoint,
 };
 use rand::Rng;
 use std::collections::BTreeMap;
 
 type Proof = test_utils::Proof;
 
-fn make_genesis<T: Payload>(signer: &ValidatorSigner) -> (EpochChangeProof, QuorumCert) {
+pub fn make_genesis<T: Payload>(signer: &ValidatorSigner) -> (EpochChangeProof, QuorumCert) {
     let validator_info =
         ValidatorInfo::new_with_test_network_keys(signer.author(), signer.public_key(), 1);
     let validator_set = ValidatorSet::new(vec![validator_info]);
@@ -58,13 +62,65 @@ type ByteArrayCallback = fn() -> (Box<dyn TSafetyRules<Vec<u8>>>, ValidatorSigne
 pub fn run_test_suite(round_func: RoundCallback, byte_func: ByteArrayCallback) {
     test_bad_execution_output(round_func);
     test_commit_rule_consecutive_rounds(round_func);
+    test_current_epoch_state(round_func);
     test_end_to_end(byte_func);
+    test_heartbeat(round_func);
     test_initialize(round_func);
+    test_initialize_from_trusted_state(round_func);
     test_preferred_block_rule(round_func);
+    test_sign_proposal(round_func);
     test_sign_timeout(round_func);
+    test_sign_uninitialized(round_func);
+    test_signature_counts(round_func);
+    test_update_sync_info(round_func);
     test_voting(round_func);
     test_voting_potential_commit_id(round_func);
     test_voting_bad_epoch(round_func);
+    test_voting_unrelated_parent(round_func);
+    test_vote_deadline(round_func);
+    test_waypoint_history(round_func);
+}
+
+/// Verify that the epoch state follows the epochs SafetyRules is initialized into.
+fn test_current_epoch_state(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+    assert_eq!(
+        safety_rules.current_epoch_state(),
+        Err(Error::NotInitialized)
+    );
+
+    let (genesis_proof, _) = make_genesis::<Round>(&signer);
+    safety_rules.initialize(&genesis_proof).unwrap();
+    let epoch_state = safety_rules.current_epoch_state().unwrap();
+    assert_eq!(epoch_state.epoch, 1);
+    assert_eq!(
+        epoch_state.verifier.get_public_key(&signer.author()),
+        Some(signer.public_key())
+    );
+    assert_eq!(epoch_state.verifier.len(), 1);
+
+    let next_epoch_proof = model_checker::make_next_epoch_proof(&signer, &genesis_proof);
+    safety_rules.initialize(&next_epoch_proof).unwrap();
+    let epoch_state = safety_rules.current_epoch_state().unwrap();
+    assert_eq!(
+        epoch_state.epoch,
+        safety_rules.consensus_state().unwrap().epoch()
+    );
+    assert_eq!(epoch_state.epoch, 2);
+}
+
+/// Heartbeats are accepted in any state, signing is unaffected as no maximum lag is configured.
+fn test_heartbeat(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+    safety_rules.heartbeat(1, 0).unwrap();
+
+    let (proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+    safety_rules.heartbeat(0, round).unwrap();
+    safety_rules
+        .sign_timeout(&Timeout::new(1, round + 1))
+        .unwrap();
 }
 
 fn test_bad_execution_output(func: RoundCallback) {
@@ -143,6 +199,16 @@ fn test_commit_rule_consecutive_rounds(func: RoundCallback) {
     safety_rules.construct_and_sign_vote(&a2).unwrap();
     safety_rules.construct_and_sign_vote(&a3).unwrap();
     safety_rules.construct_and_sign_vote(&a4).unwrap();
+
+    assert_eq!(
+        safety_rules.commit_stats().unwrap(),
+        CommitStats {
+            commits: 1,
+            parent_gaps: 3,
+            child_gaps: 1,
+            both_gaps: 1,
+        }
+    );
 }
 
 fn test_end_to_end(func: ByteArrayCallback) {
@@ -206,6 +272,41 @@ fn test_initialize(func: RoundCallback) {
     };
 }
 
+/// Verify that initializing from a trusted state requires the proof to end in that state, whether
+/// it is given as a waypoint or as an epoch state.
+fn test_initialize_from_trusted_state(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (genesis_proof, _genesis_qc) = make_genesis::<Round>(&signer);
+    let next_epoch_proof = model_checker::make_next_epoch_proof(&signer, &genesis_proof);
+    let genesis_li = genesis_proof.ledger_info_with_sigs[0].ledger_info();
+    let next_epoch_li = next_epoch_proof.ledger_info_with_sigs[1].ledger_info();
+
+    let genesis_waypoint = Waypoint::new_epoch_boundary(genesis_li).unwrap();
+    match safety_rules.initialize_from_trusted_state(genesis_waypoint.into(), &next_epoch_proof) {
+        Err(Error::WaypointMismatch(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    };
+    safety_rules
+        .initialize_from_trusted_state(genesis_waypoint.into(), &genesis_proof)
+        .unwrap();
+    assert_eq!(safety_rules.consensus_state().unwrap().epoch(), 1);
+
+    let trusted_epoch = next_epoch_li.next_epoch_state().cloned().unwrap();
+    safety_rules
+        .initialize_from_trusted_state(
+            TrustedCheckpoint::EpochState(trusted_epoch),
+            &next_epoch_proof,
+        )
+        .unwrap();
+    let state = safety_rules.consensus_state().unwrap();
+    assert_eq!(state.epoch(), 2);
+    assert_eq!(
+        state.waypoint(),
+        Waypoint::new_epoch_boundary(next_epoch_li).unwrap()
+    );
+}
+
 fn test_preferred_block_rule(func: RoundCallback) {
     // Preferred block is the highest 2-chain head.
     //
@@ -275,6 +376,41 @@ fn test_preferred_block_rule(func: RoundCallback) {
     );
 }
 
+/// Verify that proposals can only be signed for the current epoch and at increasing rounds, and
+/// that a proposal at the highest proposed round may be re-signed only if it is identical.
+fn test_sign_proposal(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+
+    let p1 = BlockData::new_proposal(1, signer.author(), round + 1, 1, genesis_qc.clone());
+    let p1_prime = BlockData::new_proposal(2, signer.author(), round + 1, 1, genesis_qc.clone());
+    let p2 = BlockData::new_proposal(3, signer.author(), round + 2, 2, genesis_qc.clone());
+
+    // Signing the same proposal twice produces the same block
+    let block = safety_rules.sign_proposal(p1.clone()).unwrap();
+    assert_eq!(block, safety_rules.sign_proposal(p1).unwrap());
+
+    // A different proposal at the same round is equivocation
+    assert_eq!(
+        safety_rules.sign_proposal(p1_prime).unwrap_err(),
+        Error::EquivocatingProposal(round + 1)
+    );
+
+    // Rounds only move forward
+    safety_rules.sign_proposal(p2).unwrap();
+    let old = BlockData::new_proposal(4, signer.author(), round + 1, 3, genesis_qc);
+    assert_eq!(
+        safety_rules.sign_proposal(old).unwrap_err(),
+        Error::OldProposedRound {
+            highest_proposed_round: round + 2,
+            proposal_round: round + 1,
+        }
+    );
+}
+
 /// Verify first that we can successfully sign a timeout on the correct conditions, then ensure
 /// that poorly set last_voted_rounds both historical and in the future fail as well as
 /// synchronization issues on preferred round are correct. Effectivelly ensure that equivocation is
@@ -325,6 +461,117 @@ fn test_sign_timeout(func: RoundCallback) {
     assert_eq!(actual_err, expected_err);
 }
 
+/// Verify that every signing operation is refused alike until a verifier has been established.
+fn test_sign_uninitialized(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (_proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc.clone(), &signer);
+    let p1 = BlockData::new_proposal(1, signer.author(), round + 1, 1, genesis_qc);
+
+    assert_eq!(
+        safety_rules.construct_and_sign_vote(&a1).unwrap_err(),
+        Error::NotInitialized
+    );
+    assert_eq!(
+        safety_rules.sign_proposal(p1).unwrap_err(),
+        Error::NotInitialized
+    );
+    assert_eq!(
+        safety_rules
+            .sign_timeout(&Timeout::new(epoch, round + 1))
+            .unwrap_err(),
+        Error::NotInitialized
+    );
+    assert_eq!(
+        safety_rules.consensus_state().unwrap().last_voted_round(),
+        0
+    );
+}
+
+/// Verify that every signature issued is counted, while refused requests are not.
+fn test_signature_counts(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+    safety_rules.initialize(&proof).unwrap();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc.clone(), &signer);
+    let p2 = BlockData::new_proposal(2, signer.author(), round + 2, 2, genesis_qc);
+    safety_rules.construct_and_sign_vote(&a1).unwrap();
+    safety_rules.construct_and_sign_vote(&a1).unwrap_err();
+    safety_rules.sign_proposal(p2.clone()).unwrap();
+    safety_rules.sign_proposal(p2).unwrap();
+    safety_rules
+        .sign_timeout(&Timeout::new(epoch, round + 2))
+        .unwrap();
+
+    let signature_counts = safety_rules.consensus_state().unwrap().signature_counts();
+    let expected = SignatureCount {
+        proposals: 2,
+        timeouts: 1,
+        votes: 1,
+    };
+    assert_eq!(signature_counts.epoch, epoch);
+    assert_eq!(signature_counts.epoch_count, expected);
+    assert_eq!(signature_counts.lifetime_count, expected);
+}
+
+/// Verify that a SyncInfo advances the preferred round from its quorum certificates and the last
+/// voted round from its timeout certificate, and that an invalid certificate leaves the state
+/// untouched.
+fn test_update_sync_info(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    let epoch = genesis_qc.certified_block().epoch();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let a2 = make_proposal_with_parent(round + 2, &a1, None, &signer);
+    let a3 = make_proposal_with_parent(round + 3, &a2, Some(&a1), &signer);
+    let a4 = make_proposal_with_parent(round + 7, &a3, Some(&a2), &signer);
+
+    safety_rules.initialize(&proof).unwrap();
+
+    let timeout = Timeout::new(epoch, round + 5);
+    let mut tc = TimeoutCertificate::new(timeout.clone());
+    tc.add_signature(signer.author(), timeout.sign(&signer));
+    let qc = a3.block().quorum_cert().clone();
+    let sync_info = SyncInfo::new(qc.clone(), qc, Some(tc));
+
+    safety_rules.update_sync_info(&sync_info).unwrap();
+    let state = safety_rules.consensus_state().unwrap();
+    assert_eq!(state.preferred_round(), a1.block().round());
+    assert_eq!(state.last_voted_round(), round + 5);
+
+    // Nothing at or below the timeout certificate can be voted on anymore
+    assert_eq!(
+        safety_rules.construct_and_sign_vote(&a3),
+        Err(Error::OldProposal {
+            last_voted_round: round + 5,
+            proposal_round: round + 3,
+        })
+    );
+    safety_rules.construct_and_sign_vote(&a4).unwrap();
+    let state = safety_rules.consensus_state().unwrap();
+
+    // A timeout certificate signed by an unknown author invalidates the entire SyncInfo
+    let bad_signer = ValidatorSigner::from_int(1);
+    let bad_timeout = Timeout::new(epoch, round + 9);
+    let mut bad_tc = TimeoutCertificate::new(bad_timeout.clone());
+    bad_tc.add_signature(bad_signer.author(), bad_timeout.sign(&bad_signer));
+    let qc = a4.block().quorum_cert().clone();
+    let bad_sync_info = SyncInfo::new(qc.clone(), qc, Some(bad_tc));
+
+    safety_rules.update_sync_info(&bad_sync_info).unwrap_err();
+    assert_eq!(safety_rules.consensus_state().unwrap(), state);
+}
+
 fn test_voting(func: RoundCallback) {
     // build a tree of the following form:
     //             _____    __________
@@ -435,6 +682,32 @@ fn test_voting_bad_epoch(func: RoundCallback) {
     );
 }
 
+fn test_voting_unrelated_parent(func: RoundCallback) {
+    // A proposal must be beyond the block its QC certifies
+    // genesis--a1--a2, b2 carries the QC of a2, which certifies a1, at the round of a1
+    let (mut safety_rules, signer) = func();
+
+    let (proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let a2 = make_proposal_with_parent(round + 2, &a1, None, &signer);
+    let b2 =
+        test_utils::make_proposal_with_qc(round + 1, a2.block().quorum_cert().clone(), &signer);
+    safety_rules.initialize(&proof).unwrap();
+
+    assert_eq!(
+        safety_rules.construct_and_sign_vote(&b2),
+        Err(Error::InvalidParent {
+            epoch: 1,
+            parent_epoch: 1,
+            parent_round: round + 1,
+            round: round + 1,
+        })
+    );
+    safety_rules.construct_and_sign_vote(&a2).unwrap();
+}
+
 fn test_voting_potential_commit_id(func: RoundCallback) {
     // Test the potential ledger info that we're going to use in case of voting
     // build a tree of the following form:
@@ -485,3 +758,53 @@ fn test_voting_potential_commit_id(func: RoundCallback) {
         a3.block().id(),
     );
 }
+
+/// Verify that a vote whose deadline has passed is refused without advancing the last voted round.
+fn test_vote_deadline(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (proof, genesis_qc) = make_genesis::<Round>(&signer);
+    let round = genesis_qc.certified_block().round();
+    safety_rules.initialize(&proof).unwrap();
+
+    let a1 = test_utils::make_proposal_with_qc(round + 1, genesis_qc, &signer);
+    let error = safety_rules
+        .construct_and_sign_vote_with_deadline(&a1, Some(0))
+        .unwrap_err();
+    assert_eq!(error, Error::VoteDeadlineExceeded { deadline_ms: 0 });
+    assert!(error.is_retriable());
+    assert_eq!(
+        safety_rules.consensus_state().unwrap().last_voted_round(),
+        0
+    );
+
+    safety_rules
+        .construct_and_sign_vote_with_deadline(&a1, Some(u64::max_value()))
+        .unwrap();
+    assert_eq!(
+        safety_rules.consensus_state().unwrap().last_voted_round(),
+        a1.block().round()
+    );
+}
+
+/// Verify that every epoch SafetyRules is initialized into is appended once to the waypoint
+/// history, which ends with the current waypoint.
+fn test_waypoint_history(func: RoundCallback) {
+    let (mut safety_rules, signer) = func();
+
+    let (genesis_proof, _) = make_genesis::<Round>(&signer);
+    safety_rules.initialize(&genesis_proof).unwrap();
+    safety_rules.initialize(&genesis_proof).unwrap();
+    let history = safety_rules.waypoint_history().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].epoch, 1);
+
+    let next_epoch_proof = model_checker::make_next_epoch_proof(&signer, &genesis_proof);
+    safety_rules.initialize(&next_epoch_proof).unwrap();
+    let history = safety_rules.waypoint_history().unwrap();
+    let state = safety_rules.consensus_state().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].epoch, state.epoch());
+    assert_eq!(history[1].waypoint, state.waypoint());
+    assert!(history[0].version() < history[1].version());
+}
@@ -0,0 +1,50 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    test_utils,
+    test_vectors::{self, TestVectors},
+    tests::suite,
+    PersistentSafetyStorage, SafetyRules, TSafetyRules,
+};
+use libra_secure_storage::InMemoryStorage;
+use std::fs;
+
+#[test]
+fn test_checked_in_vectors() {
+    let path = test_vectors::test_vectors_path();
+    if std::env::var("UPDATE_BASELINE").is_ok() {
+        let contents = serde_json::to_string_pretty(&test_vectors::generate()).unwrap();
+        fs::write(&path, contents + "\n").unwrap();
+    }
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let vectors: TestVectors = serde_json::from_str(&contents).unwrap();
+    test_vectors::verify(&vectors).unwrap();
+}
+
+/// The vectors are only worth something if SafetyRules produces the very same signatures.
+#[test]
+fn test_safety_rules_signatures() {
+    let signer = test_vectors::signer();
+    let waypoint = test_utils::validator_signers_to_waypoints(&[&signer]);
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(InMemoryStorage::new()),
+        signer.private_key().clone(),
+        waypoint,
+    );
+    let mut safety_rules = SafetyRules::<Vec<u8>>::new(signer.author(), storage);
+    let (proof, _genesis_qc) = suite::make_genesis::<Vec<u8>>(&signer);
+    safety_rules.initialize(&proof).unwrap();
+
+    let vectors = test_vectors::generate().vectors;
+    let block = safety_rules
+        .sign_proposal(test_vectors::proposal())
+        .unwrap();
+    assert_eq!(
+        hex::encode(block.signature().unwrap().to_bytes()),
+        vectors[0].signature
+    );
+    let signature = safety_rules.sign_timeout(&test_vectors::timeout()).unwrap();
+    assert_eq!(hex::encode(signature.to_bytes()), vectors[3].signature);
+}
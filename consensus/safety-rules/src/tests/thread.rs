@@ -1,9 +1,24 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{test_utils, tests::suite, SafetyRulesManager, TSafetyRules};
+use crate::{
+    persistent_safety_storage::PersistentSafetyStorage,
+    test_utils::{self, scenario::Scenario},
+    tests::suite,
+    SafetyRulesManager, TSafetyRules,
+};
 use consensus_types::common::{Payload, Round};
-use libra_types::validator_signer::ValidatorSigner;
+use libra_global_constants::LAST_VOTED_ROUND;
+use libra_secure_storage::{Fault, InMemoryStorage, Operation, ProxyStorage};
+use libra_types::{validator_signer::ValidatorSigner, waypoint::Waypoint};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
 
 #[test]
 fn test() {
@@ -17,3 +32,67 @@ fn safety_rules<T: Payload>() -> (Box<dyn TSafetyRules<T>>, ValidatorSigner) {
     let safety_rules = safety_rules_manager.client();
     (safety_rules, signer)
 }
+
+/// Readers of the consensus state must only ever see the waypoint of the epoch it reports, even
+/// while every epoch transition is interrupted half way and retried.
+#[test]
+fn test_consensus_state_during_epoch_transitions() {
+    let mut scenario = Scenario::<Round>::new(1);
+    let signer = &scenario.signers()[0];
+    let proxy = ProxyStorage::new(InMemoryStorage::new());
+    let faults = proxy.faults();
+    let storage = PersistentSafetyStorage::initialize(
+        Box::new(proxy),
+        signer.private_key().clone(),
+        scenario.waypoint(),
+    );
+    let safety_rules_manager = SafetyRulesManager::<Round>::new_thread(signer.author(), storage);
+    let safety_rules = Arc::new(Mutex::new(safety_rules_manager.client()));
+
+    let mut proofs = vec![scenario.epoch_change_proof()];
+    let mut waypoints: HashMap<u64, Waypoint> = HashMap::new();
+    waypoints.insert(scenario.epoch(), scenario.waypoint());
+    for _ in 0..10 {
+        let proposal = scenario.propose(1, &scenario.root_qc());
+        let proof = scenario.end_epoch(&proposal);
+        let ledger_info = proof.ledger_info_with_sigs.last().unwrap().ledger_info();
+        let waypoint = Waypoint::new_epoch_boundary(ledger_info).unwrap();
+        waypoints.insert(scenario.epoch(), waypoint);
+        proofs.push(proof);
+    }
+
+    let done = Arc::new(AtomicBool::new(false));
+    let readers: Vec<_> = (0..2)
+        .map(|_| {
+            let done = done.clone();
+            let safety_rules = safety_rules.clone();
+            let waypoints = waypoints.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    let state = safety_rules.lock().unwrap().consensus_state().unwrap();
+                    assert_eq!(state.waypoint(), waypoints[&state.epoch()]);
+                }
+            })
+        })
+        .collect();
+
+    safety_rules.lock().unwrap().initialize(&proofs[0]).unwrap();
+    for proof in &proofs[1..] {
+        // Fail the transition after its waypoint has been written
+        faults.inject(
+            Fault::error(Operation::Set)
+                .with_key(LAST_VOTED_ROUND)
+                .with_count(1),
+        );
+        safety_rules.lock().unwrap().initialize(proof).unwrap_err();
+        thread::yield_now();
+        safety_rules.lock().unwrap().initialize(proof).unwrap();
+    }
+    done.store(true, Ordering::SeqCst);
+
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    let state = safety_rules.lock().unwrap().consensus_state().unwrap();
+    assert_eq!(state.epoch(), scenario.epoch());
+}
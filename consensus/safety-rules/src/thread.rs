@@ -12,7 +12,7 @@ use crate::{
     remote_service::{self, RemoteService},
 };
 use consensus_types::common::{Author, Payload};
-use libra_config::utils;
+use libra_config::{config::SafetyRulesConfig, utils};
 use std::{
     marker::PhantomData,
     net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -28,13 +28,18 @@ pub struct ThreadService<T> {
 }
 
 impl<T: Payload> ThreadService<T> {
-    pub fn new(author: Author, storage: PersistentSafetyStorage) -> Self {
+    pub fn new(
+        author: Author,
+        storage: PersistentSafetyStorage,
+        config: SafetyRulesConfig,
+    ) -> Self {
         let listen_port = utils::get_available_port();
         let listen_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), listen_port);
         let server_addr = listen_addr;
 
-        let child =
-            thread::spawn(move || remote_service::execute::<T>(author, storage, listen_addr));
+        let child = thread::spawn(move || {
+            remote_service::execute::<T>(author, storage, listen_addr, config)
+        });
 
         Self {
             _child: child,
@@ -0,0 +1,46 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{ensure, Result};
+use libra_types::{epoch_state::EpochState, ledger_info::LedgerInfo, waypoint::Waypoint};
+use serde::{Deserialize, Serialize};
+
+/// The trusted state that state synchronization has reached, either as a waypoint or as the epoch
+/// state of the validator set it trusts. SafetyRules still verifies the accompanying
+/// EpochChangeProof against its own waypoint, the checkpoint only has to agree with the epoch
+/// change ledger info the proof ends in.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum TrustedCheckpoint {
+    EpochState(EpochState),
+    Waypoint(Waypoint),
+}
+
+impl TrustedCheckpoint {
+    /// Ensures the epoch change ledger info is the one described by this checkpoint.
+    pub fn verify(&self, ledger_info: &LedgerInfo) -> Result<()> {
+        match self {
+            TrustedCheckpoint::EpochState(epoch_state) => {
+                ensure!(
+                    ledger_info.next_epoch_state() == Some(epoch_state),
+                    "Trusted epoch state {} does not match the ledger info at version {}",
+                    epoch_state,
+                    ledger_info.version(),
+                );
+                Ok(())
+            }
+            TrustedCheckpoint::Waypoint(waypoint) => waypoint.verify(ledger_info),
+        }
+    }
+}
+
+impl From<EpochState> for TrustedCheckpoint {
+    fn from(epoch_state: EpochState) -> Self {
+        TrustedCheckpoint::EpochState(epoch_state)
+    }
+}
+
+impl From<Waypoint> for TrustedCheckpoint {
+    fn from(waypoint: Waypoint) -> Self {
+        TrustedCheckpoint::Waypoint(waypoint)
+    }
+}
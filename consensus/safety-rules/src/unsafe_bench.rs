@@ -0,0 +1,62 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lets benchmarks measure the cryptographic cost of SafetyRules without the cost of its storage.
+//! SafetyRules run against this storage forgets every vote it signs and will sign again in any
+//! round it has voted in, so it must never back a validator. The module only exists with the
+//! unsafe_bench feature and the safety-rules daemon refuses to start when built with it.
+//! PersistentSafetyStorage::unsafe_bench provisions a storage of this kind.
+
+use libra_secure_storage::{
+    CryptoKVStorage, Error, GetResponse, InMemoryStorage, KVStorage, Value,
+};
+
+/// A storage that keeps the first value written to each key and drops every later write, so that
+/// SafetyRules can be provisioned but none of its updates are persisted.
+#[derive(Default)]
+pub struct NoopStorage {
+    provisioned: InMemoryStorage,
+}
+
+impl NoopStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KVStorage for NoopStorage {
+    fn available(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<GetResponse, Error> {
+        self.provisioned.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        match self.provisioned.get(key) {
+            Err(Error::KeyNotSet(_)) => self.provisioned.set(key, value),
+            _ => Ok(()),
+        }
+    }
+
+    fn reset_and_clear(&mut self) -> Result<(), Error> {
+        self.provisioned.reset_and_clear()
+    }
+}
+
+impl CryptoKVStorage for NoopStorage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_are_dropped() {
+        let mut storage = NoopStorage::new();
+        storage.set("round", Value::U64(1)).unwrap();
+        storage.set("round", Value::U64(2)).unwrap();
+        assert_eq!(storage.get("round").unwrap().value, Value::U64(1));
+        assert!(storage.get("epoch").is_err());
+    }
+}
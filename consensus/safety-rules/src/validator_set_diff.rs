@@ -0,0 +1,159 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Most epoch changes add or remove a handful of validators, yet the next epoch's verifier is
+//! carried in full by the epoch ending ledger info. A ValidatorSetDiff carries only the changes to
+//! the previous epoch's validator set, and is applied to the verifier at hand to construct the next
+//! one. The result is only accepted if it hashes to the epoch state committed to by the epoch
+//! ending ledger info, so a diff can never introduce a validator set that was not agreed upon.
+
+use crate::Error;
+use libra_crypto::HashValue;
+use libra_types::{
+    account_address::AccountAddress,
+    epoch_state::EpochState,
+    validator_verifier::{ValidatorConsensusInfo, ValidatorVerifier},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ValidatorSetDiff {
+    /// The epoch of the validator set after the change
+    pub epoch: u64,
+    /// Validators that are no longer part of the set
+    pub removed: Vec<AccountAddress>,
+    /// Validators that joined the set or whose key or voting power changed
+    pub upserted: Vec<(AccountAddress, ValidatorConsensusInfo)>,
+    pub quorum_voting_power: u64,
+}
+
+impl ValidatorSetDiff {
+    /// The changes that lead from the previous epoch state to the next.
+    pub fn new(previous: &EpochState, next: &EpochState) -> Self {
+        let previous_validators = validators(&previous.verifier);
+        let next_validators = validators(&next.verifier);
+        let removed = previous_validators
+            .keys()
+            .filter(|author| !next_validators.contains_key(author))
+            .copied()
+            .collect();
+        let upserted = next_validators
+            .into_iter()
+            .filter(|(author, info)| previous_validators.get(author) != Some(info))
+            .collect();
+        Self {
+            epoch: next.epoch,
+            removed,
+            upserted,
+            quorum_voting_power: next.verifier.quorum_voting_power(),
+        }
+    }
+
+    /// Constructs the next epoch state from the previous one, which must hash to the expected
+    /// epoch state hash, see epoch_state_hash.
+    pub fn apply(
+        &self,
+        previous: &EpochState,
+        expected_hash: HashValue,
+    ) -> Result<EpochState, Error> {
+        if self.epoch <= previous.epoch {
+            return Err(Error::IncorrectEpoch(self.epoch, previous.epoch + 1));
+        }
+        let mut validators = validators(&previous.verifier);
+        for author in &self.removed {
+            if validators.remove(author).is_none() {
+                return Err(Error::InvalidValidatorSetDiff(format!(
+                    "Removed validator {} is not part of epoch {}",
+                    author, previous.epoch
+                )));
+            }
+        }
+        validators.extend(self.upserted.iter().cloned());
+        let verifier =
+            ValidatorVerifier::new_with_quorum_voting_power(validators, self.quorum_voting_power)
+                .map_err(|e| Error::InvalidValidatorSetDiff(e.to_string()))?;
+
+        let next = EpochState {
+            epoch: self.epoch,
+            verifier,
+        };
+        let hash = epoch_state_hash(&next)?;
+        if hash != expected_hash {
+            return Err(Error::InvalidValidatorSetDiff(format!(
+                "The diff leads to an epoch state with hash {}, expected {}",
+                hash, expected_hash
+            )));
+        }
+        Ok(next)
+    }
+}
+
+/// The hash of the LCS serialization of the epoch state, which identifies the validator set of an
+/// epoch in full.
+pub fn epoch_state_hash(epoch_state: &EpochState) -> Result<HashValue, Error> {
+    Ok(HashValue::from_sha3_256(&lcs::to_bytes(epoch_state)?))
+}
+
+fn validators(verifier: &ValidatorVerifier) -> BTreeMap<AccountAddress, ValidatorConsensusInfo> {
+    verifier
+        .get_ordered_account_addresses_iter()
+        .filter_map(|author| {
+            let public_key = verifier.get_public_key(&author)?;
+            let voting_power = verifier.get_voting_power(&author)?;
+            Some((
+                author,
+                ValidatorConsensusInfo::new(public_key, voting_power),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libra_types::validator_signer::ValidatorSigner;
+
+    fn epoch_state(epoch: u64, signers: &[ValidatorSigner]) -> EpochState {
+        let validators = signers
+            .iter()
+            .map(|signer| {
+                (
+                    signer.author(),
+                    ValidatorConsensusInfo::new(signer.public_key(), 1),
+                )
+            })
+            .collect();
+        EpochState {
+            epoch,
+            verifier: ValidatorVerifier::new(validators),
+        }
+    }
+
+    #[test]
+    fn test_validator_set_diff() {
+        let signers: Vec<_> = (0..5).map(ValidatorSigner::from_int).collect();
+        let previous = epoch_state(1, &signers[..4]);
+        // One validator leaves and another joins
+        let next = epoch_state(2, &signers[1..]);
+        let next_hash = epoch_state_hash(&next).unwrap();
+
+        let diff = ValidatorSetDiff::new(&previous, &next);
+        assert_eq!(diff.removed, vec![signers[0].author()]);
+        assert_eq!(diff.upserted.len(), 1);
+        assert_eq!(diff.apply(&previous, next_hash).unwrap(), next);
+
+        // The diff must lead to the committed epoch state
+        let mut forged = diff.clone();
+        forged.quorum_voting_power -= 1;
+        assert!(matches!(
+            forged.apply(&previous, next_hash),
+            Err(Error::InvalidValidatorSetDiff(_))
+        ));
+
+        // And apply to the epoch state it was computed from
+        let other = epoch_state(1, &signers[..3]);
+        diff.apply(&other, next_hash).unwrap_err();
+        diff.apply(&next, next_hash).unwrap_err();
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use consensus_types::quorum_cert::QuorumCert;
+use libra_config::config::SignatureVerification;
+use libra_crypto::hash::CryptoHash;
+use libra_types::{
+    ledger_info::LedgerInfoWithSignatures,
+    validator_verifier::{ValidatorVerifier, VerifyError},
+};
+use std::sync::Arc;
+
+/// Verifies that the signatures on a ledger info form a valid quorum of the validator set. This
+/// is the dominant cost of verifying a QC, so the scheme is pluggable: besides checking every
+/// signature in turn, signatures may be verified as a batch or, with other signature schemes,
+/// as a single aggregate.
+pub trait VerificationBackend: Send + Sync {
+    /// Identifies the backend, e.g., in benchmarks
+    fn name(&self) -> &'static str;
+
+    fn verify_signatures(
+        &self,
+        verifier: &ValidatorVerifier,
+        ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<(), VerifyError>;
+}
+
+/// Verifies each signature in turn.
+pub struct IndividualVerification;
+
+impl VerificationBackend for IndividualVerification {
+    fn name(&self) -> &'static str {
+        "individual"
+    }
+
+    fn verify_signatures(
+        &self,
+        verifier: &ValidatorVerifier,
+        ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<(), VerifyError> {
+        ledger_info.verify_signatures(verifier)
+    }
+}
+
+/// Verifies all signatures of a ledger info as one Ed25519 batch, falling back to verifying them
+/// in turn if the batch fails, so that the same error is reported as by IndividualVerification.
+pub struct BatchVerification;
+
+impl VerificationBackend for BatchVerification {
+    fn name(&self) -> &'static str {
+        "batch"
+    }
+
+    fn verify_signatures(
+        &self,
+        verifier: &ValidatorVerifier,
+        ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<(), VerifyError> {
+        verifier.batch_verify_aggregated_signature(
+            ledger_info.ledger_info().hash(),
+            ledger_info.signatures(),
+        )
+    }
+}
+
+pub fn from_config(signature_verification: SignatureVerification) -> Arc<dyn VerificationBackend> {
+    match signature_verification {
+        SignatureVerification::Individual => Arc::new(IndividualVerification),
+        SignatureVerification::Batch => Arc::new(BatchVerification),
+    }
+}
+
+/// Verifies the QC as QuorumCert::verify does, with its signatures verified by the backend.
+pub fn verify_quorum_cert(
+    backend: &dyn VerificationBackend,
+    qc: &QuorumCert,
+    verifier: &ValidatorVerifier,
+) -> anyhow::Result<()> {
+    qc.verify_with(|ledger_info| Ok(backend.verify_signatures(verifier, ledger_info)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libra_crypto::HashValue;
+    use libra_types::{
+        block_info::BlockInfo, ledger_info::LedgerInfo, validator_signer::ValidatorSigner,
+        validator_verifier::ValidatorConsensusInfo,
+    };
+
+    fn signed_ledger_info(signers: &[ValidatorSigner]) -> LedgerInfoWithSignatures {
+        let ledger_info = LedgerInfo::new(BlockInfo::empty(), HashValue::zero());
+        let signatures = signers
+            .iter()
+            .map(|signer| (signer.author(), signer.sign_message(ledger_info.hash())))
+            .collect();
+        LedgerInfoWithSignatures::new(ledger_info, signatures)
+    }
+
+    fn assert_backends_agree(
+        verifier: &ValidatorVerifier,
+        ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<(), VerifyError> {
+        let individual = IndividualVerification.verify_signatures(verifier, ledger_info);
+        let batch = BatchVerification.verify_signatures(verifier, ledger_info);
+        assert_eq!(individual, batch);
+        individual
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let signers: Vec<_> = (0..7).map(ValidatorSigner::from_int).collect();
+        let validator_infos = signers
+            .iter()
+            .map(|signer| {
+                let info = ValidatorConsensusInfo::new(signer.public_key(), 1);
+                (signer.author(), info)
+            })
+            .collect();
+        let verifier = ValidatorVerifier::new(validator_infos);
+
+        // A quorum of valid signatures
+        let ledger_info = signed_ledger_info(&signers[..5]);
+        assert_backends_agree(&verifier, &ledger_info).unwrap();
+
+        // Too little voting power
+        let ledger_info = signed_ledger_info(&signers[..4]);
+        assert!(matches!(
+            assert_backends_agree(&verifier, &ledger_info),
+            Err(VerifyError::TooLittleVotingPower { .. })
+        ));
+
+        // One invalid signature amongst a quorum fails the batch
+        let mut ledger_info = signed_ledger_info(&signers[..6]);
+        let other = LedgerInfo::new(BlockInfo::empty(), HashValue::random());
+        ledger_info.add_signature(signers[6].author(), signers[6].sign_message(other.hash()));
+        assert_eq!(
+            assert_backends_agree(&verifier, &ledger_info),
+            Err(VerifyError::InvalidSignature)
+        );
+
+        // Signatures by an author outside of the validator set
+        let outsider = ValidatorSigner::from_int(7);
+        let mut signers = signers;
+        signers.push(outsider);
+        let ledger_info = signed_ledger_info(&signers[1..]);
+        assert_eq!(
+            assert_backends_agree(&verifier, &ledger_info),
+            Err(VerifyError::UnknownAuthor)
+        );
+    }
+}
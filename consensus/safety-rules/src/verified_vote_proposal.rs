@@ -0,0 +1,91 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    accumulator_extension,
+    error::Error,
+    verification_backend::{self, VerificationBackend},
+};
+use consensus_types::{common::Payload, vote_proposal::VoteProposal};
+use libra_crypto::HashValue;
+use libra_types::{transaction::Version, validator_verifier::ValidatorVerifier};
+use std::sync::Arc;
+
+/// Performs the stateless, CPU bound checks of a vote proposal against the validator set of an
+/// epoch: the QC signatures and the accumulator extension proof. It holds no reference to
+/// SafetyRules, so it can be cloned onto worker threads and run outside of the signer's lock.
+#[derive(Clone)]
+pub struct VoteProposalVerifier {
+    backend: Arc<dyn VerificationBackend>,
+    epoch: u64,
+    verifier: Arc<ValidatorVerifier>,
+}
+
+impl VoteProposalVerifier {
+    pub(crate) fn new(
+        epoch: u64,
+        verifier: Arc<ValidatorVerifier>,
+        backend: Arc<dyn VerificationBackend>,
+    ) -> Self {
+        Self {
+            backend,
+            epoch,
+            verifier,
+        }
+    }
+
+    pub fn verify<T: Payload>(
+        &self,
+        vote_proposal: VoteProposal<T>,
+    ) -> Result<VerifiedVoteProposal<T>, Error> {
+        let proposed_block = vote_proposal.block();
+        if proposed_block.epoch() != self.epoch {
+            return Err(Error::IncorrectEpoch(proposed_block.epoch(), self.epoch));
+        }
+
+        let qc = proposed_block.quorum_cert();
+        verification_backend::verify_quorum_cert(self.backend.as_ref(), qc, &self.verifier)
+            .map_err(|e| Error::InvalidQuorumCertificate(e.to_string()))?;
+        let (executed_state_id, version) = accumulator_extension::verify_extension(
+            vote_proposal.accumulator_extension_proof(),
+            qc.certified_block(),
+        )?;
+
+        Ok(VerifiedVoteProposal {
+            epoch: self.epoch,
+            executed_state_id,
+            version,
+            vote_proposal,
+        })
+    }
+}
+
+/// A vote proposal that has passed the checks of a VoteProposalVerifier, it can only be
+/// constructed by one. SafetyRules only applies the voting rules to it before signing and rejects
+/// it if it was verified for an epoch other than the current one.
+pub struct VerifiedVoteProposal<T> {
+    epoch: u64,
+    executed_state_id: HashValue,
+    version: Version,
+    vote_proposal: VoteProposal<T>,
+}
+
+impl<T> VerifiedVoteProposal<T> {
+    /// The epoch whose validator set verified this proposal
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The root of the transaction accumulator after executing the proposed block
+    pub fn executed_state_id(&self) -> HashValue {
+        self.executed_state_id
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn vote_proposal(&self) -> &VoteProposal<T> {
+        &self.vote_proposal
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use libra_types::{transaction::Version, waypoint::Waypoint};
+use serde::{Deserialize, Serialize};
+
+/// The number of waypoints retained in storage, older ones are dropped first.
+pub const MAX_WAYPOINT_HISTORY: usize = 64;
+
+/// A waypoint that SafetyRules has been bound to, along with the epoch it begins and when it was
+/// set.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WaypointRecord {
+    pub epoch: u64,
+    pub timestamp_ms: u64,
+    pub waypoint: Waypoint,
+}
+
+impl WaypointRecord {
+    /// The version of the epoch ending ledger info the waypoint commits to
+    pub fn version(&self) -> Version {
+        self.waypoint.version()
+    }
+}
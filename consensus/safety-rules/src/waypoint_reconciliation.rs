@@ -0,0 +1,99 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    fencing, persistent_safety_storage::PersistentSafetyStorage, recovery::RecoverySlot, Error,
+};
+use libra_logger::warn;
+use libra_types::{epoch_change::EpochChangeProof, waypoint::Waypoint};
+
+/// How the waypoint in storage was reconciled with the one in the config at startup.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WaypointReconciliation {
+    /// Both waypoints are the same
+    Unchanged,
+    /// The configured waypoint is newer and proven to follow from the stored one, which it has
+    /// replaced. SafetyRules refuses to sign until the recovery with the given id is confirmed.
+    Ratcheted(u64),
+    /// The configured waypoint is newer, but without a proof the stored one is kept
+    Unproven,
+    /// The configured waypoint is older and the stored one is kept, storage never moves back
+    Outdated,
+}
+
+/// Reconciles the waypoint of the storage with the configured waypoint:
+/// * equal waypoints need no change,
+/// * a newer configured waypoint replaces the stored one if the proof leads from the stored
+///   waypoint to it, and fails if the proof does not,
+/// * an older configured waypoint is ignored,
+/// * waypoints that differ at the same version are a hard error, as one of them is not part of the
+///   chain SafetyRules has been following.
+///
+/// Only the waypoint is ratcheted, the epoch and rounds in storage are advanced once SafetyRules
+/// is initialized into the new epoch. The SafetyData it replaces is held in the recovery slot, as
+/// for a reset to a waypoint, so a ratchet is refused while another operation awaits confirmation.
+pub fn reconcile_waypoint(
+    storage: &mut PersistentSafetyStorage,
+    configured: &Waypoint,
+    proof: Option<&EpochChangeProof>,
+) -> Result<WaypointReconciliation, Error> {
+    let stored = storage.waypoint()?;
+    if stored == *configured {
+        return Ok(WaypointReconciliation::Unchanged);
+    }
+    if stored.version() == configured.version() {
+        return Err(Error::WaypointMismatch(format!(
+            "Configured waypoint {} conflicts with stored waypoint {}",
+            configured, stored
+        )));
+    }
+    if configured.version() < stored.version() {
+        warn!(
+            "Ignoring configured waypoint {}, it is older than stored waypoint {}",
+            configured, stored
+        );
+        return Ok(WaypointReconciliation::Outdated);
+    }
+
+    let proof = match proof {
+        Some(proof) => proof,
+        None => {
+            warn!(
+                "Keeping stored waypoint {}, no proof leads to the newer configured waypoint {}",
+                stored, configured
+            );
+            return Ok(WaypointReconciliation::Unproven);
+        }
+    };
+    let ledger_info = proof
+        .verify(&stored)
+        .map_err(|e| Error::WaypointMismatch(format!("{}", e)))?
+        .ledger_info();
+    if Waypoint::new_epoch_boundary(ledger_info)? != *configured {
+        return Err(Error::WaypointMismatch(format!(
+            "The proof does not end at configured waypoint {}",
+            configured
+        )));
+    }
+    let epoch_state = ledger_info
+        .next_epoch_state()
+        .ok_or(Error::InvalidLedgerInfo)?;
+    if let Some(slot) = storage.recovery_slot()? {
+        return Err(Error::RecoveryRefused(format!(
+            "{} awaits confirmation",
+            slot
+        )));
+    }
+    let slot = RecoverySlot::new(
+        "waypoint_override",
+        fencing::now_ms(),
+        storage.safety_data_snapshot()?,
+    );
+    storage.set_recovery_slot(Some(&slot))?;
+    storage.set_waypoint(epoch_state.epoch, configured)?;
+    warn!(
+        "Ratcheted stored waypoint {} to configured waypoint {}, awaiting confirmation of {}",
+        stored, configured, slot
+    );
+    Ok(WaypointReconciliation::Ratcheted(slot.id))
+}
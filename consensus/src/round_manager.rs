@@ -495,7 +495,7 @@ impl<T: Payload> RoundManager<T> {
     /// This function is called only after all the dependencies of the given QC have been retrieved.
     async fn process_certificates(&mut self) -> anyhow::Result<()> {
         let sync_info = self.block_store.sync_info();
-        self.safety_rules.update(sync_info.highest_quorum_cert())?;
+        self.safety_rules.update_sync_info(&sync_info)?;
         let consensus_state = self.safety_rules.consensus_state()?;
         counters::PREFERRED_BLOCK_ROUND.set(consensus_state.preferred_round() as i64);
 
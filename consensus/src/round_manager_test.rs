@@ -315,7 +315,12 @@ fn vote_on_successful_proposal() {
         assert_eq!(vote_msg.vote().vote_data().proposed().id(), proposal_id);
         let consensus_state = node.round_manager.consensus_state();
         let waypoint = consensus_state.waypoint();
-        assert_eq!(consensus_state, ConsensusState::new(1, 1, 0, waypoint));
+        let signature_counts = consensus_state.signature_counts();
+        assert_eq!(
+            consensus_state,
+            ConsensusState::new(1, 1, 0, waypoint).with_signature_counts(signature_counts)
+        );
+        assert_eq!(signature_counts.lifetime_count.votes, 1);
     });
 }
 
@@ -640,9 +645,10 @@ fn recover_on_restart() {
     node = node.restart(&mut playground, runtime.handle().clone());
     let consensus_state = node.round_manager.consensus_state();
     let waypoint = consensus_state.waypoint();
+    let signature_counts = consensus_state.signature_counts();
     assert_eq!(
         consensus_state,
-        ConsensusState::new(1, num_proposals, 0, waypoint)
+        ConsensusState::new(1, num_proposals, 0, waypoint).with_signature_counts(signature_counts)
     );
     for (block, _) in data {
         assert_eq!(node.block_store.block_exists(block.id()), true);
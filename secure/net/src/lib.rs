@@ -17,7 +17,7 @@
 
 use libra_logger::{debug, trace};
 use std::{
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
     net::{Shutdown, SocketAddr, TcpListener, TcpStream},
     thread, time,
 };
@@ -128,6 +128,21 @@ impl NetworkServer {
         result
     }
 
+    /// Returns a message if an entire one has already arrived from the current downstream client,
+    /// without blocking or accepting a new client.
+    pub fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        let result = stream.try_read();
+        if result.is_err() {
+            debug!("On read, downstream peer disconnected, setting stream to None");
+            self.stream = None;
+        }
+        result
+    }
+
     /// Shutdown the internal network stream
     pub fn shutdown(&mut self) -> Result<(), Error> {
         debug!("Shutdown called");
@@ -149,6 +164,13 @@ impl NetworkServer {
         result
     }
 
+    /// The address of the downstream client, if one is connected
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.stream
+            .as_ref()
+            .and_then(|stream| stream.stream.peer_addr().ok())
+    }
+
     fn client(&mut self) -> Result<&mut NetworkStream, Error> {
         if self.stream.is_none() {
             debug!("Waiting for downstream to connect");
@@ -205,6 +227,33 @@ impl NetworkStream {
         }
     }
 
+    /// Non-blocking read that returns a message only if one has been received in its entirety
+    pub fn try_read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        self.stream.set_nonblocking(true)?;
+        let result = self.try_read_nonblocking();
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+
+    fn try_read_nonblocking(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            let result = self.read_buffer();
+            if !result.is_empty() {
+                return Ok(Some(result));
+            }
+            let read = match self.stream.read(&mut self.temp_buffer) {
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            trace!("Read {} bytes from stream", read);
+            if read == 0 {
+                return Err(Error::RemoteStreamClosed);
+            }
+            self.buffer.extend(self.temp_buffer[..read].to_vec());
+        }
+    }
+
     /// Terminate the socket
     pub fn shutdown(&self) -> Result<(), Error> {
         Ok(self.stream.shutdown(Shutdown::Both)?)
@@ -289,6 +338,26 @@ mod test {
         assert_eq!(data, result);
     }
 
+    #[test]
+    fn test_try_read() {
+        let server_port = utils::get_available_port();
+        let server_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), server_port);
+        let mut server = NetworkServer::new(server_addr);
+        let mut client = NetworkClient::new(server_addr);
+
+        assert!(server.try_read().unwrap().is_none());
+        client.write(&[0, 1]).unwrap();
+        client.write(&[2, 3]).unwrap();
+        assert_eq!(server.read().unwrap(), vec![0, 1]);
+        let mut result = server.try_read().unwrap();
+        while result.is_none() {
+            thread::sleep(time::Duration::from_millis(10));
+            result = server.try_read().unwrap();
+        }
+        assert_eq!(result.unwrap(), vec![2, 3]);
+        assert!(server.try_read().unwrap().is_none());
+    }
+
     #[test]
     fn test_client_shutdown() {
         let server_port = utils::get_available_port();
@@ -17,6 +17,8 @@ pub enum Error {
     KeyNotSet(String),
     #[error("Permission denied")]
     PermissionDenied,
+    #[error("Storage locked: {0}")]
+    StorageLocked(String),
     #[error("Serialization error: {0}")]
     SerializationError(String),
     #[error("Unexpected value type")]
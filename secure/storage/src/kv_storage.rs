@@ -20,6 +20,13 @@ pub trait KVStorage: Send + Sync {
     /// invalid permissions.
     fn set(&mut self, key: &str, value: Value) -> Result<(), Error>;
 
+    /// Sets a value in storage as set does, but the value is durable once this returns even if the
+    /// backend is configured to defer persisting writes. By default this is set, for backends that
+    /// persist every write before returning.
+    fn set_durable(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        self.set(key, value)
+    }
+
     /// Resets and clears all data held in the storage engine.
     /// Note: this should only be exposed and used for testing. Resetting the storage engine is not
     /// something that should be supported in production.
@@ -4,7 +4,7 @@
 #![forbid(unsafe_code)]
 
 use libra_config::config::SecureBackend;
-use std::convert::From;
+use std::convert::TryFrom;
 
 mod crypto_kv_storage;
 mod crypto_storage;
@@ -15,6 +15,8 @@ mod kv_storage;
 mod namespaced_storage;
 mod on_disk;
 mod policy;
+#[cfg(any(test, feature = "testing"))]
+mod proxy_storage;
 mod storage;
 mod value;
 mod vault;
@@ -34,14 +36,26 @@ pub use crate::{
     vault::VaultStorage,
 };
 
-impl From<&SecureBackend> for Box<dyn Storage> {
-    fn from(backend: &SecureBackend) -> Self {
-        match backend {
+#[cfg(any(test, feature = "testing"))]
+pub use crate::proxy_storage::{Fault, FaultInjector, Operation, ProxyStorage};
+
+/// Opening a backend fails if its token cannot be read, or, e.g., with StorageLocked if the storage
+/// is held by another instance.
+impl TryFrom<&SecureBackend> for Box<dyn Storage> {
+    type Error = Error;
+
+    fn try_from(backend: &SecureBackend) -> Result<Self, Error> {
+        let read_token = |token: &libra_config::config::Token| {
+            token
+                .read_token()
+                .map_err(|e| Error::InternalError(format!("Unable to read token: {}", e)))
+        };
+        let storage: Self = match backend {
             SecureBackend::GitHub(config) => {
                 let storage = GitHubStorage::new(
                     config.owner.clone(),
                     config.repository.clone(),
-                    config.token.read_token().expect("Unable to read token"),
+                    read_token(&config.token)?,
                 );
                 if let Some(namespace) = &config.namespace {
                     Box::new(NamespacedStorage::new(storage, namespace.clone()))
@@ -51,23 +65,34 @@ impl From<&SecureBackend> for Box<dyn Storage> {
             }
             SecureBackend::InMemoryStorage => Box::new(InMemoryStorage::new()),
             SecureBackend::OnDiskStorage(config) => {
-                let storage = OnDiskStorage::new(config.path());
+                let mut storage = if config.lock {
+                    OnDiskStorage::new_locked(config.path())?
+                } else {
+                    OnDiskStorage::new(config.path())
+                };
+                storage.set_fsync_policy(config.fsync);
                 if let Some(namespace) = &config.namespace {
                     Box::new(NamespacedStorage::new(storage, namespace.clone()))
                 } else {
                     Box::new(storage)
                 }
             }
-            SecureBackend::Vault(config) => Box::new(VaultStorage::new(
-                config.server.clone(),
-                config.token.read_token().expect("Unable to read token"),
-                config.namespace.clone(),
-                config
-                    .ca_certificate
-                    .as_ref()
-                    .map(|_| config.ca_certificate().unwrap()),
-            )),
-        }
+            SecureBackend::Vault(config) => {
+                let ca_certificate = match &config.ca_certificate {
+                    Some(_) => Some(config.ca_certificate().map_err(|e| {
+                        Error::InternalError(format!("Unable to read CA certificate: {}", e))
+                    })?),
+                    None => None,
+                };
+                Box::new(VaultStorage::new(
+                    config.server.clone(),
+                    read_token(&config.token)?,
+                    config.namespace.clone(),
+                    ca_certificate,
+                ))
+            }
+        };
+        Ok(storage)
     }
 }
 
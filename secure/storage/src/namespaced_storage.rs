@@ -25,6 +25,10 @@ impl<T: KVStorage> KVStorage for NamespacedStorage<T> {
         self.inner.set(&self.ns_name(key), value)
     }
 
+    fn set_durable(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        self.inner.set_durable(&self.ns_name(key), value)
+    }
+
     /// Note: This is not a namespace function
     #[cfg(any(test, feature = "testing"))]
     fn reset_and_clear(&mut self) -> Result<(), Error> {
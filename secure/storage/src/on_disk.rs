@@ -2,15 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{CryptoKVStorage, Error, GetResponse, KVStorage, Value};
+use libra_config::config::FsyncPolicy;
 use libra_secure_time::{RealTimeService, TimeService};
 use libra_temppath::TempPath;
 use std::{
     collections::HashMap,
-    fs::{self, File},
-    io::{Read, Write},
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
     path::PathBuf,
 };
 
+/// The number of writes FsyncPolicy::Batched leaves unsynced. As every write replaces the whole
+/// file, a sync makes all earlier writes durable as well.
+const FSYNC_BATCH_SIZE: usize = 16;
+
 /// OnDiskStorage represents a key value store that is persisted to the local filesystem and is
 /// intended for single threads (or must be wrapped by a Arc<RwLock<>>). This provides no permission
 /// checks and simply offers a proof of concept to unblock building of applications without more
@@ -21,15 +27,30 @@ use std::{
 pub type OnDiskStorage = OnDiskStorageInternal<RealTimeService>;
 
 pub struct OnDiskStorageInternal<T> {
+    file_dir: PathBuf,
     file_path: PathBuf,
+    fsync_policy: FsyncPolicy,
+    lock: Option<FileLock>,
     temp_path: TempPath,
     time_service: T,
+    unsynced_writes: usize,
 }
 
 impl OnDiskStorageInternal<RealTimeService> {
     pub fn new(file_path: PathBuf) -> Self {
         Self::new_with_time_service(file_path, RealTimeService::new())
     }
+
+    /// Opens the storage for the exclusive use of this instance, guarded by an advisory lock file
+    /// next to it, e.g., secure_storage.toml.lock. Fails with StorageLocked if another instance,
+    /// possibly in another process, holds the lock. Every subsequent operation fails with
+    /// StorageLocked as well if the lock file is removed or taken over by someone else.
+    pub fn new_locked(file_path: PathBuf) -> Result<Self, Error> {
+        let lock = FileLock::acquire(&file_path)?;
+        let mut storage = Self::new(file_path);
+        storage.lock = Some(lock);
+        Ok(storage)
+    }
 }
 
 impl<T: TimeService> OnDiskStorageInternal<T> {
@@ -44,14 +65,47 @@ impl<T: TimeService> OnDiskStorageInternal<T> {
             .parent()
             .map_or(PathBuf::new(), |p| p.to_path_buf());
 
+        let temp_path = TempPath::new_with_temp_dir(file_dir.clone());
+
         Self {
+            file_dir,
             file_path,
-            temp_path: TempPath::new_with_temp_dir(file_dir),
+            fsync_policy: FsyncPolicy::Always,
+            lock: None,
+            temp_path,
             time_service,
+            unsynced_writes: 0,
         }
     }
 
+    /// Sets when writes are synced to disk, writes made with set_durable are always synced.
+    pub fn set_fsync_policy(&mut self, fsync_policy: FsyncPolicy) {
+        self.fsync_policy = fsync_policy;
+    }
+
+    fn requires_sync(&mut self, durable: bool) -> bool {
+        let sync = durable
+            || match self.fsync_policy {
+                FsyncPolicy::Always => true,
+                FsyncPolicy::Batched => self.unsynced_writes + 1 >= FSYNC_BATCH_SIZE,
+                FsyncPolicy::Never => false,
+            };
+        self.unsynced_writes = if sync { 0 } else { self.unsynced_writes + 1 };
+        sync
+    }
+
+    fn set_internal(&mut self, key: &str, value: Value, durable: bool) -> Result<(), Error> {
+        let mut data = self.read()?;
+        data.insert(
+            key.to_string(),
+            GetResponse::new(value, self.time_service.now()),
+        );
+        let sync = self.requires_sync(durable);
+        self.write(&data, sync)
+    }
+
     fn read(&self) -> Result<HashMap<String, GetResponse>, Error> {
+        self.verify_lock()?;
         let mut file = File::open(&self.file_path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
@@ -62,13 +116,91 @@ impl<T: TimeService> OnDiskStorageInternal<T> {
         Ok(data)
     }
 
-    fn write(&self, data: &HashMap<String, GetResponse>) -> Result<(), Error> {
+    /// Replaces the file with the data, if synced the data is durable once this returns.
+    fn write(&self, data: &HashMap<String, GetResponse>, sync: bool) -> Result<(), Error> {
+        self.verify_lock()?;
         let contents = serde_json::to_vec(data)?;
         let mut file = File::create(self.temp_path.path())?;
         file.write_all(&contents)?;
+        if sync {
+            file.sync_all()?;
+        }
         fs::rename(&self.temp_path, &self.file_path)?;
+        if sync {
+            // The rename is only durable once the directory holding the file is synced
+            let dir = if self.file_dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                self.file_dir.clone()
+            };
+            File::open(dir)?.sync_all()?;
+        }
         Ok(())
     }
+
+    fn verify_lock(&self) -> Result<(), Error> {
+        self.lock.as_ref().map_or(Ok(()), |lock| lock.verify())
+    }
+}
+
+/// An advisory lock over an OnDiskStorage file. It is a lock file created exclusively next to the
+/// storage file and holding a token unique to its owner, so that it works the same on every
+/// platform and filesystem, including those shared between containers, without relying on OS
+/// level locks. A lock left behind by a crashed process must be removed by the operator.
+struct FileLock {
+    path: PathBuf,
+    token: String,
+}
+
+impl FileLock {
+    fn acquire(file_path: &PathBuf) -> Result<Self, Error> {
+        let mut path = OsString::from(file_path.as_os_str());
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        let token = format!("{}:{:016x}", std::process::id(), rand::random::<u64>());
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let holder = fs::read_to_string(&path).unwrap_or_default();
+                return Err(Error::StorageLocked(format!(
+                    "{} is held by {}, remove it if no other process uses this storage",
+                    path.display(),
+                    holder
+                )));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(token.as_bytes())?;
+        file.sync_all()?;
+        Ok(Self { path, token })
+    }
+
+    /// Ensures the lock file still exists and holds this lock's token.
+    fn verify(&self) -> Result<(), Error> {
+        match fs::read_to_string(&self.path) {
+            Ok(token) if token == self.token => Ok(()),
+            Ok(token) => Err(Error::StorageLocked(format!(
+                "{} has been taken over by {}",
+                self.path.display(),
+                token
+            ))),
+            Err(e) => Err(Error::StorageLocked(format!(
+                "{} is no longer readable: {}",
+                self.path.display(),
+                e
+            ))),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Never remove a lock that has been taken over by someone else
+        if self.verify().is_ok() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
 }
 
 impl<T: Send + Sync + TimeService> KVStorage for OnDiskStorageInternal<T> {
@@ -83,17 +215,16 @@ impl<T: Send + Sync + TimeService> KVStorage for OnDiskStorageInternal<T> {
     }
 
     fn set(&mut self, key: &str, value: Value) -> Result<(), Error> {
-        let mut data = self.read()?;
-        data.insert(
-            key.to_string(),
-            GetResponse::new(value, self.time_service.now()),
-        );
-        self.write(&data)
+        self.set_internal(key, value, false)
+    }
+
+    fn set_durable(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        self.set_internal(key, value, true)
     }
 
     #[cfg(any(test, feature = "testing"))]
     fn reset_and_clear(&mut self) -> Result<(), Error> {
-        self.write(&HashMap::new())
+        self.write(&HashMap::new(), true)
     }
 }
 
@@ -0,0 +1,264 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{CryptoStorage, Error, GetResponse, KVStorage, PublicKeyResponse, Value};
+use libra_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
+    HashValue,
+};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// The storage operations that faults can be injected into.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operation {
+    Available,
+    CreateKey,
+    ExportPrivateKey,
+    Get,
+    GetPublicKey,
+    RotateKey,
+    Set,
+    SignMessage,
+}
+
+/// A fault applied to the storage operations that match it, either delaying them, failing them,
+/// or both. By default a fault applies to every matching operation on any key until cleared.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    operation: Operation,
+    key: Option<String>,
+    skip: usize,
+    count: Option<usize>,
+    latency: Duration,
+    fail: bool,
+}
+
+impl Fault {
+    /// Fails matching operations with an InternalError, without forwarding them to the backend.
+    pub fn error(operation: Operation) -> Self {
+        Self {
+            operation,
+            key: None,
+            skip: 0,
+            count: None,
+            latency: Duration::from_millis(0),
+            fail: true,
+        }
+    }
+
+    /// Delays matching operations before forwarding them to the backend.
+    pub fn latency(operation: Operation, latency: Duration) -> Self {
+        Self {
+            latency,
+            fail: false,
+            ..Self::error(operation)
+        }
+    }
+
+    /// Only applies to operations on the given key or key pair name.
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    /// Lets the first `skip` matching operations through untouched.
+    pub fn with_skip(mut self, skip: usize) -> Self {
+        self.skip = skip;
+        self
+    }
+
+    /// Makes the fault transient, it is removed after applying to `count` operations.
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    fn matches(&self, operation: Operation, key: Option<&str>) -> bool {
+        self.operation == operation
+            && match &self.key {
+                Some(fault_key) => key == Some(fault_key.as_str()),
+                None => true,
+            }
+    }
+}
+
+/// A handle to the faults of a ProxyStorage, which remains usable once the storage has been
+/// handed off, e.g., to a PersistentSafetyStorage.
+#[derive(Clone, Default)]
+pub struct FaultInjector {
+    faults: Arc<Mutex<Vec<Fault>>>,
+}
+
+impl FaultInjector {
+    pub fn inject(&self, fault: Fault) {
+        self.faults.lock().unwrap().push(fault);
+    }
+
+    pub fn clear(&self) {
+        self.faults.lock().unwrap().clear();
+    }
+
+    /// Applies every fault matching the operation, returns an error if any of them fails it.
+    fn apply(&self, operation: Operation, key: Option<&str>) -> Result<(), Error> {
+        let mut latency = Duration::from_millis(0);
+        let mut fail = false;
+        {
+            let mut faults = self.faults.lock().unwrap();
+            for fault in faults
+                .iter_mut()
+                .filter(|fault| fault.matches(operation, key))
+            {
+                if fault.skip > 0 {
+                    fault.skip -= 1;
+                    continue;
+                }
+                latency += fault.latency;
+                fail |= fault.fail;
+                if let Some(count) = &mut fault.count {
+                    *count = count.saturating_sub(1);
+                }
+            }
+            faults.retain(|fault| fault.count != Some(0));
+        }
+
+        if latency > Duration::from_millis(0) {
+            thread::sleep(latency);
+        }
+        if fail {
+            Err(Error::InternalError(format!(
+                "Injected fault for {:?} on {}",
+                operation,
+                key.unwrap_or("storage")
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Wraps a storage backend and injects latency and errors into its operations, so that tests can
+/// reliably reproduce slow or failing storage, such as a crash between two dependent writes.
+pub struct ProxyStorage<T> {
+    faults: FaultInjector,
+    inner: T,
+}
+
+impl<T> ProxyStorage<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            faults: FaultInjector::default(),
+            inner,
+        }
+    }
+
+    pub fn faults(&self) -> FaultInjector {
+        self.faults.clone()
+    }
+}
+
+impl<T: KVStorage> KVStorage for ProxyStorage<T> {
+    fn available(&self) -> Result<(), Error> {
+        self.faults.apply(Operation::Available, None)?;
+        self.inner.available()
+    }
+
+    fn get(&self, key: &str) -> Result<GetResponse, Error> {
+        self.faults.apply(Operation::Get, Some(key))?;
+        self.inner.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        self.faults.apply(Operation::Set, Some(key))?;
+        self.inner.set(key, value)
+    }
+
+    fn set_durable(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        self.faults.apply(Operation::Set, Some(key))?;
+        self.inner.set_durable(key, value)
+    }
+
+    /// Note: Faults are not injected into resets
+    fn reset_and_clear(&mut self) -> Result<(), Error> {
+        self.inner.reset_and_clear()
+    }
+}
+
+impl<T: CryptoStorage> CryptoStorage for ProxyStorage<T> {
+    fn create_key(&mut self, name: &str) -> Result<Ed25519PublicKey, Error> {
+        self.faults.apply(Operation::CreateKey, Some(name))?;
+        self.inner.create_key(name)
+    }
+
+    fn export_private_key(&self, name: &str) -> Result<Ed25519PrivateKey, Error> {
+        self.faults.apply(Operation::ExportPrivateKey, Some(name))?;
+        self.inner.export_private_key(name)
+    }
+
+    fn export_private_key_for_version(
+        &self,
+        name: &str,
+        version: Ed25519PublicKey,
+    ) -> Result<Ed25519PrivateKey, Error> {
+        self.faults.apply(Operation::ExportPrivateKey, Some(name))?;
+        self.inner.export_private_key_for_version(name, version)
+    }
+
+    fn get_public_key(&self, name: &str) -> Result<PublicKeyResponse, Error> {
+        self.faults.apply(Operation::GetPublicKey, Some(name))?;
+        self.inner.get_public_key(name)
+    }
+
+    fn rotate_key(&mut self, name: &str) -> Result<Ed25519PublicKey, Error> {
+        self.faults.apply(Operation::RotateKey, Some(name))?;
+        self.inner.rotate_key(name)
+    }
+
+    fn sign_message(&mut self, name: &str, message: &HashValue) -> Result<Ed25519Signature, Error> {
+        self.faults.apply(Operation::SignMessage, Some(name))?;
+        self.inner.sign_message(name, message)
+    }
+
+    fn sign_message_using_version(
+        &mut self,
+        name: &str,
+        version: Ed25519PublicKey,
+        message: &HashValue,
+    ) -> Result<Ed25519Signature, Error> {
+        self.faults.apply(Operation::SignMessage, Some(name))?;
+        self.inner
+            .sign_message_using_version(name, version, message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::InMemoryStorage;
+
+    #[test]
+    fn test_faults() {
+        let mut storage = ProxyStorage::new(InMemoryStorage::new());
+        let faults = storage.faults();
+        faults.inject(
+            Fault::error(Operation::Set)
+                .with_key("a")
+                .with_skip(1)
+                .with_count(1),
+        );
+
+        storage.set("a", Value::U64(0)).unwrap();
+        storage.set("a", Value::U64(1)).unwrap_err();
+        storage.set("b", Value::U64(2)).unwrap();
+        storage.set("a", Value::U64(3)).unwrap();
+        assert_eq!(storage.get("a").unwrap().value, Value::U64(3));
+
+        faults.inject(Fault::error(Operation::Get));
+        storage.get("b").unwrap_err();
+        faults.clear();
+        assert_eq!(storage.get("b").unwrap().value, Value::U64(2));
+    }
+}
@@ -31,6 +31,10 @@ impl KVStorage for BoxStorage {
         self.0.set(key, value)
     }
 
+    fn set_durable(&mut self, key: &str, value: Value) -> Result<(), Error> {
+        self.0.set_durable(key, value)
+    }
+
     #[cfg(any(test, feature = "testing"))]
     fn reset_and_clear(&mut self) -> Result<(), Error> {
         self.0.reset_and_clear()
@@ -1,8 +1,10 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{tests::suite, OnDiskStorage};
+use crate::{tests::suite, Error, KVStorage, OnDiskStorage, Value};
+use libra_config::config::FsyncPolicy;
 use libra_temppath::TempPath;
+use std::fs;
 
 #[test]
 fn on_disk() {
@@ -10,3 +12,53 @@ fn on_disk() {
     let mut storage = Box::new(OnDiskStorage::new(path_buf));
     suite::execute_all_storage_tests(storage.as_mut());
 }
+
+#[test]
+fn on_disk_fsync_policies() {
+    for policy in &[FsyncPolicy::Batched, FsyncPolicy::Never] {
+        let temp_path = TempPath::new();
+        let mut storage = OnDiskStorage::new(temp_path.path().to_path_buf());
+        storage.set_fsync_policy(*policy);
+        suite::execute_all_storage_tests(&mut storage);
+
+        for round in 0..20 {
+            storage.set("round", Value::U64(round)).unwrap();
+        }
+        storage.set_durable("durable", Value::U64(1)).unwrap();
+        let storage = OnDiskStorage::new(temp_path.path().to_path_buf());
+        assert_eq!(storage.get("round").unwrap().value, Value::U64(19));
+        assert_eq!(storage.get("durable").unwrap().value, Value::U64(1));
+    }
+}
+
+#[test]
+fn on_disk_locked() {
+    let temp_path = TempPath::new();
+    let path_buf = temp_path.path().to_path_buf();
+    let lock_path = path_buf.with_file_name(format!(
+        "{}.lock",
+        path_buf.file_name().unwrap().to_str().unwrap()
+    ));
+
+    let mut storage = OnDiskStorage::new_locked(path_buf.clone()).unwrap();
+    storage.set("key", Value::U64(1)).unwrap();
+    match OnDiskStorage::new_locked(path_buf.clone()) {
+        Err(Error::StorageLocked(_)) => (),
+        _ => panic!("Expected the storage to be locked"),
+    }
+
+    // Another process stealing the lock fences this one off
+    fs::write(&lock_path, "stolen").unwrap();
+    match storage.set("key", Value::U64(2)) {
+        Err(Error::StorageLocked(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    drop(storage);
+    assert!(lock_path.exists());
+
+    fs::remove_file(&lock_path).unwrap();
+    let storage = OnDiskStorage::new_locked(path_buf).unwrap();
+    assert_eq!(storage.get("key").unwrap().value, Value::U64(1));
+    drop(storage);
+    assert!(!lock_path.exists());
+}
@@ -24,6 +24,7 @@ const STORAGE_TESTS: &[fn(&mut dyn Storage)] = &[
     test_get_uncreated_key_pair,
     test_hash_value,
     test_incremental_timestamp,
+    test_set_durable,
     test_verify_incorrect_value_types,
 ];
 
@@ -100,6 +101,15 @@ fn test_get_set(storage: &mut dyn Storage) {
     );
 }
 
+/// This test ensures that values set durably are retrieved like any other value.
+fn test_set_durable(storage: &mut dyn Storage) {
+    storage.set_durable(U64_KEY, Value::U64(10)).unwrap();
+    assert_eq!(storage.get(U64_KEY).unwrap().value.u64().unwrap(), 10);
+    storage.set(U64_KEY, Value::U64(647)).unwrap();
+    storage.set_durable(U64_KEY, Value::U64(648)).unwrap();
+    assert_eq!(storage.get(U64_KEY).unwrap().value.u64().unwrap(), 648);
+}
+
 /// This test stores different types of values into storage, retrieves them, and asserts
 /// that the value unwrap functions return an unexpected type error on an incorrect unwrap.
 fn test_verify_incorrect_value_types(storage: &mut dyn Storage) {
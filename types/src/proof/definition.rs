@@ -797,6 +797,14 @@ impl<H: CryptoHasher> AccumulatorExtensionProof<H> {
         Ok(original_tree.append(self.leaves.as_slice()))
     }
 
+    pub fn frozen_subtree_roots(&self) -> &Vec<HashValue> {
+        &self.frozen_subtree_roots
+    }
+
+    pub fn num_leaves(&self) -> LeafCount {
+        self.num_leaves
+    }
+
     pub fn leaves(&self) -> &Vec<HashValue> {
         &self.leaves
     }